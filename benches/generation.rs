@@ -0,0 +1,33 @@
+#![feature(test)]
+
+extern crate pumpkin;
+extern crate rand;
+extern crate test;
+
+use pumpkin::{prime, safe_prime};
+use rand::rngs::OsRng;
+use test::Bencher;
+
+#[bench]
+fn bench_generate_512_bit_prime(b: &mut Bencher) {
+    let mut rngesus = OsRng::new().unwrap();
+    b.iter(|| prime::from_rng(512, &mut rngesus));
+}
+
+#[bench]
+fn bench_generate_1024_bit_prime(b: &mut Bencher) {
+    let mut rngesus = OsRng::new().unwrap();
+    b.iter(|| prime::from_rng(1024, &mut rngesus));
+}
+
+#[bench]
+fn bench_generate_2048_bit_prime(b: &mut Bencher) {
+    let mut rngesus = OsRng::new().unwrap();
+    b.iter(|| prime::from_rng(2048, &mut rngesus));
+}
+
+#[bench]
+fn bench_generate_512_bit_safe_prime(b: &mut Bencher) {
+    let mut rngesus = OsRng::new().unwrap();
+    b.iter(|| safe_prime::from_rng(512, &mut rngesus));
+}
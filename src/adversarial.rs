@@ -0,0 +1,633 @@
+//! Constructs composites that are built to fool probabilistic primality
+//! tests, for testing this crate's tests and anyone else's.
+//!
+//! The following constructions are available:
+//!
+//! * `carmichael()`: Chernick's Carmichael numbers, `(6k + 1)(12k + 1)(18k +
+//!   1)` with all three factors prime. They pass the Fermat test to every
+//!   base coprime to them, and only those that Miller-Rabin to base 2
+//!   rejects are returned, so each one passes `fermat` but fails MR.
+//!
+//! * `strong_pseudoprime()`: Arnault's construction of a product of three
+//!   primes `p`, `k2 (p - 1) + 1` and `k3 (p - 1) + 1` that is a strong
+//!   pseudoprime to every one of a chosen set of prime bases. Miller-Rabin
+//!   with those bases alone accepts it.
+//!
+//! * `lucas_pseudoprime()`: Lehmer's Fibonacci numbers `F(2p) = F(p) L(p)`,
+//!   which are Lucas pseudoprimes with `P = 1` and `Q = -1` for every prime
+//!   `p > 5`.
+//!
+//! * `strong_lucas_pseudoprimes()`: every strong Lucas pseudoprime with
+//!   Selfridge's parameters, as used by Baillie-PSW, below a limit.
+//!
+//! `to_json()` exports any of them as test vectors in the format of Project
+//! Wycheproof's `primality_test.json`.
+
+use ramp::Int;
+
+use rand::Rng;
+
+use std::fmt;
+
+use backend::BigInt;
+use common::reference::{self, Natural};
+use common::{is_prime_with_rng, miller_rabin_with_bases, random_bits};
+use error::{Error, Result};
+use prime::small::is_prime_u32;
+
+/// The smallest bit length of the composites constructed here.
+pub const MIN_BIT_LENGTH: usize = 64;
+
+/// The largest multiplier `k` tried by `strong_pseudoprime()`.
+const MAX_MULTIPLIER: u32 = 1000;
+
+/// The largest base accepted by `strong_pseudoprime()`.
+const MAX_BASE: u32 = 1000;
+
+/// The construction a pseudoprime came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// A Carmichael number.
+    Carmichael,
+    /// A strong pseudoprime to a set of bases.
+    StrongPseudoprime,
+    /// A Lucas pseudoprime.
+    LucasPseudoprime,
+    /// A strong Lucas pseudoprime with Selfridge's parameters.
+    StrongLucasPseudoprime,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Kind::Carmichael => write!(f, "CarmichaelNumber"),
+            Kind::StrongPseudoprime => write!(f, "StrongPseudoprime"),
+            Kind::LucasPseudoprime => write!(f, "LucasPseudoprime"),
+            Kind::StrongLucasPseudoprime => write!(f, "StrongLucasPseudoprime"),
+        }
+    }
+}
+
+/// A composite built to pass one or more probabilistic primality tests.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pseudoprime {
+    /// The composite itself.
+    pub value: Int,
+    /// Its factors, whose product is `value`.
+    pub factors: Vec<Int>,
+    /// The construction it came from.
+    pub kind: Kind,
+    /// The bases of the Miller-Rabin test that it is known to pass, if any.
+    pub bases: Vec<u32>,
+    /// A description of how it was built.
+    pub comment: String,
+}
+
+impl Pseudoprime {
+    /// Returns the flags of the Wycheproof test vector: the kind, plus
+    /// `Base2FermatPseudoprime` and `Base2StrongPseudoprime` for each of the
+    /// tests to base 2 that it passes.
+    pub fn flags(&self) -> Vec<String> {
+        let one = Int::one();
+        let minus_one = &self.value - 1_usize;
+        let mut flags = vec![self.kind.to_string()];
+
+        if Int::from(2).pow_mod(&minus_one, &self.value) == one {
+            flags.push("Base2FermatPseudoprime".to_string());
+        }
+        if miller_rabin_with_bases(&self.value, &[2]) {
+            flags.push("Base2StrongPseudoprime".to_string());
+        }
+
+        flags
+    }
+}
+
+/// Constructs a Chernick Carmichael number of exactly `bit_length` bits,
+/// that Miller-Rabin to base 2 rejects.
+pub fn carmichael<R: Rng + ?Sized>(bit_length: usize, rngesus: &mut R) -> Result<Pseudoprime> {
+    check_bit_length(bit_length)?;
+
+    // The product is about 1296 k^3, and 1296 is just over 2^10.
+    let k_bits = (bit_length - 10) / 3;
+
+    loop {
+        let mut k: Int = random_bits(rngesus, k_bits)?;
+        k.set_bit(k_bits as u32 - 1, true);
+
+        let factors: Vec<Int> = [6_usize, 12, 18]
+            .iter()
+            .map(|&multiplier| &k * multiplier + 1_usize)
+            .collect();
+        let value: Int = factors
+            .iter()
+            .fold(Int::one(), |product, factor| product * factor);
+
+        if value.bit_length() as usize != bit_length
+            || !factors
+                .iter()
+                .all(|factor| is_prime_with_rng(factor, rngesus))
+            || miller_rabin_with_bases(&value, &[2])
+        {
+            continue;
+        }
+
+        return Ok(Pseudoprime {
+            comment: format!("Chernick Carmichael number with k = {}", k),
+            value,
+            factors,
+            kind: Kind::Carmichael,
+            bases: Vec::new(),
+        });
+    }
+}
+
+/// Constructs a strong pseudoprime to every one of the prime `bases`, of
+/// exactly `bit_length` bits, by Arnault's method.
+///
+/// The three factors must each be 3 mod 4, and have every base as a
+/// quadratic non-residue. Then each base to the power `(n - 1) / 2` is -1
+/// modulo every factor, as long as `p - 1` divides `n - 1` for each factor
+/// `p`. Both are arranged by fixing the residue of the first factor modulo
+/// four times each base and modulo the multipliers `k2` and `k3`.
+///
+/// Fails with `Error::InvalidParameter` if `bases` is empty, repeats itself,
+/// or holds anything other than primes below 1000, or if `bit_length` leaves
+/// too little room for the congruences.
+pub fn strong_pseudoprime<R: Rng + ?Sized>(
+    bases: &[u32],
+    bit_length: usize,
+    rngesus: &mut R,
+) -> Result<Pseudoprime> {
+    check_bit_length(bit_length)?;
+    check_bases(bases)?;
+
+    let (k2, k3, congruences) =
+        arnault_congruences(bases).ok_or_else(|| Error::InvalidParameter {
+            name: "bases",
+            reason: "no multipliers satisfy the congruences".to_string(),
+        })?;
+
+    let modulus = congruences
+        .iter()
+        .fold(Int::one(), |product, &(modulus, _)| {
+            product * modulus as usize
+        });
+    let multipliers = [1, k2, k3];
+
+    // n is about k2 k3 p^3.
+    let k_bits = (u64::from(k2) * u64::from(k3)).leading_zeros();
+    let p_bits = (bit_length + k_bits as usize - 64) / 3;
+    let room = p_bits
+        .checked_sub(modulus.bit_length() as usize)
+        .filter(|&room| room >= 8)
+        .ok_or_else(|| Error::InvalidParameter {
+            name: "bit_length",
+            reason: format!("too small for {} bases", bases.len()),
+        })?;
+
+    loop {
+        let residue = crt(&congruences, rngesus);
+        let t: Int = random_bits(rngesus, room + 1)?;
+        let p: Int = residue + t * &modulus;
+
+        let factors: Vec<Int> = multipliers
+            .iter()
+            .map(|&k| (&p - 1_usize) * k as usize + 1_usize)
+            .collect();
+        let value: Int = factors
+            .iter()
+            .fold(Int::one(), |product, factor| product * factor);
+
+        if value.bit_length() as usize != bit_length
+            || !factors
+                .iter()
+                .all(|factor| is_prime_with_rng(factor, rngesus))
+            || !bases
+                .iter()
+                .all(|&basis| miller_rabin_with_bases(&value, &[basis]))
+        {
+            continue;
+        }
+
+        let mut sorted = bases.to_vec();
+        sorted.sort_unstable();
+
+        return Ok(Pseudoprime {
+            comment: format!(
+                "Arnault strong pseudoprime to bases {:?}, with k2 = {} and k3 = {}",
+                sorted, k2, k3
+            ),
+            value,
+            factors,
+            kind: Kind::StrongPseudoprime,
+            bases: sorted,
+        });
+    }
+}
+
+/// Constructs the Lucas pseudoprime `F(2p) = F(p) L(p)`, for a prime
+/// `p > 5`.
+///
+/// Fails with `Error::InvalidParameter` for any other `p`.
+pub fn lucas_pseudoprime(p: u32) -> Result<Pseudoprime> {
+    if p <= 5 || !is_prime_u32(p) {
+        return Err(Error::InvalidParameter {
+            name: "p",
+            reason: "must be a prime above 5".to_string(),
+        });
+    }
+
+    // (F(i - 1), F(i)), starting from i = 1.
+    let (mut previous, mut current) = (Int::zero(), Int::one());
+    for _ in 1..p {
+        let next = &previous + &current;
+        previous = current;
+        current = next;
+    }
+
+    let fibonacci = current.clone();
+    let lucas = &previous + &previous + &current;
+
+    Ok(Pseudoprime {
+        value: &fibonacci * &lucas,
+        factors: vec![fibonacci, lucas],
+        kind: Kind::LucasPseudoprime,
+        bases: Vec::new(),
+        comment: format!("Fibonacci number F(2p) = F(p) L(p) with p = {}", p),
+    })
+}
+
+/// Returns every strong Lucas pseudoprime with Selfridge's parameters below
+/// `limit`, in increasing order.
+pub fn strong_lucas_pseudoprimes(limit: u32) -> Vec<Pseudoprime> {
+    (9..limit)
+        .step_by(2)
+        .filter(|&n| !is_prime_u32(n))
+        .filter(|&n| reference::is_strong_lucas_probable_prime(&Natural::from_u32(n)))
+        .map(|n| Pseudoprime {
+            value: Int::from(n),
+            factors: factorize(n).into_iter().map(Int::from).collect(),
+            kind: Kind::StrongLucasPseudoprime,
+            bases: Vec::new(),
+            comment: "strong Lucas pseudoprime with Selfridge's parameters".to_string(),
+        })
+        .collect()
+}
+
+/// Exports `vectors` as a Wycheproof primality test file, where each one is
+/// a test case whose expected result is `invalid`, as it is composite.
+pub fn to_json(vectors: &[Pseudoprime]) -> String {
+    let tests: Vec<String> = vectors
+        .iter()
+        .enumerate()
+        .map(|(i, vector)| {
+            let flags: Vec<String> = vector
+                .flags()
+                .iter()
+                .map(|flag| format!("\"{}\"", flag))
+                .collect();
+
+            format!(
+                "        {{\n          \"tcId\" : {},\n          \"comment\" : \"{}\",\n          \
+                 \"value\" : \"{}\",\n          \"result\" : \"invalid\",\n          \
+                 \"flags\" : [{}]\n        }}",
+                i + 1,
+                vector.comment,
+                signed_hex(&vector.value),
+                flags.join(", ")
+            )
+        })
+        .collect();
+
+    format!(
+        "{{\n  \"algorithm\" : \"PrimalityTest\",\n  \"generatorVersion\" : \"pumpkin {}\",\n  \
+         \"numberOfTests\" : {},\n  \"header\" : [\n    \"Composites constructed to pass \
+         probabilistic primality tests.\"\n  ],\n  \"notes\" : {{\n{}\n  }},\n  \
+         \"schema\" : \"primality_test_schema.json\",\n  \"testGroups\" : [\n    {{\n      \
+         \"type\" : \"PrimalityTest\",\n      \"tests\" : [\n{}\n      ]\n    }}\n  ]\n}}\n",
+        env!("CARGO_PKG_VERSION"),
+        vectors.len(),
+        NOTES
+            .iter()
+            .map(|&(flag, note)| format!("    \"{}\" : \"{}\"", flag, note))
+            .collect::<Vec<_>>()
+            .join(",\n"),
+        tests.join(",\n")
+    )
+}
+
+/// The flags used by `to_json()`, and what they mean.
+const NOTES: [(&str, &str); 6] = [
+    (
+        "Base2FermatPseudoprime",
+        "The composite passes the Fermat test to base 2.",
+    ),
+    (
+        "Base2StrongPseudoprime",
+        "The composite passes Miller-Rabin to base 2.",
+    ),
+    (
+        "CarmichaelNumber",
+        "The composite passes the Fermat test to every base coprime to it.",
+    ),
+    (
+        "LucasPseudoprime",
+        "The composite passes the Lucas test with P = 1 and Q = -1.",
+    ),
+    (
+        "StrongLucasPseudoprime",
+        "The composite passes the strong Lucas test with Selfridge's parameters.",
+    ),
+    (
+        "StrongPseudoprime",
+        "The composite passes Miller-Rabin to every base named in the comment.",
+    ),
+];
+
+fn check_bit_length(bit_length: usize) -> Result<()> {
+    ::common::check_bit_length(bit_length, MIN_BIT_LENGTH)
+}
+
+fn check_bases(bases: &[u32]) -> Result<()> {
+    let mut sorted = bases.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+
+    let reason = if bases.is_empty() {
+        Some("must not be empty")
+    } else if sorted.len() != bases.len() {
+        Some("must not repeat")
+    } else if bases
+        .iter()
+        .any(|&basis| basis >= MAX_BASE || !is_prime_u32(basis))
+    {
+        Some("must be primes below 1000")
+    } else {
+        None
+    };
+
+    match reason {
+        Some(reason) => Err(Error::InvalidParameter {
+            name: "bases",
+            reason: reason.to_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// A modulus, and the residues allowed modulo it.
+type Congruence = (u32, Vec<u32>);
+
+/// Finds the first multipliers `k2 < k3`, among the odd primes that are not
+/// bases, for which Arnault's congruences on the first factor `p` can be
+/// met. Returns them along with the congruences, as pairs of a modulus and
+/// the residues of `p` allowed modulo it. The moduli are pairwise coprime.
+fn arnault_congruences(bases: &[u32]) -> Option<(u32, u32, Vec<Congruence>)> {
+    let candidates: Vec<u32> = (3..MAX_MULTIPLIER)
+        .step_by(2)
+        .filter(|&k| is_prime_u32(k) && !bases.contains(&k))
+        .collect();
+
+    for (i, &k2) in candidates.iter().enumerate() {
+        'multiplier: for &k3 in &candidates[i + 1..] {
+            let multipliers = [1, k2, k3];
+
+            // Every factor must be 3 mod 4, and, with 2 as a base, 3 mod 8.
+            let mut congruences = if bases.contains(&2) {
+                if k2 % 4 != 1 || k3 % 4 != 1 {
+                    continue;
+                }
+                vec![(8, vec![3])]
+            } else {
+                vec![(4, vec![3])]
+            };
+
+            for &basis in bases.iter().filter(|&&basis| basis != 2) {
+                // The residues modulo 4a of the primes that are 3 mod 4 and
+                // have `a` as a non-residue, which is all that determines
+                // the Jacobi symbol.
+                let modulus = 4 * basis;
+                let non_residues: Vec<u32> = (3..modulus)
+                    .step_by(4)
+                    .filter(|&z| reference::jacobi_u32(basis, z) == -1)
+                    .collect();
+
+                let allowed: Vec<u32> = (0..basis)
+                    .filter(|&p| {
+                        multipliers.iter().all(|&k| {
+                            let factor = (k as u64 * (u64::from(p) + u64::from(modulus) - 1) + 1)
+                                % u64::from(basis);
+                            non_residues.iter().any(|&z| u64::from(z % basis) == factor)
+                        })
+                    })
+                    .collect();
+
+                if allowed.is_empty() {
+                    continue 'multiplier;
+                }
+                congruences.push((basis, allowed));
+            }
+
+            // n must be 1 modulo each multiplier, without the multiplier
+            // dividing p - 1.
+            for &k in &[k2, k3] {
+                let allowed: Vec<u32> = (2..k)
+                    .filter(|&p| {
+                        let p = u64::from(p);
+                        multipliers.iter().fold(1, |product, &multiplier| {
+                            product
+                                * ((u64::from(multiplier) * (p + u64::from(k) - 1) + 1)
+                                    % u64::from(k))
+                                % u64::from(k)
+                        }) == 1
+                    })
+                    .collect();
+
+                if allowed.is_empty() {
+                    continue 'multiplier;
+                }
+                congruences.push((k, allowed));
+            }
+
+            return Some((k2, k3, congruences));
+        }
+    }
+
+    None
+}
+
+/// Picks one allowed residue for each congruence at random, and combines
+/// them with the Chinese remainder theorem.
+fn crt<R: Rng + ?Sized>(congruences: &[Congruence], rngesus: &mut R) -> Int {
+    let mut residue = Int::zero();
+    let mut modulus = Int::one();
+
+    for &(m, ref allowed) in congruences {
+        let target = u64::from(allowed[rngesus.gen_range(0, allowed.len())]);
+        let m = u64::from(m);
+
+        // residue + modulus * t = target (mod m).
+        let current = u64::from(&(&residue % m as usize));
+        let step = u64::from(&(&modulus % m as usize));
+        let t = (target + m - current) % m * inverse(step, m) % m;
+
+        residue += &modulus * t as usize;
+        modulus *= m as usize;
+    }
+
+    residue
+}
+
+/// Returns the inverse of `a` modulo `m`, for coprime `a` and `m`.
+fn inverse(a: u64, m: u64) -> u64 {
+    let (mut old_r, mut r) = (a as i64, m as i64);
+    let (mut old_s, mut s) = (1_i64, 0_i64);
+
+    while r != 0 {
+        let quotient = old_r / r;
+        let next_r = old_r - quotient * r;
+        old_r = r;
+        r = next_r;
+        let next_s = old_s - quotient * s;
+        old_s = s;
+        s = next_s;
+    }
+
+    old_s.rem_euclid(m as i64) as u64
+}
+
+/// Returns the prime factors of `n`, with multiplicity.
+fn factorize(mut n: u32) -> Vec<u32> {
+    let mut factors = Vec::new();
+    let mut divisor = 2;
+
+    while divisor * divisor <= n {
+        while n.is_multiple_of(divisor) {
+            factors.push(divisor);
+            n /= divisor;
+        }
+        divisor += 1;
+    }
+    if n > 1 {
+        factors.push(n);
+    }
+
+    factors
+}
+
+/// Encodes `value` as big-endian, two's complement hexadecimal, as
+/// Wycheproof does, with a leading zero byte where the top bit is set.
+fn signed_hex(value: &Int) -> String {
+    let mut bytes = value.to_be_bytes();
+    if bytes.first().is_none_or(|&byte| byte & 0x80 != 0) {
+        bytes.insert(0, 0);
+    }
+
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        carmichael, lucas_pseudoprime, signed_hex, strong_lucas_pseudoprimes, strong_pseudoprime,
+        to_json, Kind, Pseudoprime,
+    };
+    use backend::{is_prime, is_prime_bpsw};
+    use constant_time;
+    use error::Error;
+    use ramp::Int;
+    use rand::thread_rng;
+
+    fn check_composite(pseudoprime: &Pseudoprime) {
+        let product = pseudoprime
+            .factors
+            .iter()
+            .fold(Int::one(), |product, factor| product * factor);
+        assert_eq!(product, pseudoprime.value);
+        assert!(pseudoprime.factors.len() > 1);
+
+        assert!(!is_prime(&pseudoprime.value));
+        assert!(!is_prime_bpsw(&pseudoprime.value));
+        if pseudoprime.value.bit_length() > 64 {
+            assert!(!constant_time::is_prime(&pseudoprime.value).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_adversarial_carmichael() {
+        let n = carmichael(128, &mut thread_rng()).unwrap();
+        check_composite(&n);
+
+        assert_eq!(n.value.bit_length(), 128);
+        for factor in &n.factors {
+            // Korselt's criterion.
+            let minus_one: Int = factor - 1_usize;
+            assert_eq!(&(&n.value - 1_usize) % &minus_one, Int::zero());
+        }
+
+        let flags = n.flags();
+        assert!(flags.contains(&"Base2FermatPseudoprime".to_string()));
+        assert!(!flags.contains(&"Base2StrongPseudoprime".to_string()));
+    }
+
+    #[test]
+    fn test_adversarial_strong_pseudoprime() {
+        let mut rngesus = thread_rng();
+        let n = strong_pseudoprime(&[7, 2, 5, 3], 200, &mut rngesus).unwrap();
+        check_composite(&n);
+
+        assert_eq!(n.value.bit_length(), 200);
+        assert_eq!(n.bases, vec![2, 3, 5, 7]);
+        assert!(n.flags().contains(&"Base2StrongPseudoprime".to_string()));
+
+        let n = strong_pseudoprime(&[3, 5, 7, 11, 13], 256, &mut rngesus).unwrap();
+        check_composite(&n);
+
+        for bases in &[&[][..], &[2, 2], &[4], &[1009]] {
+            match strong_pseudoprime(bases, 256, &mut rngesus) {
+                Err(Error::InvalidParameter { name, .. }) => assert_eq!(name, "bases"),
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+        assert!(strong_pseudoprime(&[2, 3, 5, 7, 11, 13, 17, 19, 23], 64, &mut rngesus).is_err());
+    }
+
+    #[test]
+    fn test_adversarial_lucas() {
+        let n = lucas_pseudoprime(263).unwrap();
+        check_composite(&n);
+        assert_eq!(n.value.bit_length(), 365);
+        assert!(lucas_pseudoprime(9).is_err());
+
+        let found: Vec<Int> = strong_lucas_pseudoprimes(20000)
+            .into_iter()
+            .inspect(check_composite)
+            .map(|n| n.value)
+            .collect();
+        let expected: Vec<Int> = [5459_u32, 5777, 10877, 16109, 18971]
+            .iter()
+            .map(|&n| Int::from(n))
+            .collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn test_adversarial_json() {
+        let vectors = vec![
+            lucas_pseudoprime(7).unwrap(),
+            strong_lucas_pseudoprimes(6000).remove(0),
+        ];
+        let json = to_json(&vectors);
+
+        assert!(json.contains("\"numberOfTests\" : 2"));
+        assert!(json.contains("\"tcId\" : 2"));
+        assert!(json.contains("\"value\" : \"0179\""));
+        assert!(json.contains("\"flags\" : [\"StrongLucasPseudoprime\"]"));
+        assert_eq!(vectors[0].kind, Kind::LucasPseudoprime);
+
+        assert_eq!(signed_hex(&Int::from(0x80)), "0080");
+        assert_eq!(signed_hex(&Int::from(0x7f)), "7f");
+        assert_eq!(signed_hex(&Int::zero()), "00");
+    }
+}
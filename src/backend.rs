@@ -0,0 +1,206 @@
+//! Runs the primality tests and generators over big integer types other than
+//! `ramp::Int`.
+//!
+//! The arithmetic used by the tests is described by the `BigInt` trait, which
+//! needs little more than modular exponentiation and a big-endian byte
+//! encoding. `ramp::Int` implements it unconditionally, as it is the type
+//! returned by the rest of the crate. Further implementations are switched on
+//! with cargo features:
+//!
+//! | Feature         | Type                             | `no_std` |
+//! |-----------------|----------------------------------|----------|
+//! | `num-bigint`    | `num_bigint::BigUint`            | yes      |
+//! | `rug`           | `rug::Integer` (backed by GMP)   | no       |
+//! | `crypto-bigint` | `crypto_bigint::Uint<LIMBS>`     | yes      |
+//!
+//! `ramp::Int` needs the `std` feature. Without it, this module is all that
+//! is left of the crate's generators: `from_rng()` and `is_prime_with_rng()`
+//! draw every random number they need from the generator passed in.
+//!
+//! `from_rng_verified()` additionally confirms the prime it found with
+//! `is_prime_bpsw()`, which runs on plain arithmetic of its own rather than
+//! the backend's.
+//!
+//! Rust's orphan rules forbid this crate from implementing `From` between two
+//! types that it does not own, such as `ramp::Int` and `num_bigint::BigUint`.
+//! `convert()` fills that role instead, moving a value between any two
+//! backends.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! extern crate num_bigint;
+//! extern crate pumpkin;
+//!
+//! use num_bigint::BigUint;
+//! use pumpkin::backend;
+//! use rand::rngs::OsRng;
+//!
+//! let mut rngesus = OsRng::new().unwrap();
+//! let p: BigUint = backend::from_rng(2048, &mut rngesus).unwrap();
+//! assert!(backend::is_prime(&p));
+//! ```
+
+#[cfg(feature = "crypto-bigint")]
+mod crypto_bigint;
+#[cfg(feature = "num-bigint")]
+mod num_bigint;
+#[cfg(feature = "std")]
+mod ramp;
+#[cfg(feature = "rug")]
+mod rug;
+
+use rand::Rng;
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Deref;
+
+use common::{self, check_bit_length, MIN_BIT_LENGTH};
+use error::{Error, Result};
+use report::GenerationReport;
+
+#[cfg(feature = "std")]
+pub use common::is_prime;
+pub use common::{is_prime_bpsw, is_prime_with_rng};
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// The operations the primality tests need from a big integer type.
+///
+/// This trait is sealed: it is implemented for the supported backends only,
+/// and cannot be implemented outside of this crate.
+pub trait BigInt: Clone + Ord + fmt::Debug + private::Sealed + 'static {
+    /// The largest number of bits a value can hold, or `None` if the type
+    /// grows as needed.
+    const MAX_BITS: Option<usize> = None;
+
+    /// Decodes a non-negative number from its big-endian bytes. Leading zero
+    /// bytes are allowed.
+    fn from_be_bytes(bytes: &[u8]) -> Self;
+
+    /// Encodes the number as big-endian bytes, without any leading zero
+    /// bytes. Zero encodes as an empty vector.
+    fn to_be_bytes(&self) -> Vec<u8>;
+
+    /// Computes `self ^ exponent mod modulus`, where `modulus` is odd.
+    fn pow_mod(&self, exponent: &Self, modulus: &Self) -> Self;
+
+    /// Overwrites the digits of the number in place, leaving it zero.
+    ///
+    /// Copies the type made of itself while resizing are out of reach, so
+    /// this only guarantees that the current buffer is cleared.
+    fn wipe(&mut self);
+
+    /// Converts a `u64` into this type.
+    fn from_u64(number: u64) -> Self {
+        Self::from_be_bytes(&number.to_be_bytes())
+    }
+}
+
+/// Constructs a new prime number of type `B` with the size of `bit_length`
+/// bits, sourced from an already-initialized random number generator.
+///
+/// Note: the `bit_length` MUST be at least 512-bits, and must fit in `B`.
+pub fn from_rng<B: BigInt, R: Rng + ?Sized>(bit_length: usize, rngesus: &mut R) -> Result<B> {
+    check_backend_bit_length::<B>(bit_length)?;
+    common::gen_prime_unchecked(bit_length, rngesus, &mut GenerationReport::default())
+}
+
+/// Constructs a new prime number exactly like `from_rng()`, then confirms it
+/// with an independent Baillie-PSW test before returning it.
+///
+/// Fails with `Error::Inconsistent`, and does not try again, if the two
+/// tests disagree.
+pub fn from_rng_verified<B: BigInt, R: Rng + ?Sized>(
+    bit_length: usize,
+    rngesus: &mut R,
+) -> Result<B> {
+    check_backend_bit_length::<B>(bit_length)?;
+    common::gen_prime_verified(bit_length, rngesus, &mut GenerationReport::default())
+}
+
+/// Moves a number from one backend to another.
+///
+/// Fails with `Error::InvalidParameter` if `number` has more bits than `B`
+/// can hold.
+pub fn convert<A: BigInt, B: BigInt>(number: &A) -> Result<B> {
+    let bytes = number.to_be_bytes();
+
+    match B::MAX_BITS {
+        Some(max) if common::bytes_bit_length(&bytes) > max => Err(Error::InvalidParameter {
+            name: "number",
+            reason: format!("does not fit in {} bits", max),
+        }),
+        _ => Ok(B::from_be_bytes(&bytes)),
+    }
+}
+
+/// Wipes the number it holds when dropped.
+pub(crate) struct Wiping<B: BigInt>(pub B);
+
+impl<B: BigInt> Deref for Wiping<B> {
+    type Target = B;
+
+    fn deref(&self) -> &B {
+        &self.0
+    }
+}
+
+impl<B: BigInt> Drop for Wiping<B> {
+    fn drop(&mut self) {
+        self.0.wipe();
+    }
+}
+
+fn check_backend_bit_length<B: BigInt>(bit_length: usize) -> Result<()> {
+    check_bit_length(bit_length, MIN_BIT_LENGTH)?;
+
+    match B::MAX_BITS {
+        Some(max) if bit_length > max => Err(Error::InvalidParameter {
+            name: "bit_length",
+            reason: format!("the backend holds at most {} bits", max),
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{convert, from_rng, is_prime, BigInt};
+    use ramp::Int;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn test_backend_ramp_bytes() {
+        let number = Int::from_str_radix("1234567890abcdef0123456789", 16).unwrap();
+        let bytes = number.to_be_bytes();
+
+        assert_eq!(bytes[0], 0x12);
+        assert_eq!(bytes.len(), 13);
+        assert_eq!(Int::from_be_bytes(&bytes), number);
+        assert_eq!(Int::from_be_bytes(&[0, 0, 1]), Int::one());
+        assert!(Int::zero().to_be_bytes().is_empty());
+        assert_eq!(Int::from(0x8001).to_be_bytes(), vec![0x80, 0x01]);
+    }
+
+    #[test]
+    fn test_backend_ramp_wipe() {
+        let mut number = Int::from_str_radix("1234567890abcdef0123456789", 16).unwrap();
+        number.wipe();
+
+        assert_eq!(number, Int::zero());
+    }
+
+    #[test]
+    fn test_backend_ramp_prime() {
+        let mut rngesus = OsRng::new().unwrap();
+        let p: Int = from_rng(512, &mut rngesus).unwrap();
+
+        assert_eq!(p.bit_length(), 512);
+        assert!(is_prime(&p));
+        assert_eq!(convert::<Int, Int>(&p).unwrap(), p);
+    }
+}
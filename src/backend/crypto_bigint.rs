@@ -0,0 +1,85 @@
+use crypto_bigint::modular::runtime_mod::{DynResidue, DynResidueParams};
+use crypto_bigint::{NonZero, Uint};
+
+use alloc::vec::Vec;
+
+use super::{private, BigInt};
+
+impl<const LIMBS: usize> private::Sealed for Uint<LIMBS> {}
+
+impl<const LIMBS: usize> BigInt for Uint<LIMBS> {
+    const MAX_BITS: Option<usize> = Some(Uint::<LIMBS>::BITS);
+
+    fn from_be_bytes(bytes: &[u8]) -> Uint<LIMBS> {
+        let start = bytes.iter().take_while(|&&byte| byte == 0).count();
+        let bytes = &bytes[start..];
+        assert!(bytes.len() <= Uint::<LIMBS>::BYTES, "number is too large");
+
+        let mut padded = vec![0u8; Uint::<LIMBS>::BYTES];
+        padded[Uint::<LIMBS>::BYTES - bytes.len()..].copy_from_slice(bytes);
+
+        Uint::from_be_slice(&padded)
+    }
+
+    fn to_be_bytes(&self) -> Vec<u8> {
+        let bytes: Vec<u8> = self
+            .as_words()
+            .iter()
+            .rev()
+            .flat_map(|word| word.to_be_bytes().to_vec())
+            .collect();
+        let start = bytes.iter().take_while(|&&byte| byte == 0).count();
+
+        bytes[start..].to_vec()
+    }
+
+    fn pow_mod(&self, exponent: &Uint<LIMBS>, modulus: &Uint<LIMBS>) -> Uint<LIMBS> {
+        let params = DynResidueParams::new(modulus);
+        let base = DynResidue::new(&self.rem(&NonZero::new(*modulus).unwrap()), params);
+
+        base.pow(exponent).retrieve()
+    }
+
+    fn wipe(&mut self) {
+        *self = Uint::ZERO;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backend::{convert, from_rng, is_prime, BigInt};
+    use crypto_bigint::{U256, U512};
+    use error::Error;
+    use ramp::Int;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn test_backend_crypto_bigint_prime() {
+        let mut rngesus = OsRng::new().unwrap();
+        let p: U512 = from_rng(512, &mut rngesus).unwrap();
+
+        assert_eq!(p.bits(), 512);
+        assert!(is_prime(&p));
+
+        let converted: Int = convert(&p).unwrap();
+        assert_eq!(convert::<Int, U512>(&converted).unwrap(), p);
+        assert!(convert::<Int, U256>(&converted).is_err());
+    }
+
+    #[test]
+    fn test_backend_crypto_bigint_too_small() {
+        let mut rngesus = OsRng::new().unwrap();
+
+        match from_rng::<U256, _>(512, &mut rngesus) {
+            Err(Error::InvalidParameter { name, .. }) => assert_eq!(name, "bit_length"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_backend_crypto_bigint_bytes() {
+        let number = U256::from_u64(0x0102_0304);
+        assert_eq!(number.to_be_bytes(), vec![1, 2, 3, 4]);
+        assert_eq!(U256::from_be_bytes(&[0, 0, 1, 2, 3, 4]), number);
+    }
+}
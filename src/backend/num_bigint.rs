@@ -0,0 +1,66 @@
+use num_bigint::BigUint;
+
+use alloc::vec::Vec;
+
+use super::{private, BigInt};
+
+impl private::Sealed for BigUint {}
+
+impl BigInt for BigUint {
+    fn from_be_bytes(bytes: &[u8]) -> BigUint {
+        BigUint::from_bytes_be(bytes)
+    }
+
+    fn to_be_bytes(&self) -> Vec<u8> {
+        let bytes = self.to_bytes_be();
+
+        // `to_bytes_be()` encodes zero as a single zero byte.
+        if bytes == [0] {
+            Vec::new()
+        } else {
+            bytes
+        }
+    }
+
+    fn pow_mod(&self, exponent: &BigUint, modulus: &BigUint) -> BigUint {
+        self.modpow(exponent, modulus)
+    }
+
+    fn wipe(&mut self) {
+        // Clearing the bits from the bottom up keeps the length unchanged,
+        // so the buffer is only released once it is entirely zero.
+        for bit in 0..self.bits() {
+            self.set_bit(bit, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backend::{convert, from_rng, is_prime, BigInt};
+    use num_bigint::BigUint;
+    use ramp::Int;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn test_backend_num_bigint_prime() {
+        let mut rngesus = OsRng::new().unwrap();
+        let p: BigUint = from_rng(512, &mut rngesus).unwrap();
+
+        assert_eq!(p.bits(), 512);
+        assert!(is_prime(&p));
+        assert!(!is_prime(&(&p * &p)));
+
+        let converted: Int = convert(&p).unwrap();
+        assert_eq!(converted.to_str_radix(10, false), p.to_string());
+        assert_eq!(convert::<Int, BigUint>(&converted).unwrap(), p);
+    }
+
+    #[test]
+    fn test_backend_num_bigint_zero() {
+        assert!(BigUint::from(0_u32).to_be_bytes().is_empty());
+        assert!(!is_prime(&BigUint::from(0_u32)));
+        assert!(!is_prime(&BigUint::from(1_u32)));
+        assert!(is_prime(&BigUint::from(2_u32)));
+    }
+}
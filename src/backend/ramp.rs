@@ -0,0 +1,47 @@
+use ramp::Int;
+
+use super::{private, BigInt};
+
+impl private::Sealed for Int {}
+
+impl BigInt for Int {
+    fn from_be_bytes(bytes: &[u8]) -> Int {
+        let mut number = Int::zero();
+        for byte in bytes {
+            number <<= 8;
+            number += *byte as usize;
+        }
+
+        number
+    }
+
+    fn to_be_bytes(&self) -> Vec<u8> {
+        if *self == Int::zero() {
+            return Vec::new();
+        }
+
+        // Read the bits straight out of the limbs, so that no intermediate
+        // copy of the value, such as a hex string, is left on the heap.
+        let length = (self.bit_length() as usize).div_ceil(8);
+        let mut bytes = vec![0u8; length];
+        for bit in 0..self.bit_length() {
+            if self.bit(bit) {
+                bytes[length - 1 - bit as usize / 8] |= 1 << (bit % 8);
+            }
+        }
+
+        bytes
+    }
+
+    fn pow_mod(&self, exponent: &Int, modulus: &Int) -> Int {
+        Int::pow_mod(self, exponent, modulus)
+    }
+
+    fn wipe(&mut self) {
+        // Clearing bits never reallocates, and clearing them from the bottom
+        // up keeps the length unchanged until the last one.
+        for bit in 0..self.bit_length() {
+            self.set_bit(bit, false);
+        }
+    }
+}
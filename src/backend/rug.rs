@@ -0,0 +1,31 @@
+use rug::integer::Order;
+use rug::Integer;
+
+use super::{private, BigInt};
+
+impl private::Sealed for Integer {}
+
+impl BigInt for Integer {
+    fn from_be_bytes(bytes: &[u8]) -> Integer {
+        Integer::from_digits(bytes, Order::Msf)
+    }
+
+    fn to_be_bytes(&self) -> Vec<u8> {
+        self.to_digits(Order::Msf)
+    }
+
+    fn pow_mod(&self, exponent: &Integer, modulus: &Integer) -> Integer {
+        // Only fails for negative exponents without an inverse, which the
+        // primality tests never use.
+        Integer::from(
+            self.pow_mod_ref(exponent, modulus)
+                .expect("exponent is non-negative"),
+        )
+    }
+
+    fn wipe(&mut self) {
+        for bit in 0..self.significant_bits() {
+            self.set_bit(bit, false);
+        }
+    }
+}
@@ -0,0 +1,849 @@
+//! Primality certificates, which prove a number prime without relying on
+//! randomness or on a probabilistic test.
+//!
+//! A `Certificate` is a tree, whose leaves are primes small enough to check
+//! directly. Every other node proves its number `n` prime from proven prime
+//! factors `q` of `n - 1`:
+//!
+//! * `Pratt` certificates factor `n - 1` completely, and give a generator
+//!   `g` of the multiplicative group modulo `n`: `g^(n - 1) = 1`, but
+//!   `g^((n - 1) / q) != 1` for every `q`.
+//!
+//! * `Pocklington` certificates factor `n - 1 = F R` only in part, and give
+//!   each `q` a witness `a` with `a^(n - 1) = 1` and
+//!   `gcd(a^((n - 1) / q) - 1, n) = 1`. Pocklington's criterion proves `n`
+//!   prime if `F > sqrt(n)`. The Brillhart-Lehmer-Selfridge N-1 test lowers
+//!   that to `F > n^(1/3)`, provided that, writing `n = c2 F^2 + c1 F + 1`,
+//!   `c1^2 - 4 c2` is not a square.
+//!
+//! * `Ecpp` certificates give an elliptic curve `E` modulo `n`, a point `P`
+//!   on it and a multiple `m` of its order, with a prime factor
+//!   `q > (n^(1/4) + 1)^2`: `m P = O`, but `(m / q) P != O`. The theorem of
+//!   Goldwasser, Kilian and Atkin then proves `n` prime. They come from the
+//!   `ecpp` module, and prove primes whatever the factorization of `n - 1`.
+//!
+//! `verify_certificate()` checks a certificate deterministically, with a
+//! handful of modular exponentiations per node. `certify()` builds one for
+//! any prime whose `n - 1` is factored far enough, finding the generator or
+//! witnesses itself.
+//!
+//! ## Format
+//!
+//! Certificates are serialized with `Display` and parsed with `FromStr`, in
+//! a line-based text format. The first line is `pumpkin-certificate 1`, and
+//! every other line proves one prime, written in hexadecimal, after the
+//! lines proving the primes it relies on:
+//!
+//! ```text
+//! pumpkin-certificate 1
+//! small 2
+//! small 5
+//! small 67
+//! pratt 407 e 2^1 5^1 67^1
+//! pocklington 80f 407^1:2
+//! ```
+//!
+//! `pratt n g q^e ...` gives the generator `g` and every prime power of
+//! `n - 1`, `pocklington n q^e:a ...` gives each prime power of `F` with
+//! its witness, and `ecpp n a b m q x y` gives the curve
+//! `y^2 = x^3 + a x + b`, the multiple `m`, its prime factor `q` and the
+//! point `(x, y)`. The last line proves the certified prime. Blank lines and
+//! lines starting with `#` are ignored.
+//!
+//! Each prime is proven by one line only, and every prime a line relies on
+//! is smaller than the one it proves. A prime that several lines rely on is
+//! parsed once and shared between them, and verified once.
+
+use ramp::Int;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error as StdError;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use ecpp::curve::{Curve, Point};
+use error::{Error, Result};
+use prime::small::is_prime_u64;
+
+/// The first line of the text format.
+const HEADER: &str = "pumpkin-certificate 1";
+
+/// How many candidates `certify()` tries for a generator or a witness.
+const MAX_WITNESS: u32 = 1000;
+
+/// A proof that a number is prime.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Certificate {
+    /// A prime below 2^64, which is checked directly.
+    Small(u64),
+    /// A prime proven by a generator and the full factorization of `n - 1`.
+    Pratt(Pratt),
+    /// A prime proven by Pocklington's criterion, or by the
+    /// Brillhart-Lehmer-Selfridge N-1 test.
+    Pocklington(Pocklington),
+    /// A prime proven by a point on an elliptic curve.
+    Ecpp(Ecpp),
+}
+
+/// A proof of primality from the full factorization of `n - 1`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pratt {
+    /// The number proven prime.
+    pub n: Int,
+    /// A generator of the multiplicative group modulo `n`.
+    pub generator: Int,
+    /// The proven prime factors of `n - 1`, whose product is `n - 1`.
+    pub factors: Vec<PrattFactor>,
+}
+
+/// A prime factor of `n - 1` in a `Pratt` certificate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrattFactor {
+    /// The certificate of the factor itself.
+    pub certificate: Arc<Certificate>,
+    /// The power of the factor in `n - 1`.
+    pub exponent: u32,
+}
+
+/// A proof of primality from a partial factorization of `n - 1`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pocklington {
+    /// The number proven prime.
+    pub n: Int,
+    /// The proven prime factors of `n - 1` whose product, `F`, is greater
+    /// than `sqrt(n)`, or than the cube root of `n` if `n` passes the
+    /// Brillhart-Lehmer-Selfridge test.
+    pub factors: Vec<PocklingtonFactor>,
+}
+
+/// A prime factor of `n - 1` in a `Pocklington` certificate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PocklingtonFactor {
+    /// The certificate of the factor itself.
+    pub certificate: Arc<Certificate>,
+    /// The power of the factor that counts towards `F`.
+    pub exponent: u32,
+    /// The witness `a` of the criterion for this factor.
+    pub witness: Int,
+}
+
+/// A proof of primality from a point on an elliptic curve.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ecpp {
+    /// The number proven prime.
+    pub n: Int,
+    /// The coefficient `a` of the curve `y^2 = x^3 + a x + b`.
+    pub a: Int,
+    /// The coefficient `b` of the curve.
+    pub b: Int,
+    /// A multiple of the order of the point, usually the order of the
+    /// curve.
+    pub m: Int,
+    /// The `x` coordinate of the point.
+    pub x: Int,
+    /// The `y` coordinate of the point.
+    pub y: Int,
+    /// The certificate of `q`, a prime factor of `m` greater than
+    /// `(n^(1/4) + 1)^2`.
+    pub certificate: Arc<Certificate>,
+}
+
+impl Certificate {
+    /// Returns the number that the certificate proves prime.
+    pub fn n(&self) -> Int {
+        match *self {
+            Certificate::Small(n) => Int::from(n),
+            Certificate::Pratt(ref certificate) => certificate.n.clone(),
+            Certificate::Pocklington(ref certificate) => certificate.n.clone(),
+            Certificate::Ecpp(ref certificate) => certificate.n.clone(),
+        }
+    }
+
+    /// Returns the certificates of the factors of `n - 1`.
+    fn children(&self) -> Vec<&Certificate> {
+        match *self {
+            Certificate::Small(_) => Vec::new(),
+            Certificate::Pratt(ref certificate) => certificate
+                .factors
+                .iter()
+                .map(|factor| &*factor.certificate)
+                .collect(),
+            Certificate::Pocklington(ref certificate) => certificate
+                .factors
+                .iter()
+                .map(|factor| &*factor.certificate)
+                .collect(),
+            Certificate::Ecpp(ref certificate) => vec![&*certificate.certificate],
+        }
+    }
+
+    /// Writes the lines proving the primes this certificate relies on, and
+    /// then its own, skipping the primes already `written`.
+    fn write_lines(&self, f: &mut fmt::Formatter, written: &mut BTreeSet<Int>) -> fmt::Result {
+        let n = self.n();
+        if written.contains(&n) {
+            return Ok(());
+        }
+        for child in self.children() {
+            child.write_lines(f, written)?;
+        }
+
+        match *self {
+            Certificate::Small(n) => write!(f, "\nsmall {:x}", n)?,
+            Certificate::Pratt(ref certificate) => {
+                write!(f, "\npratt {:x} {:x}", certificate.n, certificate.generator)?;
+                for factor in &certificate.factors {
+                    write!(f, " {:x}^{}", factor.certificate.n(), factor.exponent)?;
+                }
+            }
+            Certificate::Pocklington(ref certificate) => {
+                write!(f, "\npocklington {:x}", certificate.n)?;
+                for factor in &certificate.factors {
+                    write!(
+                        f,
+                        " {:x}^{}:{:x}",
+                        factor.certificate.n(),
+                        factor.exponent,
+                        factor.witness
+                    )?;
+                }
+            }
+            Certificate::Ecpp(ref certificate) => write!(
+                f,
+                "\necpp {:x} {:x} {:x} {:x} {:x} {:x} {:x}",
+                certificate.n,
+                certificate.a,
+                certificate.b,
+                certificate.m,
+                certificate.certificate.n(),
+                certificate.x,
+                certificate.y
+            )?,
+        }
+        written.insert(n);
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Certificate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(HEADER)?;
+        self.write_lines(f, &mut BTreeSet::new())?;
+        f.write_str("\n")
+    }
+}
+
+impl FromStr for Certificate {
+    type Err = Error;
+
+    /// Parses a certificate from the text format.
+    ///
+    /// Fails with `Error::Parse` if the text is malformed, proves a prime
+    /// twice, or relies on a prime before the line proving it or on one
+    /// that is not smaller than the prime being proven. The certificate is
+    /// not verified.
+    fn from_str(text: &str) -> Result<Certificate> {
+        let mut lines = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        if lines.next() != Some(HEADER) {
+            return Err(parse_error(None));
+        }
+
+        let mut proven: BTreeMap<Int, Arc<Certificate>> = BTreeMap::new();
+        let mut last = None;
+        for line in lines {
+            let mut fields = line.split_whitespace();
+            let kind = fields.next();
+            let n = parse_int(fields.next())?;
+            if proven.contains_key(&n) {
+                return Err(parse_error(None));
+            }
+
+            let certificate = match kind {
+                Some("small") if n.bit_length() <= 64 && fields.next().is_none() => {
+                    Certificate::Small(u64::from(&n))
+                }
+                Some("pratt") => {
+                    let generator = parse_int(fields.next())?;
+                    let factors = fields
+                        .map(|field| {
+                            let (certificate, exponent) = parse_power(field, &n, &proven)?;
+                            Ok(PrattFactor {
+                                certificate,
+                                exponent,
+                            })
+                        })
+                        .collect::<Result<_>>()?;
+
+                    Certificate::Pratt(Pratt {
+                        n,
+                        generator,
+                        factors,
+                    })
+                }
+                Some("pocklington") => {
+                    let factors = fields
+                        .map(|field| {
+                            let mut parts = field.splitn(2, ':');
+                            let power = parts.next().unwrap_or("");
+                            let (certificate, exponent) = parse_power(power, &n, &proven)?;
+                            Ok(PocklingtonFactor {
+                                certificate,
+                                exponent,
+                                witness: parse_int(parts.next())?,
+                            })
+                        })
+                        .collect::<Result<_>>()?;
+
+                    Certificate::Pocklington(Pocklington { n, factors })
+                }
+                Some("ecpp") => {
+                    let a = parse_int(fields.next())?;
+                    let b = parse_int(fields.next())?;
+                    let m = parse_int(fields.next())?;
+                    let q = parse_int(fields.next())?;
+                    let x = parse_int(fields.next())?;
+                    let y = parse_int(fields.next())?;
+                    if fields.next().is_some() {
+                        return Err(parse_error(None));
+                    }
+                    let certificate = find_proven(&q, &n, &proven)?;
+
+                    Certificate::Ecpp(Ecpp {
+                        n,
+                        a,
+                        b,
+                        m,
+                        x,
+                        y,
+                        certificate,
+                    })
+                }
+                _ => return Err(parse_error(None)),
+            };
+
+            let certificate = Arc::new(certificate);
+            proven.insert(certificate.n(), certificate.clone());
+            last = Some(certificate);
+        }
+
+        // No line may rely on the last one, so once the others are gone,
+        // it is the only reference left.
+        drop(proven);
+        let last = last.ok_or_else(|| parse_error(None))?;
+        Ok(Arc::try_unwrap(last).unwrap_or_else(|last| (*last).clone()))
+    }
+}
+
+/// A prime along with the certificate that proves it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CertifiedPrime {
+    /// The prime itself.
+    pub prime: Int,
+    /// The proof of its primality.
+    pub certificate: Certificate,
+}
+
+/// Checks that `certificate` proves its number prime.
+///
+/// Fails with `Error::Verification` if any node of the certificate does not
+/// hold.
+pub fn verify_certificate(certificate: &Certificate) -> Result<()> {
+    // Nodes are checked after the nodes they rely on, from a stack rather
+    // than by recursion, and each number only once: a number that one node
+    // proves prime is prime for every other node as well.
+    let mut proven = BTreeSet::new();
+    let mut pending = vec![(certificate, false)];
+
+    while let Some((node, expanded)) = pending.pop() {
+        let n = node.n();
+        if proven.contains(&n) {
+            continue;
+        }
+        if !expanded {
+            pending.push((node, true));
+            pending.extend(node.children().into_iter().map(|child| (child, false)));
+            continue;
+        }
+
+        let holds = match *node {
+            Certificate::Small(n) => is_prime_u64(n),
+            Certificate::Pratt(ref certificate) => verify_pratt(certificate),
+            Certificate::Pocklington(ref certificate) => verify_pocklington(certificate),
+            Certificate::Ecpp(ref certificate) => verify_ecpp(certificate),
+        };
+        if !holds {
+            return Err(Error::Verification {
+                what: "the primality certificate",
+            });
+        }
+
+        proven.insert(n);
+    }
+
+    Ok(())
+}
+
+/// Builds a certificate for the prime `n` from proven prime factors of
+/// `n - 1`, each with its exponent. The certificate is a `Pratt` one if
+/// the factors multiply to `n - 1`, and a `Pocklington` one otherwise. The
+/// smallest generator or witnesses that work are used, so the result only
+/// depends on its arguments.
+///
+/// Fails with `Error::InvalidParameter` if the factors do not divide
+/// `n - 1`, or their product is not above the cube root of `n`, and with
+/// `Error::Verification` if `n` cannot be certified with them, as when it
+/// is composite.
+pub fn certify(n: &Int, factors: &[(Certificate, u32)]) -> Result<Certificate> {
+    if factors
+        .iter()
+        .any(|&(_, exponent)| exponent as usize >= n.bit_length() as usize)
+    {
+        return Err(Error::InvalidParameter {
+            name: "factors",
+            reason: "must have exponents below the length of n".to_string(),
+        });
+    }
+
+    let minus_one = n - 1_usize;
+    let f = factors
+        .iter()
+        .fold(Int::one(), |f, &(ref certificate, exponent)| {
+            f * certificate.n().pow(exponent as usize)
+        });
+
+    if *n <= 3_i32 || &minus_one % &f != 0_i32 || &f * &f * &f <= *n {
+        return Err(Error::InvalidParameter {
+            name: "factors",
+            reason: "must divide n - 1, with a product above its cube root".to_string(),
+        });
+    }
+
+    let no_witness = Error::Verification {
+        what: "the primality certificate",
+    };
+    let candidates = || (2..MAX_WITNESS).map(Int::from);
+
+    let certificate = if f == minus_one {
+        let primes: Vec<Int> = factors
+            .iter()
+            .map(|(certificate, _)| certificate.n())
+            .collect();
+        let generator = candidates()
+            .find(|generator| is_generator(generator, n, &primes))
+            .ok_or(no_witness)?;
+
+        Certificate::Pratt(Pratt {
+            n: n.clone(),
+            generator,
+            factors: factors
+                .iter()
+                .map(|&(ref certificate, exponent)| PrattFactor {
+                    certificate: Arc::new(certificate.clone()),
+                    exponent,
+                })
+                .collect(),
+        })
+    } else {
+        let mut proven = Vec::with_capacity(factors.len());
+        for &(ref certificate, exponent) in factors {
+            let q = certificate.n();
+            let witness = match candidates().find(|witness| is_witness(witness, n, &q)) {
+                Some(witness) => witness,
+                None => return Err(no_witness),
+            };
+
+            proven.push(PocklingtonFactor {
+                certificate: Arc::new(certificate.clone()),
+                exponent,
+                witness,
+            });
+        }
+
+        Certificate::Pocklington(Pocklington {
+            n: n.clone(),
+            factors: proven,
+        })
+    };
+
+    verify_certificate(&certificate)?;
+    Ok(certificate)
+}
+
+/// Checks a `Pratt` node, whose factors are already proven prime.
+fn verify_pratt(certificate: &Pratt) -> bool {
+    let n = &certificate.n;
+    let mut product = Int::one();
+    let mut primes = Vec::with_capacity(certificate.factors.len());
+
+    for factor in &certificate.factors {
+        let q = factor.certificate.n();
+        if !valid_exponent(factor.exponent, n) || primes.contains(&q) {
+            return false;
+        }
+
+        product *= q.pow(factor.exponent as usize);
+        primes.push(q);
+    }
+
+    *n >= 3_i32
+        && product == n - 1_usize
+        && certificate.generator > 1_i32
+        && certificate.generator < product
+        && is_generator(&certificate.generator, n, &primes)
+}
+
+/// Checks a `Pocklington` node, whose factors are already proven prime.
+fn verify_pocklington(certificate: &Pocklington) -> bool {
+    let one = Int::one();
+    let n = &certificate.n;
+    if *n <= 3_i32 || n.is_even() {
+        return false;
+    }
+
+    let minus_one = n - &one;
+    let mut f = one.clone();
+    let mut primes = Vec::with_capacity(certificate.factors.len());
+
+    for factor in &certificate.factors {
+        let q = factor.certificate.n();
+        let witness = &factor.witness;
+        if !valid_exponent(factor.exponent, n)
+            || primes.contains(&q)
+            || *witness < 2_i32
+            || *witness >= minus_one
+            || !is_witness(witness, n, &q)
+        {
+            return false;
+        }
+
+        f *= q.pow(factor.exponent as usize);
+        primes.push(q);
+    }
+
+    if &minus_one % &f != 0_i32 || &f * &f * &f <= *n {
+        return false;
+    }
+    if &f * &f > *n {
+        return true;
+    }
+
+    // Between the cube and square roots, n = c2 F^2 + c1 F + 1 with c1 < F
+    // is prime unless c1^2 - 4 c2 is a square.
+    let (c2, c1) = (minus_one / &f).divmod(&f);
+    let discriminant = &c1 * &c1 - c2 * 4_usize;
+    if discriminant < 0_i32 {
+        return true;
+    }
+
+    match discriminant.sqrt_rem() {
+        Some((_, remainder)) => remainder != 0_i32,
+        None => false,
+    }
+}
+
+/// Checks an `Ecpp` node, whose factor `q` is already proven prime.
+fn verify_ecpp(certificate: &Ecpp) -> bool {
+    let n = &certificate.n;
+    let m = &certificate.m;
+    let q = certificate.certificate.n();
+    if *n <= 3_i32 || n.is_even() || n % 3_usize == 0_i32 {
+        return false;
+    }
+    if *m <= 0_i32 || m % &q != 0_i32 || q <= min_ecpp_factor(n) {
+        return false;
+    }
+
+    let curve = match Curve::new(&certificate.a, &certificate.b, n) {
+        Some(curve) => curve,
+        None => return false,
+    };
+    let point = Point::Affine(certificate.x.clone(), certificate.y.clone());
+    if *curve.a() != certificate.a || *curve.b() != certificate.b || !curve.contains(&point) {
+        return false;
+    }
+
+    // Neither multiplication may fail, and (m / q) P must not be O.
+    match curve.mul(&point, &(m / &q)) {
+        Some(Point::Infinity) | None => false,
+        Some(cofactor_point) => curve.mul(&cofactor_point, &q) == Some(Point::Infinity),
+    }
+}
+
+/// Checks that a factor of `n - 1` may have the power `exponent`: it must
+/// be positive, and since the factor is at least 2, below the length of
+/// `n`. This also keeps the power from being computed when it is huge.
+fn valid_exponent(exponent: u32, n: &Int) -> bool {
+    exponent > 0 && exponent < n.bit_length()
+}
+
+/// Returns a bound that a prime `q` must exceed to prove `n` prime with
+/// ECPP: `(floor(n^(1/4)) + 2)^2`, just above `(n^(1/4) + 1)^2`.
+pub(crate) fn min_ecpp_factor(n: &Int) -> Int {
+    let fourth_root = n
+        .clone()
+        .sqrt_rem()
+        .and_then(|(root, _)| root.sqrt_rem())
+        .map(|(root, _)| root)
+        .unwrap_or_else(Int::zero);
+
+    (fourth_root + 2_usize).square()
+}
+
+/// Checks that `generator^(n - 1) = 1`, but `generator^((n - 1) / q) != 1`
+/// for each prime `q` in `primes`, modulo `n`.
+fn is_generator(generator: &Int, n: &Int, primes: &[Int]) -> bool {
+    let one = Int::one();
+    let minus_one = n - &one;
+
+    generator.pow_mod(&minus_one, n) == one
+        && primes
+            .iter()
+            .all(|q| generator.pow_mod(&(&minus_one / q), n) != one)
+}
+
+/// Checks that `witness^(n - 1) = 1`, and that
+/// `gcd(witness^((n - 1) / q) - 1, n) = 1`, modulo `n`.
+fn is_witness(witness: &Int, n: &Int, q: &Int) -> bool {
+    let one = Int::one();
+    let minus_one = n - &one;
+
+    witness.pow_mod(&minus_one, n) == one
+        && (witness.pow_mod(&(&minus_one / q), n) - &one).gcd(n) == one
+}
+
+/// Parses a number in hexadecimal.
+fn parse_int(field: Option<&str>) -> Result<Int> {
+    let field = field.ok_or_else(|| parse_error(None))?;
+    if field.starts_with('-') || field.starts_with('+') {
+        return Err(parse_error(None));
+    }
+
+    Int::from_str_radix(field, 16).map_err(|err| parse_error(Some(Box::new(err))))
+}
+
+/// Parses `q^e`, taking the certificate of `q` from those already `proven`.
+fn parse_power(
+    field: &str,
+    n: &Int,
+    proven: &BTreeMap<Int, Arc<Certificate>>,
+) -> Result<(Arc<Certificate>, u32)> {
+    let mut parts = field.splitn(2, '^');
+    let q = parse_int(parts.next())?;
+    let exponent = parts
+        .next()
+        .ok_or_else(|| parse_error(None))?
+        .parse()
+        .map_err(|err| parse_error(Some(Box::new(err))))?;
+
+    Ok((find_proven(&q, n, proven)?, exponent))
+}
+
+/// Finds the certificate of `q` among those already `proven`, for a line
+/// proving `n`, which must be greater.
+fn find_proven(
+    q: &Int,
+    n: &Int,
+    proven: &BTreeMap<Int, Arc<Certificate>>,
+) -> Result<Arc<Certificate>> {
+    match proven.get(q) {
+        Some(certificate) if q < n => Ok(certificate.clone()),
+        _ => Err(parse_error(None)),
+    }
+}
+
+fn parse_error(source: Option<Box<dyn StdError + Send + Sync>>) -> Error {
+    Error::Parse {
+        what: "primality certificate",
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        certify, verify_certificate, Certificate, Pocklington, PocklingtonFactor, Pratt,
+        PrattFactor,
+    };
+    use error::Error;
+    use ramp::Int;
+    use std::sync::Arc;
+
+    // 1031 = 2 * 5 * 103 + 1, and 103 > sqrt(1031).
+    fn certificate(n: u32, q: u64, witness: u32) -> Certificate {
+        Certificate::Pocklington(Pocklington {
+            n: Int::from(n),
+            factors: vec![PocklingtonFactor {
+                certificate: Arc::new(Certificate::Small(q)),
+                exponent: 1,
+                witness: Int::from(witness),
+            }],
+        })
+    }
+
+    fn pratt(n: u32, generator: u32, primes: &[u64]) -> Certificate {
+        Certificate::Pratt(Pratt {
+            n: Int::from(n),
+            generator: Int::from(generator),
+            factors: primes
+                .iter()
+                .map(|&q| PrattFactor {
+                    certificate: Arc::new(Certificate::Small(q)),
+                    exponent: 1,
+                })
+                .collect(),
+        })
+    }
+
+    fn small(n: u64) -> (Certificate, u32) {
+        (Certificate::Small(n), 1)
+    }
+
+    #[test]
+    fn test_verify_certificate() {
+        assert!(verify_certificate(&certificate(1031, 103, 2)).is_ok());
+        assert_eq!(certificate(1031, 103, 2).n(), Int::from(1031));
+
+        // 5 divides 1031 - 1, but 5 < sqrt(1031).
+        assert!(verify_certificate(&certificate(1031, 5, 2)).is_err());
+        // 1033 - 1 is not divisible by 103.
+        assert!(verify_certificate(&certificate(1033, 103, 2)).is_err());
+        // 1030 is even, and 2^1030 != 1 (mod 1031 * 3).
+        assert!(verify_certificate(&certificate(1030, 103, 2)).is_err());
+        assert!(verify_certificate(&certificate(1031 * 3, 103, 2)).is_err());
+        // 1 and n - 1 are not witnesses.
+        assert!(verify_certificate(&certificate(1031, 103, 1)).is_err());
+        assert!(verify_certificate(&certificate(1031, 103, 1030)).is_err());
+        // 101 is not the factor that was claimed.
+        assert!(verify_certificate(&certificate(1031, 101, 2)).is_err());
+
+        assert!(verify_certificate(&Certificate::Small(65537)).is_ok());
+        assert!(verify_certificate(&Certificate::Small(65535)).is_err());
+
+        // 14 generates the group modulo 1031, but 2 has order 515.
+        assert!(verify_certificate(&pratt(1031, 14, &[2, 5, 103])).is_ok());
+        assert!(verify_certificate(&pratt(1031, 2, &[2, 5, 103])).is_err());
+        // The factorization must be complete.
+        assert!(verify_certificate(&pratt(1031, 14, &[2, 103])).is_err());
+        assert!(verify_certificate(&pratt(1031, 14, &[2, 5, 103, 103])).is_err());
+
+        // An exponent this large is rejected before the power is computed.
+        let mut huge = pratt(1031, 14, &[2, 5, 103]);
+        if let Certificate::Pratt(ref mut pratt) = huge {
+            pratt.factors[0].exponent = u32::MAX;
+        }
+        assert!(verify_certificate(&huge).is_err());
+        assert!(certify(&Int::from(1031), &[(Certificate::Small(2), u32::MAX)]).is_err());
+    }
+
+    #[test]
+    fn test_certify() {
+        let pratt = certify(&Int::from(1031), &[small(2), small(5), small(103)]).unwrap();
+        assert_eq!(pratt, self::pratt(1031, 14, &[2, 5, 103]));
+
+        // 2063 - 1 = 2 * 1031, where 1031 > sqrt(2063).
+        let pocklington = certify(&Int::from(2063), &[(pratt.clone(), 1)]).unwrap();
+        match pocklington {
+            Certificate::Pocklington(ref certificate) => {
+                assert_eq!(certificate.factors[0].witness, Int::from(2))
+            }
+            ref other => panic!("unexpected certificate: {:?}", other),
+        }
+
+        // 2^89 - 2 = 2 * 3 * 5 * 17 * 23 * 89 * 353 * 397 * 683 * 2113 *
+        // 2931542417, and the factors up to 397 lie between the cube and
+        // square roots of 2^89 - 1, which needs the Brillhart-Lehmer-Selfridge
+        // test.
+        let m89 = (Int::one() << 89) - 1_usize;
+        let factors: Vec<_> = [2, 3, 5, 17, 23, 89, 353, 397]
+            .iter()
+            .map(|&q| small(q))
+            .collect();
+        let f = factors
+            .iter()
+            .fold(Int::one(), |f, (certificate, _)| f * certificate.n());
+        assert!(&f * &f < m89 && &f * &f * &f > m89);
+
+        let bls = certify(&m89, &factors).unwrap();
+        assert!(verify_certificate(&bls).is_ok());
+        assert!(certify(&(m89 + 2_usize), &factors).is_err());
+
+        match certify(&Int::from(1031), &[small(2), small(5)]) {
+            Err(Error::InvalidParameter { name, .. }) => assert_eq!(name, "factors"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // 1033 * 2063 - 1 = 2 * 19 * 56081, but 1033 * 2063 has no generator.
+        match certify(
+            &Int::from(1033 * 2063),
+            &[small(2), small(19), small(56081)],
+        ) {
+            Err(Error::Verification { .. }) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_certificate_format() {
+        let pratt = certify(&Int::from(1031), &[small(2), small(5), small(103)]).unwrap();
+        let certificate = certify(&Int::from(2063), &[small(2), (pratt, 1)]).unwrap();
+
+        let text = certificate.to_string();
+        assert_eq!(
+            text,
+            "pumpkin-certificate 1\nsmall 2\nsmall 5\nsmall 67\npratt 407 e 2^1 5^1 67^1\n\
+             pratt 80f 5 2^1 407^1\n"
+        );
+        assert_eq!(text.parse::<Certificate>().unwrap(), certificate);
+
+        let commented = format!(
+            "# 2063 = 2 * 1031 + 1\n\n{}",
+            text.replace("pratt", "  pratt")
+        );
+        assert_eq!(commented.parse::<Certificate>().unwrap(), certificate);
+
+        for bad in &[
+            "",
+            "pumpkin-certificate 2\nsmall 2\n",
+            "pumpkin-certificate 1\n",
+            "pumpkin-certificate 1\nsmall 2 3\n",
+            "pumpkin-certificate 1\nsmall 10000000000000000\n",
+            "pumpkin-certificate 1\nlarge 2\n",
+            "pumpkin-certificate 1\nsmall 2\npratt 5 2 2^x\n",
+            "pumpkin-certificate 1\nsmall 2\npratt 5 -2 2^2\n",
+            "pumpkin-certificate 1\npratt 5 2 2^2\nsmall 2\n",
+            "pumpkin-certificate 1\nsmall 3\npocklington 7 3^1\n",
+            "pumpkin-certificate 1\nsmall 2\nsmall 2\n",
+            "pumpkin-certificate 1\nsmall 2\nsmall 3\npratt 3 2 2^1\n",
+            "pumpkin-certificate 1\nsmall 7\npocklington 7 7^1:2\n",
+            "pumpkin-certificate 1\nsmall b\npocklington 7 b^1:2\n",
+        ] {
+            match bad.parse::<Certificate>() {
+                Err(Error::Parse { what, .. }) => assert_eq!(what, "primality certificate"),
+                other => panic!("unexpected result for {:?}: {:?}", bad, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_certificate_format_shared() {
+        // Each line relies on the two before it, so the tree would double in
+        // size with every line if the lines were not shared.
+        let mut text = "pumpkin-certificate 1\nsmall 2\nsmall 3\n".to_string();
+        for n in 4..200 {
+            text += &format!("pocklington {:x} {:x}^1:2 {:x}^1:2\n", n, n - 2, n - 1);
+        }
+
+        let certificate = text.parse::<Certificate>().unwrap();
+        assert_eq!(certificate.n(), Int::from(199));
+        assert_eq!(certificate.to_string().lines().count(), 199);
+        match verify_certificate(&certificate) {
+            Err(Error::Verification { .. }) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -1,19 +1,180 @@
-use ramp::traits::Integer;
-use ramp::{Int, RandomInt};
+pub mod fixed;
+pub mod reference;
 
-use rand::rngs::OsRng;
+#[cfg(feature = "std")]
+use ramp::Int;
+
+#[cfg(feature = "std")]
 use rand::thread_rng;
+use rand::{Rng, RngCore};
+
+use zeroize::Zeroizing;
+
+use alloc::vec::Vec;
 
+use self::reference::Natural;
+use backend::{BigInt, Wiping};
 use error::{Error, Result};
+use report::{GenerationReport, Stopwatch};
+#[cfg(feature = "self-test")]
+use self_test;
+
+#[cfg(feature = "tracing")]
+macro_rules! trace_rejection {
+    ($stage:expr) => {
+        trace!(stage = $stage, "candidate rejected")
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_rejection {
+    ($stage:expr) => {};
+}
 
+/// The smallest bit length accepted by the secure generators.
+pub const MIN_BIT_LENGTH: usize = 512;
+
+#[cfg(feature = "std")]
 pub fn two() -> Int {
     Int::from(2_usize)
 }
+#[cfg(feature = "std")]
 pub fn three() -> Int {
     Int::from(3_usize)
 }
 
-static SMALL_PRIMES: [u32; 2047] = [
+/// Returns the inverse of `a` modulo `modulus`, or `None` if the two share a
+/// factor.
+#[cfg(feature = "std")]
+pub fn mod_inverse(a: &Int, modulus: &Int) -> Option<Int> {
+    let (mut old_r, mut r) = (a % modulus, modulus.clone());
+    let (mut old_s, mut s) = (Int::one(), Int::zero());
+
+    while r != Int::zero() {
+        let (quotient, remainder) = old_r.divmod(&r);
+        old_r = r;
+        r = remainder;
+        let next_s = &old_s - &quotient * &s;
+        old_s = s;
+        s = next_s;
+    }
+
+    if old_r != Int::one() {
+        return None;
+    }
+    if old_s < Int::zero() {
+        old_s += modulus;
+    }
+
+    Some(old_s)
+}
+
+/// Returns the Jacobi symbol `(a / n)`, for odd, positive `n`.
+#[cfg(feature = "std")]
+pub fn jacobi(a: &Int, n: &Int) -> i32 {
+    let mut a = a % n;
+    if a < Int::zero() {
+        a += n;
+    }
+    let mut n = n.clone();
+    let mut symbol = 1;
+
+    while a != Int::zero() {
+        let zeros = a.trailing_zeros();
+        a >>= zeros as usize;
+
+        let n_mod_8 = u64::from(&n) % 8;
+        if zeros % 2 == 1 && (n_mod_8 == 3 || n_mod_8 == 5) {
+            symbol = -symbol;
+        }
+        if u64::from(&a) % 4 == 3 && n_mod_8 % 4 == 3 {
+            symbol = -symbol;
+        }
+
+        let remainder = &n % &a;
+        n = a;
+        a = remainder;
+    }
+
+    if n == Int::one() {
+        symbol
+    } else {
+        0
+    }
+}
+
+/// Returns a square root of `a` modulo the odd prime `p` by the
+/// Tonelli-Shanks algorithm, or `None` if `a` is not a square. A composite
+/// `p` also gives `None`, unless the root happens to be right.
+#[cfg(feature = "std")]
+pub fn sqrt_mod(a: &Int, p: &Int) -> Option<Int> {
+    let one = Int::one();
+    let mut a = a % p;
+    if a < Int::zero() {
+        a += p;
+    }
+    if a == Int::zero() {
+        return Some(a);
+    }
+    if jacobi(&a, p) != 1 {
+        return None;
+    }
+
+    // p - 1 = odd 2^s, and z is a non-square.
+    let minus_one = p - &one;
+    let s = minus_one.trailing_zeros();
+    let odd = &minus_one >> s as usize;
+    let z = (2..1000_u32).map(Int::from).find(|z| jacobi(z, p) == -1)?;
+
+    let mut m = s;
+    let mut c = z.pow_mod(&odd, p);
+    let mut t = a.pow_mod(&odd, p);
+    let mut root = a.pow_mod(&((&odd + 1_usize) >> 1), p);
+
+    while t != one {
+        let mut i = 0;
+        let mut power = t.clone();
+        while power != one {
+            power = power.square() % p;
+            i += 1;
+            if i == m {
+                return None;
+            }
+        }
+
+        let mut b = c;
+        for _ in 0..m - i - 1 {
+            b = b.square() % p;
+        }
+        m = i;
+        c = b.square() % p;
+        t = t * &c % p;
+        root = root * b % p;
+    }
+
+    if root.square() % p == a {
+        Some(root)
+    } else {
+        None
+    }
+}
+
+/// Draws a uniformly random number from `low` (inclusive) to `high`
+/// (exclusive).
+#[cfg(feature = "std")]
+pub fn random_range<R: Rng + ?Sized>(low: &Int, high: &Int, rngesus: &mut R) -> Result<Int> {
+    let range = high - low;
+    let bit_length = range.bit_length() as usize;
+
+    loop {
+        let random: Int = random_bits(rngesus, bit_length)?;
+        if random < range {
+            return Ok(random + low);
+        }
+    }
+}
+
+pub static SMALL_PRIMES: [u32; 2047] = [
     3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
     101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
     197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
@@ -155,36 +316,224 @@ static SMALL_PRIMES: [u32; 2047] = [
 ];
 
 /// Constructs a new prime number with the size of `bit_length` bits, sourced
-/// from an already-initialized `OsRng`.
-pub fn gen_prime(bit_length: usize, rngesus: &mut OsRng) -> Result {
-    if bit_length < 512 {
-        Err(Error::BitLength(bit_length))
+/// from an already-initialized random number generator.
+#[cfg(feature = "std")]
+pub fn gen_prime<R: Rng + ?Sized>(bit_length: usize, rngesus: &mut R) -> Result {
+    gen_prime_recorded(bit_length, rngesus, &mut GenerationReport::default())
+}
+
+/// Constructs a new prime number exactly like `gen_prime()`, additionally
+/// returning a `GenerationReport` describing the work that went into it.
+#[cfg(feature = "std")]
+pub fn gen_prime_with_report<R: Rng + ?Sized>(
+    bit_length: usize,
+    rngesus: &mut R,
+) -> Result<(Int, GenerationReport)> {
+    let started = Stopwatch::start();
+    let mut report = GenerationReport::default();
+    let prime = gen_prime_recorded(bit_length, rngesus, &mut report)?;
+    report.total_time = started.elapsed();
+
+    #[cfg(feature = "tracing")]
+    report.trace("prime");
+
+    Ok((prime, report))
+}
+
+/// Constructs a new prime number, accumulating statistics about the search
+/// into `report`.
+#[cfg(feature = "std")]
+pub fn gen_prime_recorded<R: Rng + ?Sized>(
+    bit_length: usize,
+    rngesus: &mut R,
+    report: &mut GenerationReport,
+) -> Result {
+    check_bit_length(bit_length, MIN_BIT_LENGTH)?;
+    gen_prime_unchecked(bit_length, rngesus, report)
+}
+
+/// Constructs a new prime number of any `bit_length` of at least two,
+/// without enforcing `MIN_BIT_LENGTH`. Callers are responsible for checking
+/// the bit length themselves.
+pub fn gen_prime_unchecked<B: BigInt, R: Rng + ?Sized>(
+    bit_length: usize,
+    rngesus: &mut R,
+    report: &mut GenerationReport,
+) -> Result<B> {
+    #[cfg(feature = "self-test")]
+    self_test::on_first_use::<B>()?;
+
+    gen_prime_untested(bit_length, rngesus, report)
+}
+
+/// Constructs a new prime number exactly like `gen_prime_unchecked()`,
+/// without running the self-tests first. This is what the self-tests
+/// themselves generate with.
+pub fn gen_prime_untested<B: BigInt, R: Rng + ?Sized>(
+    bit_length: usize,
+    rngesus: &mut R,
+    report: &mut GenerationReport,
+) -> Result<B> {
+    #[cfg(feature = "tracing")]
+    let _span = debug_span!("gen_prime", bit_length = bit_length as u64).entered();
+
+    let mut rngesus = CountingRng::new(rngesus);
+
+    // In order to remove as much bias from the system as possible, test
+    // 500 potential candidates at a time before re-seeding the candidate
+    // with a new random number.
+    loop {
+        let sampling = Stopwatch::start();
+        let mut bytes =
+            random_bytes(&mut rngesus, bit_length).map_err(|err| rngesus.failure_or(err))?;
+
+        // We first want to make sure that the candidate is in the appropriate
+        // size range before continuing. This can easily be done by setting the
+        // two most significant bits of the candidate number to 1.
+        set_bit(&mut bytes, bit_length - 1);
+        set_bit(&mut bytes, bit_length - 2);
+
+        // Next, flip the least significant bit to 1, to make sure the candidate
+        // is odd (no sense in testing primality on an even number, after all).
+        set_bit(&mut bytes, 0);
+
+        let mut candidate = B::from_be_bytes(&bytes);
+
+        report.candidates += 1;
+        report.sampling_time += sampling.elapsed();
+
+        // The bases of the probable prime tests come from the same
+        // generator, so that a caller-supplied generator is the only source
+        // of randomness.
+        if is_prime_recorded(&candidate, &mut rngesus, report) {
+            report.rng_bytes += rngesus.bytes;
+            return Ok(candidate);
+        }
+
+        // A generator that failed while drawing a basis got the candidate
+        // rejected, and must not be drawn from again.
+        if let Some(err) = rngesus.failure.take() {
+            candidate.wipe();
+            return Err(Error::from_rng(err));
+        }
+
+        // Rejected candidates still say something about the generator's
+        // state, so they are not left behind in freed memory.
+        candidate.wipe();
+    }
+}
+
+/// Constructs a new prime number exactly like `gen_prime_unchecked()`, then
+/// confirms it with the Baillie-PSW test of `reference`, which shares none
+/// of the arithmetic that found it.
+///
+/// Fails with `Error::Inconsistent`, rather than trying another candidate,
+/// if the two disagree: that only happens when something is broken.
+pub fn gen_prime_verified<B: BigInt, R: Rng + ?Sized>(
+    bit_length: usize,
+    rngesus: &mut R,
+    report: &mut GenerationReport,
+) -> Result<B> {
+    let mut prime: B = gen_prime_unchecked(bit_length, rngesus, report)?;
+
+    let bytes = Zeroizing::new(prime.to_be_bytes());
+    let natural = Natural::from_be_bytes(&bytes);
+
+    let disagreement = if natural.bit_length() != bit_length {
+        Some("the bit length of the generated prime")
+    } else if !reference::is_prime_bpsw(&natural) {
+        Some("the generated prime is composite by Baillie-PSW")
     } else {
-        let mut candidate: Int;
-
-        // In order to remove as much bias from the system as possible, test
-        // 500 potential candidates at a time before re-seeding the candidate
-        // with a new random number.
-        loop {
-            candidate = rngesus.gen_uint(bit_length);
-
-            // We first want to make sure that the candidate is in the appropriate
-            // size range before continuing. This can easily be done by setting the
-            // two most significant bits of the candidate number to 1.
-            candidate.set_bit(1, true);
-            candidate.set_bit(2, true);
-
-            // Next, flip the least significant bit to 1, to make sure the candidate
-            // is odd (no sense in testing primality on an even number, after all).
-            candidate.set_bit(bit_length as u32, true);
-
-            if is_prime(&candidate) {
-                return Ok(candidate);
-            }
+        None
+    };
+
+    match disagreement {
+        Some(what) => {
+            prime.wipe();
+            Err(Error::Inconsistent { what })
         }
+        None => Ok(prime),
     }
 }
 
+/// Fails with `Error::BitLength` when `bit_length` is below `minimum`.
+pub fn check_bit_length(bit_length: usize, minimum: usize) -> Result<()> {
+    if bit_length < minimum {
+        Err(Error::BitLength {
+            length: bit_length,
+            minimum,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Draws a uniformly random, non-negative number of at most `bit_length`
+/// bits from `rngesus`, reporting a failing generator as `Error::Rng` or
+/// `Error::EntropyHealth`.
+pub fn random_bits<B: BigInt, R: RngCore + ?Sized>(
+    rngesus: &mut R,
+    bit_length: usize,
+) -> Result<B> {
+    random_bytes(rngesus, bit_length).map(|bytes| B::from_be_bytes(&bytes))
+}
+
+/// Draws the big-endian bytes of a uniformly random number of at most
+/// `bit_length` bits. The bytes are wiped when dropped.
+pub fn random_bytes<R: RngCore + ?Sized>(
+    rngesus: &mut R,
+    bit_length: usize,
+) -> Result<Zeroizing<Vec<u8>>> {
+    let mut bytes = Zeroizing::new(vec![0u8; bit_length.div_ceil(8)]);
+    rngesus
+        .try_fill_bytes(&mut bytes)
+        .map_err(Error::from_rng)?;
+
+    let excess = bytes.len() * 8 - bit_length;
+    if excess > 0 {
+        bytes[0] &= 0xff >> excess;
+    }
+
+    Ok(bytes)
+}
+
+/// Sets bit `bit` of the big-endian number held in `bytes`.
+pub fn set_bit(bytes: &mut [u8], bit: usize) {
+    let index = bytes.len() - 1 - bit / 8;
+    bytes[index] |= 1 << (bit % 8);
+}
+
+/// Returns the number of significant bits in the big-endian number held in
+/// `bytes`.
+pub fn bytes_bit_length(bytes: &[u8]) -> usize {
+    match bytes.iter().position(|&byte| byte != 0) {
+        Some(start) => (bytes.len() - start) * 8 - bytes[start].leading_zeros() as usize,
+        None => 0,
+    }
+}
+
+/// Returns the remainder of the big-endian number held in `bytes` divided by
+/// `divisor`.
+pub fn bytes_rem(bytes: &[u8], divisor: u32) -> u32 {
+    let divisor = u64::from(divisor);
+    let (head, tail) = bytes.split_at(bytes.len() % 4);
+
+    // Both the remainder and each four-byte word are below 2^32, so folding
+    // in a word at a time never overflows.
+    let mut remainder = be_word(head) % divisor;
+    for word in tail.chunks(4) {
+        remainder = ((remainder << 32) | be_word(word)) % divisor;
+    }
+
+    remainder as u32
+}
+
+fn be_word(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0, |word, &byte| (word << 8) | u64::from(byte))
+}
+
 /// Runs the following three tests on a given `candidate` to determine
 /// primality:
 ///
@@ -194,88 +543,188 @@ pub fn gen_prime(bit_length: usize, rngesus: &mut OsRng) -> Result {
 ///
 /// Should the candidate number pass all three tests, then you can be
 /// reasonably sure that the candidate is prime.
-pub fn is_prime(candidate: &Int) -> bool {
+///
+/// The bases of the probable prime tests are drawn from the thread-local
+/// random number generator.
+#[cfg(feature = "std")]
+pub fn is_prime<B: BigInt>(candidate: &B) -> bool {
+    is_prime_with_rng(candidate, &mut thread_rng())
+}
+
+/// Runs the same tests as `is_prime()`, drawing the bases of the probable
+/// prime tests from `rngesus`.
+///
+/// Should `rngesus` fail, the candidate is reported as composite.
+pub fn is_prime_with_rng<B: BigInt, R: RngCore + ?Sized>(candidate: &B, rngesus: &mut R) -> bool {
+    is_prime_recorded(candidate, rngesus, &mut GenerationReport::default())
+}
+
+/// Runs the same tests as `is_prime_with_rng()`, recording which stage (if
+/// any) rejected the candidate and how long each stage took into `report`.
+pub fn is_prime_recorded<B: BigInt, R: RngCore + ?Sized>(
+    candidate: &B,
+    rngesus: &mut R,
+    report: &mut GenerationReport,
+) -> bool {
     // First, iterate through the array of small primes and divide the
     // candidate. If the candidate divides any of them, then we know the number
     // is a multiple of that prime; that is, the candidate is composite.
 
-    if *candidate == Int::from(2) {
+    if *candidate <= B::from_u64(1) {
+        return false;
+    }
+
+    let bytes = Zeroizing::new(candidate.to_be_bytes());
+
+    if *bytes == [2] {
         return true;
     }
 
-    if !candidate.is_odd() {
+    if bytes[bytes.len() - 1] & 1 == 0 {
+        report.trial_division_rejections += 1;
+        trace_rejection!("trial_division");
         return false;
     }
 
-    for p in SMALL_PRIMES.into_iter() {
-        let prime: Int = Int::from(*p);
-        let (_, r) = candidate.divmod(&prime);
+    let stage = Stopwatch::start();
+    for &prime in SMALL_PRIMES.iter() {
+        if bytes_rem(&bytes, prime) == 0 {
+            report.trial_division_time += stage.elapsed();
 
-        if r == Int::zero() {
-            return *candidate == prime;
+            if *candidate == B::from_u64(u64::from(prime)) {
+                return true;
+            }
+
+            report.trial_division_rejections += 1;
+            trace_rejection!("trial_division");
+            return false;
         }
     }
+    report.trial_division_time += stage.elapsed();
+
+    // Candidates of every common key size run the remaining tests on
+    // fixed-width integers that live on the stack. Anything wider falls back
+    // to the backend's own arithmetic.
+    let mut rngesus = rngesus;
+    fixed::with_modulus(&bytes, |modulus| {
+        probable_prime_stages(modulus, &mut rngesus, report)
+    })
+    .unwrap_or_else(|| probable_prime_stages(&Generic(candidate), &mut rngesus, report))
+}
+
+/// The probabilistic stages of `is_prime()`, run against a single odd
+/// candidate that has already passed trial division.
+pub trait ProbablePrime {
+    /// Runs a Fermat test to a base drawn from `rngesus`. These tests fail
+    /// if `rngesus` does.
+    fn fermat(&self, rngesus: &mut dyn RngCore) -> bool;
+
+    /// Runs `limit` rounds of Miller-Rabin to bases drawn from `rngesus`,
+    /// counting each round executed into `rounds`.
+    fn miller_rabin(&self, limit: usize, rngesus: &mut dyn RngCore, rounds: &mut u64) -> bool;
+}
+
+/// Runs the probable prime tests with the arithmetic of the backend itself.
+struct Generic<'a, B: 'a>(&'a B);
+
+impl<'a, B: BigInt> ProbablePrime for Generic<'a, B> {
+    fn fermat(&self, rngesus: &mut dyn RngCore) -> bool {
+        fermat(self.0, rngesus)
+    }
 
+    fn miller_rabin(&self, limit: usize, rngesus: &mut dyn RngCore, rounds: &mut u64) -> bool {
+        miller_rabin_counted(self.0, limit, rngesus, rounds)
+    }
+}
+
+fn probable_prime_stages(
+    tests: &dyn ProbablePrime,
+    rngesus: &mut dyn RngCore,
+    report: &mut GenerationReport,
+) -> bool {
     // Second, do a Fermat test on the candidate
-    if !fermat(candidate) {
+    let stage = Stopwatch::start();
+    let passed = tests.fermat(rngesus);
+    report.fermat_time += stage.elapsed();
+
+    if !passed {
+        report.fermat_rejections += 1;
+        trace_rejection!("fermat");
         return false;
     }
 
     // Finally, do a Miller-Rabin test
     // See https://eprint.iacr.org/2018/749.pdf for good choices on appropriate number of tests
-    if !miller_rabin(candidate, 15) {
+    let stage = Stopwatch::start();
+    let passed = tests.miller_rabin(15, rngesus, &mut report.miller_rabin_rounds);
+    report.miller_rabin_time += stage.elapsed();
+
+    if !passed {
+        report.miller_rabin_rejections += 1;
+        trace_rejection!("miller_rabin");
         return false;
     }
 
     true
 }
 
-fn fermat(candidate: &Int) -> bool {
+fn fermat<B: BigInt>(candidate: &B, rngesus: &mut dyn RngCore) -> bool {
     // Perform Fermat's little theorem on the candidate to determine probable
     // primality.
-    let random = thread_rng().gen_int_range(&Int::one(), candidate);
+    let random = match random_below(1, candidate, rngesus) {
+        Some(random) => random,
+        None => return false,
+    };
 
-    let result = mod_exp(&random, &(candidate - 1), candidate);
+    let result = random.pow_mod(&minus_one(candidate), candidate);
 
-    result == Int::one()
+    result == B::from_u64(1)
 }
 
-fn mod_exp(base: &Int, exponent: &Int, modulus: &Int) -> Int {
-    let mut result = Int::one();
-    let mut base = base.clone();
-    let mut exponent = exponent.clone();
+/// Draws a random number from `low` (inclusive) to `candidate` (exclusive),
+/// or returns `None` if `rngesus` fails.
+fn random_below<B: BigInt>(low: u64, candidate: &B, rngesus: &mut dyn RngCore) -> Option<B> {
+    let bit_length = bytes_bit_length(&candidate.to_be_bytes());
+    let low = B::from_u64(low);
 
-    while exponent > Int::zero() {
-        if exponent.trailing_zeros() == 0 {
-            result = (&base * result) % modulus;
-        }
+    loop {
+        let random: B = random_bits(rngesus, bit_length).ok()?;
 
-        base = base.pow_mod(&two(), modulus);
-        exponent >>= 1;
+        if random >= low && random < *candidate {
+            return Some(random);
+        }
     }
+}
 
-    result
+/// Returns `candidate - 1` for an odd `candidate`.
+fn minus_one<B: BigInt>(candidate: &B) -> Wiping<B> {
+    let mut bytes = Zeroizing::new(candidate.to_be_bytes());
+    let last = bytes.len() - 1;
+    bytes[last] &= 0xfe;
+
+    Wiping(B::from_be_bytes(&bytes))
 }
 
+#[cfg(test)]
 fn miller_rabin(candidate: &Int, limit: usize) -> bool {
-    // Perform the Miller-Rabin test on the candidate, 'limit' times.
-    let (s, d) = rewrite(candidate);
+    miller_rabin_counted(candidate, limit, &mut thread_rng(), &mut 0)
+}
 
+fn miller_rabin_counted<B: BigInt>(
+    candidate: &B,
+    limit: usize,
+    rngesus: &mut dyn RngCore,
+    rounds: &mut u64,
+) -> bool {
+    // Perform the Miller-Rabin test on the candidate, 'limit' times.
     for _ in 0..limit {
-        let basis = thread_rng().gen_int_range(&two(), candidate);
-        let mut x = mod_exp(&basis, &d, candidate);
-
-        if x == Int::one() || x == (candidate - 1) {
-            continue;
-        } else {
-            for _ in Int::one()..s - 1 {
-                x = mod_exp(&x, &two(), candidate);
-                if x == Int::one() {
-                    return false;
-                } else if x == candidate - 1 {
-                    break;
-                }
-            }
+        *rounds += 1;
+
+        let basis = match random_below(2, candidate, rngesus) {
+            Some(basis) => basis,
+            None => return false,
+        };
+        if !is_strong_probable_prime(candidate, &basis) {
             return false;
         }
     }
@@ -283,37 +732,169 @@ fn miller_rabin(candidate: &Int, limit: usize) -> bool {
     true
 }
 
-fn rewrite(candidate: &Int) -> (Int, Int) {
-    let mut d = candidate - 1_usize;
-    let mut s = Int::zero();
+/// Runs the Baillie-PSW test on `candidate`, with the plain arithmetic of
+/// `reference` rather than that of the backend.
+pub fn is_prime_bpsw<B: BigInt>(candidate: &B) -> bool {
+    let bytes = Zeroizing::new(candidate.to_be_bytes());
+    reference::is_prime_bpsw(&Natural::from_be_bytes(&bytes))
+}
+
+/// Runs Miller-Rabin against each of the given fixed `bases`, rather than
+/// against randomly chosen ones. With a large enough set of bases this is a
+/// deterministic test for candidates below a known bound.
+pub fn miller_rabin_with_bases<B: BigInt>(candidate: &B, bases: &[u32]) -> bool {
+    let one = B::from_u64(1);
+    let zero = B::from_u64(0);
+
+    bases.iter().all(|&basis| {
+        let basis = B::from_u64(u64::from(basis)).pow_mod(&one, candidate);
+        basis == zero || is_strong_probable_prime(candidate, &basis)
+    })
+}
+
+/// Runs a single round of Miller-Rabin on the odd `candidate`, returning
+/// `false` if `basis` proves it composite.
+fn is_strong_probable_prime<B: BigInt>(candidate: &B, basis: &B) -> bool {
+    let one = B::from_u64(1);
+    let two = B::from_u64(2);
+    let minus_one = minus_one(candidate);
+    let (s, d) = rewrite(&*minus_one);
+    let mut x = basis.pow_mod(&*d, candidate);
+
+    if x == one || x == *minus_one {
+        return true;
+    }
+
+    for _ in 1..s {
+        x = x.pow_mod(&two, candidate);
+        if x == *minus_one {
+            return true;
+        } else if x == one {
+            return false;
+        }
+    }
+
+    false
+}
+
+/// Writes the even, non-zero `minus_one` as `2^s * d`, with `d` odd,
+/// returning `(s, d)`.
+fn rewrite<B: BigInt>(minus_one: &B) -> (usize, Wiping<B>) {
+    let mut bytes = Zeroizing::new(minus_one.to_be_bytes());
+    let zero_bytes = bytes.iter().rev().take_while(|&&byte| byte == 0).count();
+    let length = bytes.len() - zero_bytes;
+    bytes.truncate(length);
+
+    let shift = bytes[bytes.len() - 1].trailing_zeros();
+    let mut carry = 0;
+    if shift > 0 {
+        for byte in bytes.iter_mut() {
+            let shifted = (*byte >> shift) | carry;
+            carry = *byte << (8 - shift);
+            *byte = shifted;
+        }
+    }
 
-    while d.trailing_zeros() == 0 {
-        //
-        d = &d >> 1;
-        s = &s + 1;
+    (
+        zero_bytes * 8 + shift as usize,
+        Wiping(B::from_be_bytes(&bytes)),
+    )
+}
+
+/// Wraps a random number generator, keeping track of how many bytes have
+/// been drawn from it, and of the first error it returned.
+///
+/// The probable prime tests can only report a failing generator by
+/// rejecting the candidate, so generators check `failure` after each one.
+pub struct CountingRng<'a, R: 'a + ?Sized> {
+    inner: &'a mut R,
+    bytes: u64,
+    pub failure: Option<::rand::Error>,
+}
+
+impl<'a, R: RngCore + ?Sized> CountingRng<'a, R> {
+    pub fn new(inner: &'a mut R) -> CountingRng<'a, R> {
+        CountingRng {
+            inner,
+            bytes: 0,
+            failure: None,
+        }
     }
 
-    (s, d)
+    /// Returns the generator's original error in place of `err`, which
+    /// carries a copy of it.
+    pub fn failure_or(&mut self, err: Error) -> Error {
+        self.failure.take().map(Error::from_rng).unwrap_or(err)
+    }
+}
+
+impl<'a, R: RngCore + ?Sized> RngCore for CountingRng<'a, R> {
+    fn next_u32(&mut self) -> u32 {
+        self.bytes += 4;
+        self.inner.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.bytes += 8;
+        self.inner.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.bytes += dest.len() as u64;
+        self.inner.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> ::core::result::Result<(), ::rand::Error> {
+        self.bytes += dest.len() as u64;
+        self.inner.try_fill_bytes(dest).map_err(|err| {
+            let copy = ::rand::Error::new(err.kind, err.msg);
+            self.failure.get_or_insert(err);
+            copy
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     extern crate ramp;
 
-    use super::{fermat, miller_rabin};
+    use super::{fermat, gen_prime, miller_rabin};
+    use error::Error;
     use ramp::Int;
+    use rand::{self, thread_rng, ErrorKind, RngCore};
+    use std::error::Error as StdError;
+
+    struct BrokenRng;
+
+    impl RngCore for BrokenRng {
+        fn next_u32(&mut self) -> u32 {
+            panic!("BrokenRng cannot produce numbers")
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            panic!("BrokenRng cannot produce numbers")
+        }
+
+        fn fill_bytes(&mut self, _: &mut [u8]) {
+            panic!("BrokenRng cannot produce numbers")
+        }
+
+        fn try_fill_bytes(&mut self, _: &mut [u8]) -> Result<(), rand::Error> {
+            Err(rand::Error::new(ErrorKind::Unavailable, "broken"))
+        }
+    }
 
     #[test]
     fn test_fermat_prime() {
         let candidate = Int::from(65537);
-        let result = fermat(&candidate);
+        let result = fermat(&candidate, &mut thread_rng());
         assert_eq!(result, true);
     }
 
     #[test]
     fn test_fermat_not_prime() {
         let candidate = Int::from(65535);
-        let result = fermat(&candidate);
+        let result = fermat(&candidate, &mut thread_rng());
         assert_eq!(result, false);
     }
 
@@ -330,4 +911,12 @@ mod tests {
         let result = miller_rabin(&candidate, 64);
         assert_eq!(result, false);
     }
+
+    #[test]
+    fn test_gen_prime_rng_failure() {
+        match gen_prime(512, &mut BrokenRng) {
+            Err(ref err @ Error::Rng(_)) => assert!(err.source().is_some()),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
 }
@@ -0,0 +1,682 @@
+//! Fixed-width, stack-allocated arithmetic for the probable prime tests.
+//!
+//! Exponentiation dominates the cost of testing a candidate, and doing it
+//! with heap-allocated integers means allocating on every multiplication.
+//! Candidates of up to `MAX_BITS` bits are instead copied once into an array
+//! of 64-bit limbs, sized for the smallest of the supported widths that
+//! holds them, and tested with Montgomery multiplication entirely on the
+//! stack.
+
+use rand::RngCore;
+
+use zeroize::Zeroize;
+
+use core::cmp::Ordering;
+
+use super::ProbablePrime;
+
+/// The widest candidate, in bits, that is tested with fixed-width integers.
+pub const MAX_BITS: usize = 8192;
+
+/// Runs `f` against `candidate` loaded into the narrowest fixed-width
+/// modulus that holds it, or returns `None` if it is wider than `MAX_BITS`.
+///
+/// `candidate` holds the big-endian bytes of an odd number greater than one.
+pub fn with_modulus<T, F>(candidate: &[u8], f: F) -> Option<T>
+where
+    F: FnOnce(&dyn ProbablePrime) -> T,
+{
+    if candidate.len() * 8 > MAX_BITS {
+        return None;
+    }
+
+    macro_rules! dispatch {
+        ($($limbs:expr),*) => {
+            $(
+                if candidate.len() <= $limbs * 8 {
+                    return Some(f(&Modulus::<$limbs>::new(candidate)));
+                }
+            )*
+        };
+    }
+
+    dispatch!(4, 8, 16, 24, 32, 48, 64, 96, 128);
+
+    None
+}
+
+/// Runs a Fermat test and `rounds` rounds of Miller-Rabin on `candidate`,
+/// which has exactly `bit_length` bits, in constant time with respect to its
+/// value, with bases drawn from `rngesus`. Returns `None` if it is wider
+/// than `MAX_BITS`.
+///
+/// `candidate` holds the big-endian bytes of an odd number, and must not be
+/// divisible by any of the small primes.
+#[cfg(feature = "std")]
+pub fn is_probable_prime_ct<R: RngCore + ?Sized>(
+    candidate: &[u8],
+    bit_length: usize,
+    rounds: usize,
+    rngesus: &mut R,
+) -> Option<bool> {
+    macro_rules! dispatch {
+        ($($limbs:expr),*) => {
+            $(
+                if bit_length <= $limbs * 64 {
+                    let modulus = Modulus::<$limbs>::new(candidate);
+                    return Some(modulus.is_probable_prime_ct(bit_length, rounds, rngesus));
+                }
+            )*
+        };
+    }
+
+    dispatch!(4, 8, 16, 24, 32, 48, 64, 96, 128);
+
+    None
+}
+
+/// An unsigned integer of `LIMBS` 64-bit limbs, least significant first.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Uint<const LIMBS: usize>([u64; LIMBS]);
+
+impl<const LIMBS: usize> Uint<LIMBS> {
+    fn from_u64(number: u64) -> Uint<LIMBS> {
+        let mut limbs = [0; LIMBS];
+        limbs[0] = number;
+
+        Uint(limbs)
+    }
+
+    fn from_be_bytes(bytes: &[u8]) -> Uint<LIMBS> {
+        let mut limbs = [0; LIMBS];
+        for (i, &byte) in bytes.iter().rev().enumerate() {
+            limbs[i / 8] |= u64::from(byte) << (8 * (i % 8));
+        }
+
+        Uint(limbs)
+    }
+
+    fn bit_length(&self) -> usize {
+        match self.0.iter().rposition(|&limb| limb != 0) {
+            Some(top) => top * 64 + 64 - self.0[top].leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    fn shr(&self, bits: usize) -> Uint<LIMBS> {
+        let (limbs, bits) = (bits / 64, bits % 64);
+        let mut result = [0; LIMBS];
+
+        for (i, limb) in result.iter_mut().enumerate().take(LIMBS - limbs) {
+            *limb = self.0[i + limbs] >> bits;
+            if bits > 0 && i + limbs + 1 < LIMBS {
+                *limb |= self.0[i + limbs + 1] << (64 - bits);
+            }
+        }
+
+        Uint(result)
+    }
+
+    /// Subtracts `other`, returning whether the subtraction wrapped.
+    fn sub_assign(&mut self, other: &Uint<LIMBS>) -> bool {
+        let mut borrow = false;
+        for (limb, &subtrahend) in self.0.iter_mut().zip(other.0.iter()) {
+            let (difference, first) = limb.overflowing_sub(subtrahend);
+            let (difference, second) = difference.overflowing_sub(borrow as u64);
+            *limb = difference;
+            borrow = first | second;
+        }
+
+        borrow
+    }
+
+    /// Doubles `self` modulo `modulus`, where `self < modulus`, without
+    /// branching on either.
+    fn double_mod(&mut self, modulus: &Uint<LIMBS>) {
+        let carry = self.0[LIMBS - 1] >> 63;
+        for i in (1..LIMBS).rev() {
+            self.0[i] = (self.0[i] << 1) | (self.0[i - 1] >> 63);
+        }
+        self.0[0] <<= 1;
+
+        self.reduce_once(carry, modulus);
+    }
+
+    /// Adds `other` modulo `modulus`, where both are below `modulus`,
+    /// without branching on any of them.
+    #[cfg(feature = "std")]
+    fn add_mod(&mut self, other: &Uint<LIMBS>, modulus: &Uint<LIMBS>) {
+        let mut carry = false;
+        for (limb, &addend) in self.0.iter_mut().zip(other.0.iter()) {
+            let (sum, first) = limb.overflowing_add(addend);
+            let (sum, second) = sum.overflowing_add(carry as u64);
+            *limb = sum;
+            carry = first | second;
+        }
+
+        self.reduce_once(carry as u64, modulus);
+    }
+
+    /// Subtracts `modulus` if `self`, with `high` as an extra top limb of
+    /// zero or one, is at least `modulus`. Does not branch on either.
+    fn reduce_once(&mut self, high: u64, modulus: &Uint<LIMBS>) {
+        let mut reduced = *self;
+        let borrow = reduced.sub_assign(modulus) as u64;
+
+        *self = Uint::select(self, &reduced, mask(high | (borrow ^ 1)));
+    }
+
+    /// Returns `b` where `choice` is all ones, and `a` where it is zero.
+    fn select(a: &Uint<LIMBS>, b: &Uint<LIMBS>, choice: u64) -> Uint<LIMBS> {
+        let mut result = *a;
+        for (limb, &other) in result.0.iter_mut().zip(b.0.iter()) {
+            *limb ^= (*limb ^ other) & choice;
+        }
+
+        result
+    }
+
+    /// Returns all ones if `self == other`, and zero otherwise.
+    #[cfg(feature = "std")]
+    fn ct_eq(&self, other: &Uint<LIMBS>) -> u64 {
+        let difference = self
+            .0
+            .iter()
+            .zip(other.0.iter())
+            .fold(0, |difference, (&a, &b)| difference | (a ^ b));
+
+        mask(((difference | difference.wrapping_neg()) >> 63) ^ 1)
+    }
+}
+
+/// Widens the low bit of `bit` into a mask of all ones or all zeros.
+fn mask(bit: u64) -> u64 {
+    (bit & 1).wrapping_neg()
+}
+
+/// Draws a `u64` from `rngesus`, or returns `None` if it fails.
+fn try_next_u64<R: RngCore + ?Sized>(rngesus: &mut R) -> Option<u64> {
+    let mut bytes = [0u8; 8];
+    rngesus.try_fill_bytes(&mut bytes).ok()?;
+
+    Some(u64::from_le_bytes(bytes))
+}
+
+/// Returns all ones if `a < b`, and zero otherwise, for `a, b < 2^63`.
+#[cfg(feature = "std")]
+fn ct_lt(a: u64, b: u64) -> u64 {
+    mask(a.wrapping_sub(b) >> 63)
+}
+
+impl<const LIMBS: usize> PartialOrd for Uint<LIMBS> {
+    fn partial_cmp(&self, other: &Uint<LIMBS>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<const LIMBS: usize> Ord for Uint<LIMBS> {
+    fn cmp(&self, other: &Uint<LIMBS>) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+/// An odd modulus together with the constants needed for Montgomery
+/// multiplication, where `R = 2^(64 * LIMBS)`.
+struct Modulus<const LIMBS: usize> {
+    n: Uint<LIMBS>,
+    /// `-n^-1 mod 2^64`.
+    n_inv: u64,
+    /// `R mod n`, i.e. one in Montgomery form.
+    one: Uint<LIMBS>,
+    /// `-R mod n`, i.e. `n - 1` in Montgomery form.
+    minus_one: Uint<LIMBS>,
+    /// `R^2 mod n`, used to move numbers into Montgomery form.
+    r2: Uint<LIMBS>,
+    /// `n - 1`, the Fermat exponent.
+    n_minus_one: Uint<LIMBS>,
+    /// `s` and `d` such that `n - 1 = 2^s * d` with `d` odd.
+    s: u64,
+    d: Uint<LIMBS>,
+}
+
+impl<const LIMBS: usize> Modulus<LIMBS> {
+    fn new(bytes: &[u8]) -> Modulus<LIMBS> {
+        let n = Uint::from_be_bytes(bytes);
+
+        // Newton's iteration doubles the number of correct low bits each
+        // step, starting from the one bit that is right for any odd `n`.
+        let mut inv = 1_u64;
+        for _ in 0..6 {
+            inv = inv.wrapping_mul(2_u64.wrapping_sub(n.0[0].wrapping_mul(inv)));
+        }
+
+        // Reaching R mod n and R^2 mod n by repeated doubling avoids needing
+        // a division routine.
+        let mut one = Uint::from_u64(1);
+        for _ in 0..LIMBS * 64 {
+            one.double_mod(&n);
+        }
+
+        let mut r2 = one;
+        for _ in 0..LIMBS * 64 {
+            r2.double_mod(&n);
+        }
+
+        let mut minus_one = n;
+        minus_one.sub_assign(&one);
+
+        let mut n_minus_one = n;
+        n_minus_one.0[0] &= !1;
+
+        // Write n - 1 as 2^s * d by shifting one bit at a time, for a fixed
+        // number of steps, keeping only the shifts below the lowest set bit.
+        let mut s = 0_u64;
+        let mut d = n_minus_one;
+        let mut seen = 0_u64;
+        for _ in 0..LIMBS * 64 {
+            seen |= d.0[0] & 1;
+            s += seen ^ 1;
+
+            let shifted = d.shr(1);
+            d = Uint::select(&shifted, &d, mask(seen));
+        }
+
+        Modulus {
+            n,
+            n_inv: inv.wrapping_neg(),
+            one,
+            minus_one,
+            r2,
+            n_minus_one,
+            s,
+            d,
+        }
+    }
+
+    /// Computes `a * b / R mod n`, interleaving each row of the product
+    /// with its reduction.
+    fn mul(&self, a: &Uint<LIMBS>, b: &Uint<LIMBS>) -> Uint<LIMBS> {
+        let mut t = [0_u64; LIMBS];
+        let mut t_high = 0_u64;
+
+        for &b_limb in b.0.iter() {
+            let product = u128::from(t[0]) + u128::from(a.0[0]) * u128::from(b_limb);
+            let m = (product as u64).wrapping_mul(self.n_inv);
+            let reduced = u128::from(product as u64) + u128::from(m) * u128::from(self.n.0[0]);
+            let mut product_carry = product >> 64;
+            let mut reduced_carry = reduced >> 64;
+
+            for j in 1..LIMBS {
+                let product =
+                    u128::from(t[j]) + u128::from(a.0[j]) * u128::from(b_limb) + product_carry;
+                let reduced = u128::from(product as u64)
+                    + u128::from(m) * u128::from(self.n.0[j])
+                    + reduced_carry;
+                t[j - 1] = reduced as u64;
+                product_carry = product >> 64;
+                reduced_carry = reduced >> 64;
+            }
+
+            let top = u128::from(t_high) + product_carry + reduced_carry;
+            t[LIMBS - 1] = top as u64;
+            t_high = (top >> 64) as u64;
+        }
+
+        let mut result = Uint(t);
+        result.reduce_once(t_high, &self.n);
+
+        result
+    }
+
+    fn to_montgomery(&self, a: &Uint<LIMBS>) -> Uint<LIMBS> {
+        self.mul(a, &self.r2)
+    }
+
+    #[cfg(test)]
+    fn retrieve(&self, a: &Uint<LIMBS>) -> Uint<LIMBS> {
+        self.mul(a, &Uint::from_u64(1))
+    }
+
+    /// Raises the Montgomery form `base` to `exponent`, four bits at a time.
+    fn pow(&self, base: &Uint<LIMBS>, exponent: &Uint<LIMBS>) -> Uint<LIMBS> {
+        let mut table = [self.one; 16];
+        for i in 1..16 {
+            table[i] = self.mul(&table[i - 1], base);
+        }
+
+        let mut result = self.one;
+        let windows = exponent.bit_length().div_ceil(4);
+        for window in (0..windows).rev() {
+            for _ in 0..4 {
+                result = self.mul(&result, &result);
+            }
+
+            let bits = (exponent.0[window / 16] >> (4 * (window % 16))) & 0xf;
+            if bits != 0 {
+                result = self.mul(&result, &table[bits as usize]);
+            }
+        }
+
+        result
+    }
+
+    /// Raises the Montgomery form `base` to `exponent`, which has at most
+    /// `bits` bits, without branching on or indexing memory by either.
+    ///
+    /// Every window multiplies by a table entry, selected by touching all of
+    /// the entries, and the number of windows depends only on `bits`.
+    #[cfg(feature = "std")]
+    fn pow_ct(&self, base: &Uint<LIMBS>, exponent: &Uint<LIMBS>, bits: usize) -> Uint<LIMBS> {
+        let mut table = [self.one; 16];
+        for i in 1..16 {
+            table[i] = self.mul(&table[i - 1], base);
+        }
+
+        let mut result = self.one;
+        for window in (0..bits.div_ceil(4)).rev() {
+            for _ in 0..4 {
+                result = self.mul(&result, &result);
+            }
+
+            let index = (exponent.0[window / 16] >> (4 * (window % 16))) & 0xf;
+            let mut entry = self.one;
+            for (i, candidate) in table.iter().enumerate() {
+                let hit = (i as u64 ^ index).wrapping_sub(1) >> 63;
+                entry = Uint::select(&entry, candidate, mask(hit));
+            }
+
+            result = self.mul(&result, &entry);
+        }
+
+        result
+    }
+
+    /// Draws a random basis, uniform between two and `n - 2` up to a bias of
+    /// 2^-64, in Montgomery form, or returns `None` if `rngesus` fails.
+    ///
+    /// A number `x` one limb wider than `n` is reduced to `x / R mod n` with
+    /// the Montgomery multiplication, which never compares against `n`, and
+    /// taken as the Montgomery form of the basis. The draw is repeated only
+    /// if it lands on zero, one or `n - 1`, which for `n` of any real size
+    /// does not happen.
+    #[cfg(feature = "std")]
+    fn random_basis_ct<R: RngCore + ?Sized>(&self, rngesus: &mut R) -> Option<Uint<LIMBS>> {
+        loop {
+            let mut low = Uint([0; LIMBS]);
+            for limb in low.0.iter_mut() {
+                *limb = try_next_u64(rngesus)?;
+            }
+            let high = Uint::from_u64(try_next_u64(rngesus)?);
+
+            // x / R = low / R + high, and high < 2^64 may exceed a small n.
+            let mut basis = self.mul(&low, &Uint::from_u64(1));
+            basis.add_mod(
+                &self.mul(&self.to_montgomery(&high), &Uint::from_u64(1)),
+                &self.n,
+            );
+            low.0.zeroize();
+
+            let trivial = basis.ct_eq(&Uint([0; LIMBS]))
+                | basis.ct_eq(&self.one)
+                | basis.ct_eq(&self.minus_one);
+            if trivial == 0 {
+                return Some(basis);
+            }
+        }
+    }
+
+    /// Runs a Fermat test followed by `rounds` rounds of Miller-Rabin, where
+    /// `n` has `bits` bits, without branching on `n` or on intermediate
+    /// values. Only the outcome of a whole round decides whether to continue.
+    #[cfg(feature = "std")]
+    fn is_probable_prime_ct<R: RngCore + ?Sized>(
+        &self,
+        bits: usize,
+        rounds: usize,
+        rngesus: &mut R,
+    ) -> bool {
+        let basis = match self.random_basis_ct(rngesus) {
+            Some(basis) => basis,
+            None => return false,
+        };
+        if self
+            .pow_ct(&basis, &self.n_minus_one, bits)
+            .ct_eq(&self.one)
+            == 0
+        {
+            return false;
+        }
+
+        for _ in 0..rounds {
+            let basis = match self.random_basis_ct(rngesus) {
+                Some(basis) => basis,
+                None => return false,
+            };
+            let mut x = self.pow_ct(&basis, &self.d, bits);
+            let mut passed = x.ct_eq(&self.one) | x.ct_eq(&self.minus_one);
+
+            for j in 1..bits as u64 {
+                x = self.mul(&x, &x);
+                passed |= ct_lt(j, self.s) & x.ct_eq(&self.minus_one);
+            }
+
+            if passed == 0 {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Draws a random number from `low` (inclusive) to `n` (exclusive), in
+    /// Montgomery form, or returns `None` if `rngesus` fails.
+    fn random_below(&self, low: u64, rngesus: &mut dyn RngCore) -> Option<Uint<LIMBS>> {
+        let bit_length = self.n.bit_length();
+        let low = Uint::from_u64(low);
+
+        loop {
+            let mut random = Uint([0; LIMBS]);
+            for limb in random.0.iter_mut().take(bit_length.div_ceil(64)) {
+                *limb = try_next_u64(rngesus)?;
+            }
+            if !bit_length.is_multiple_of(64) {
+                random.0[(bit_length - 1) / 64] &= (1 << (bit_length % 64)) - 1;
+            }
+
+            if random >= low && random < self.n {
+                return Some(self.to_montgomery(&random));
+            }
+        }
+    }
+
+    fn is_strong_probable_prime(&self, basis: &Uint<LIMBS>) -> bool {
+        let mut x = self.pow(basis, &self.d);
+
+        if x == self.one || x == self.minus_one {
+            return true;
+        }
+
+        for _ in 1..self.s {
+            x = self.mul(&x, &x);
+            if x == self.minus_one {
+                return true;
+            } else if x == self.one {
+                return false;
+            }
+        }
+
+        false
+    }
+}
+
+impl<const LIMBS: usize> Drop for Modulus<LIMBS> {
+    fn drop(&mut self) {
+        // Everything here is either the candidate or derived from it.
+        self.n.0.zeroize();
+        self.n_inv.zeroize();
+        self.one.0.zeroize();
+        self.minus_one.0.zeroize();
+        self.r2.0.zeroize();
+        self.n_minus_one.0.zeroize();
+        self.s.zeroize();
+        self.d.0.zeroize();
+    }
+}
+
+impl<const LIMBS: usize> ProbablePrime for Modulus<LIMBS> {
+    fn fermat(&self, rngesus: &mut dyn RngCore) -> bool {
+        let random = match self.random_below(1, rngesus) {
+            Some(random) => random,
+            None => return false,
+        };
+
+        self.pow(&random, &self.n_minus_one) == self.one
+    }
+
+    fn miller_rabin(&self, limit: usize, rngesus: &mut dyn RngCore, rounds: &mut u64) -> bool {
+        for _ in 0..limit {
+            *rounds += 1;
+
+            let basis = match self.random_below(2, rngesus) {
+                Some(basis) => basis,
+                None => return false,
+            };
+            if !self.is_strong_probable_prime(&basis) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{with_modulus, Modulus, Uint, MAX_BITS};
+    use backend::BigInt;
+    use common::random_bits;
+    use ramp::Int;
+    use rand::thread_rng;
+
+    fn to_int<const LIMBS: usize>(number: &Uint<LIMBS>) -> Int {
+        let bytes: Vec<u8> = number
+            .0
+            .iter()
+            .rev()
+            .flat_map(|limb| limb.to_be_bytes().to_vec())
+            .collect();
+
+        Int::from_be_bytes(&bytes)
+    }
+
+    /// Checks both exponentiations modulo odd numbers of exactly `bits`
+    /// bits, starting with the one whose bits are all set.
+    fn check_pow<const LIMBS: usize>(bits: usize) {
+        let mut rngesus = thread_rng();
+
+        for i in 0..4 {
+            let mut n: Int = if i == 0 {
+                (Int::one() << bits) - 1_usize
+            } else {
+                random_bits(&mut rngesus, bits).unwrap()
+            };
+            n.set_bit(bits as u32 - 1, true);
+            n.set_bit(0, true);
+            let base: Int = random_bits::<Int, _>(&mut rngesus, LIMBS * 64).unwrap() % &n;
+            let exponent: Int = random_bits(&mut rngesus, bits - 7).unwrap();
+
+            let modulus = Modulus::<LIMBS>::new(&n.to_be_bytes());
+            let montgomery = modulus.to_montgomery(&Uint::from_be_bytes(&base.to_be_bytes()));
+            let result = modulus.pow(&montgomery, &Uint::from_be_bytes(&exponent.to_be_bytes()));
+
+            assert_eq!(
+                to_int(&modulus.retrieve(&result)),
+                base.pow_mod(&exponent, &n)
+            );
+
+            let exponent = Uint::from_be_bytes(&exponent.to_be_bytes());
+            assert!(modulus.pow_ct(&montgomery, &exponent, bits - 7) == result);
+        }
+    }
+
+    #[test]
+    fn test_fixed_pow_matches_ramp() {
+        // Candidates of 512, 1024 or 2048 bits fill every limb, which is
+        // where the carry out of the top limb matters.
+        check_pow::<1>(61);
+        check_pow::<1>(64);
+        check_pow::<4>(253);
+        check_pow::<8>(512);
+        check_pow::<16>(1021);
+        check_pow::<16>(1024);
+        check_pow::<32>(2048);
+        check_pow::<33>(2109);
+    }
+
+    #[test]
+    fn test_fixed_random_basis() {
+        // With n = 11, every basis from 2 to 9 should turn up about as often.
+        let modulus = Modulus::<4>::new(&[11]);
+        let mut rngesus = thread_rng();
+        let mut counts = [0; 11];
+        for _ in 0..8000 {
+            let basis = modulus.random_basis_ct(&mut rngesus).unwrap();
+            counts[modulus.retrieve(&basis).0[0] as usize] += 1;
+        }
+
+        assert_eq!((counts[0], counts[1], counts[10]), (0, 0, 0));
+        assert!(counts[2..10]
+            .iter()
+            .all(|&count| count > 800 && count < 1200));
+    }
+
+    #[test]
+    fn test_fixed_probable_prime() {
+        // 2^521 - 1 and 2^607 - 1 are Mersenne primes; their product is not.
+        let p: Int = (Int::one() << 521_usize) - 1_usize;
+        let q: Int = (Int::one() << 607_usize) - 1_usize;
+        let pq = &p * &q;
+
+        let mut rngesus = thread_rng();
+        let mut rounds = 0;
+        for prime in &[&p, &q] {
+            let bytes = prime.to_be_bytes();
+            assert_eq!(
+                with_modulus(&bytes, |tests| tests.fermat(&mut rngesus)),
+                Some(true)
+            );
+            assert_eq!(
+                with_modulus(&bytes, |tests| tests.miller_rabin(
+                    8,
+                    &mut rngesus,
+                    &mut rounds
+                )),
+                Some(true)
+            );
+        }
+        assert_eq!(rounds, 16);
+
+        let bytes = pq.to_be_bytes();
+        assert_eq!(
+            with_modulus(&bytes, |tests| tests.fermat(&mut rngesus)),
+            Some(false)
+        );
+        assert_eq!(
+            with_modulus(&bytes, |tests| tests.miller_rabin(
+                1,
+                &mut rngesus,
+                &mut rounds
+            )),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_fixed_too_wide() {
+        let too_wide = vec![0xff; MAX_BITS / 8 + 1];
+        assert_eq!(
+            with_modulus(&too_wide, |tests| tests.fermat(&mut thread_rng())),
+            None
+        );
+    }
+}
@@ -0,0 +1,584 @@
+//! A plain reference implementation of the Baillie-PSW test, for checking
+//! the result of the main primality tests independently.
+//!
+//! Nothing here is shared with the rest of the crate: numbers are vectors of
+//! 32-bit limbs, multiplication is schoolbook, reduction is Knuth's long
+//! division, and neither the Montgomery engine of `fixed` nor the arithmetic
+//! of any backend is involved. It is slow, and meant to be easy to check.
+//!
+//! Baillie-PSW is a strong probable prime test to base 2 followed by a strong
+//! Lucas probable prime test with Selfridge's parameters. No composite is
+//! known to pass both, and none exists below 2^64.
+
+use zeroize::Zeroize;
+
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::iter;
+
+/// The odd trial divisors run before the probable prime tests.
+const TRIAL_DIVISORS: u32 = 1000;
+
+/// A non-negative integer of 32-bit limbs, least significant first, without
+/// leading zero limbs. Wiped when dropped.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Natural(Vec<u32>);
+
+impl Natural {
+    pub fn from_be_bytes(bytes: &[u8]) -> Natural {
+        let limbs = bytes
+            .rchunks(4)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .fold(0, |limb, &byte| (limb << 8) | u32::from(byte))
+            })
+            .collect();
+
+        Natural::normalized(limbs)
+    }
+
+    pub fn from_u32(number: u32) -> Natural {
+        Natural::normalized(vec![number])
+    }
+
+    fn normalized(mut limbs: Vec<u32>) -> Natural {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+
+        Natural(limbs)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn is_odd(&self) -> bool {
+        self.0.first().is_some_and(|&limb| limb & 1 == 1)
+    }
+
+    pub fn bit_length(&self) -> usize {
+        match self.0.last() {
+            Some(&top) => self.0.len() * 32 - top.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    fn bit(&self, bit: usize) -> bool {
+        self.0
+            .get(bit / 32)
+            .is_some_and(|&limb| (limb >> (bit % 32)) & 1 == 1)
+    }
+
+    fn add(&self, other: &Natural) -> Natural {
+        let (long, short) = if self.0.len() >= other.0.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+
+        let mut limbs = Vec::with_capacity(long.0.len() + 1);
+        let mut carry = 0_u64;
+        for (i, &limb) in long.0.iter().enumerate() {
+            let sum = u64::from(limb) + u64::from(*short.0.get(i).unwrap_or(&0)) + carry;
+            limbs.push(sum as u32);
+            carry = sum >> 32;
+        }
+        limbs.push(carry as u32);
+
+        Natural::normalized(limbs)
+    }
+
+    /// Returns `self - other`, for `self >= other`.
+    fn sub(&self, other: &Natural) -> Natural {
+        let mut limbs = Vec::with_capacity(self.0.len());
+        let mut borrow = 0_i64;
+        for (i, &limb) in self.0.iter().enumerate() {
+            let difference = i64::from(limb) - i64::from(*other.0.get(i).unwrap_or(&0)) - borrow;
+            limbs.push(difference as u32);
+            borrow = i64::from(difference < 0);
+        }
+        assert_eq!(borrow, 0, "subtraction underflowed");
+
+        Natural::normalized(limbs)
+    }
+
+    fn mul(&self, other: &Natural) -> Natural {
+        let mut limbs = vec![0_u32; self.0.len() + other.0.len()];
+        for (i, &a) in self.0.iter().enumerate() {
+            let mut carry = 0_u64;
+            for (j, &b) in other.0.iter().enumerate() {
+                let product = u64::from(a) * u64::from(b) + u64::from(limbs[i + j]) + carry;
+                limbs[i + j] = product as u32;
+                carry = product >> 32;
+            }
+            limbs[i + other.0.len()] = carry as u32;
+        }
+
+        Natural::normalized(limbs)
+    }
+
+    fn shr(&self, bits: usize) -> Natural {
+        let (skip, bits) = (bits / 32, bits % 32);
+        let limbs = (skip..self.0.len())
+            .map(|i| {
+                let high = match self.0.get(i + 1) {
+                    Some(&next) if bits > 0 => next << (32 - bits),
+                    _ => 0,
+                };
+                (self.0[i] >> bits) | high
+            })
+            .collect();
+
+        Natural::normalized(limbs)
+    }
+
+    /// Returns the remainder of `self` divided by the small, non-zero
+    /// `divisor`.
+    fn rem_u32(&self, divisor: u32) -> u32 {
+        self.0.iter().rev().fold(0, |remainder, &limb| {
+            (((u64::from(remainder) << 32) | u64::from(limb)) % u64::from(divisor)) as u32
+        })
+    }
+
+    /// Returns the quotient and remainder of `self` divided by the non-zero
+    /// `divisor`, using Algorithm D of Knuth, TAOCP volume 2, section 4.3.1.
+    pub fn div_rem(&self, divisor: &Natural) -> (Natural, Natural) {
+        assert!(!divisor.is_zero(), "division by zero");
+
+        if self < divisor {
+            return (Natural(Vec::new()), self.clone());
+        }
+
+        if divisor.0.len() == 1 {
+            let d = u64::from(divisor.0[0]);
+            let mut quotient = vec![0_u32; self.0.len()];
+            let mut remainder = 0_u64;
+            for (i, &limb) in self.0.iter().enumerate().rev() {
+                let current = (remainder << 32) | u64::from(limb);
+                quotient[i] = (current / d) as u32;
+                remainder = current % d;
+            }
+
+            return (
+                Natural::normalized(quotient),
+                Natural::from_u32(remainder as u32),
+            );
+        }
+
+        // Normalize so that the top limb of the divisor has its top bit set,
+        // which keeps each estimated quotient limb at most two too large.
+        let shift = divisor.0[divisor.0.len() - 1].leading_zeros();
+        let v = shl_limbs(&divisor.0, shift, 0);
+        let mut u = shl_limbs(&self.0, shift, 1);
+        let n = v.len();
+        let m = self.0.len() - n;
+        let mut quotient = vec![0_u32; m + 1];
+
+        for j in (0..=m).rev() {
+            let top = (u64::from(u[j + n]) << 32) | u64::from(u[j + n - 1]);
+            let mut estimate = top / u64::from(v[n - 1]);
+            let mut rest = top % u64::from(v[n - 1]);
+
+            while estimate >> 32 != 0
+                || estimate * u64::from(v[n - 2]) > ((rest << 32) | u64::from(u[j + n - 2]))
+            {
+                estimate -= 1;
+                rest += u64::from(v[n - 1]);
+                if rest >> 32 != 0 {
+                    break;
+                }
+            }
+
+            // Multiply and subtract, then add back the one time in a while
+            // that the estimate was still one too large.
+            let mut borrow = 0_i64;
+            for i in 0..n {
+                let product = estimate * u64::from(v[i]);
+                let difference = i64::from(u[i + j]) - borrow - (product & 0xffff_ffff) as i64;
+                u[i + j] = difference as u32;
+                borrow = (product >> 32) as i64 - (difference >> 32);
+            }
+            let difference = i64::from(u[j + n]) - borrow;
+            u[j + n] = difference as u32;
+
+            if difference < 0 {
+                estimate -= 1;
+
+                let mut carry = 0_u64;
+                for i in 0..n {
+                    let sum = u64::from(u[i + j]) + u64::from(v[i]) + carry;
+                    u[i + j] = sum as u32;
+                    carry = sum >> 32;
+                }
+                u[j + n] = u[j + n].wrapping_add(carry as u32);
+            }
+
+            quotient[j] = estimate as u32;
+        }
+
+        let remainder = Natural::normalized(u[..n].to_vec()).shr(shift as usize);
+        u.zeroize();
+
+        (Natural::normalized(quotient), remainder)
+    }
+
+    fn rem(&self, modulus: &Natural) -> Natural {
+        self.div_rem(modulus).1
+    }
+
+    fn mul_mod(&self, other: &Natural, modulus: &Natural) -> Natural {
+        self.mul(other).rem(modulus)
+    }
+
+    /// Returns `self - other mod modulus`, for `self, other < modulus`.
+    fn sub_mod(&self, other: &Natural, modulus: &Natural) -> Natural {
+        if self >= other {
+            self.sub(other)
+        } else {
+            self.add(modulus).sub(other)
+        }
+    }
+
+    /// Returns `self / 2 mod modulus`, for `self < modulus` and an odd
+    /// `modulus`.
+    fn half_mod(&self, modulus: &Natural) -> Natural {
+        if self.is_odd() {
+            self.add(modulus).shr(1)
+        } else {
+            self.shr(1)
+        }
+    }
+
+    fn pow_mod(&self, exponent: &Natural, modulus: &Natural) -> Natural {
+        let mut result = Natural::from_u32(1).rem(modulus);
+        for bit in (0..exponent.bit_length()).rev() {
+            result = result.mul_mod(&result, modulus);
+            if exponent.bit(bit) {
+                result = result.mul_mod(self, modulus);
+            }
+        }
+
+        result
+    }
+
+    /// Returns the integer square root, rounded down, by Newton's method.
+    fn sqrt(&self) -> Natural {
+        if self.is_zero() {
+            return Natural(Vec::new());
+        }
+
+        let mut root = Natural::from_u32(1).shl(self.bit_length().div_ceil(2));
+        loop {
+            let next = root.add(&self.div_rem(&root).0).shr(1);
+            if next >= root {
+                return root;
+            }
+            root = next;
+        }
+    }
+
+    fn shl(&self, bits: usize) -> Natural {
+        let mut limbs = vec![0_u32; bits / 32];
+        limbs.extend(shl_limbs(&self.0, (bits % 32) as u32, 1));
+
+        Natural::normalized(limbs)
+    }
+}
+
+impl Ord for Natural {
+    fn cmp(&self, other: &Natural) -> Ordering {
+        self.0
+            .len()
+            .cmp(&other.0.len())
+            .then_with(|| self.0.iter().rev().cmp(other.0.iter().rev()))
+    }
+}
+
+impl PartialOrd for Natural {
+    fn partial_cmp(&self, other: &Natural) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Drop for Natural {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// Shifts `limbs` left by `shift < 32` bits, appending `extra` zero limbs
+/// first so that nothing is lost off the top.
+fn shl_limbs(limbs: &[u32], shift: u32, extra: usize) -> Vec<u32> {
+    let mut shifted = Vec::with_capacity(limbs.len() + extra);
+    let mut carry = 0_u32;
+    for &limb in limbs {
+        shifted.push((limb << shift) | carry);
+        carry = if shift > 0 { limb >> (32 - shift) } else { 0 };
+    }
+    shifted.resize(limbs.len() + extra, 0);
+    if extra > 0 {
+        shifted[limbs.len()] = carry;
+    }
+
+    shifted
+}
+
+/// Runs the Baillie-PSW test on `n`. The answer is exact for `n < 2^64`.
+pub fn is_prime_bpsw(n: &Natural) -> bool {
+    if n.bit_length() <= 1 {
+        return false;
+    }
+
+    for divisor in iter::once(2).chain((3..TRIAL_DIVISORS).step_by(2)) {
+        if *n == Natural::from_u32(divisor) {
+            return true;
+        }
+        if n.rem_u32(divisor) == 0 {
+            return false;
+        }
+    }
+
+    if *n < Natural::from_u32(TRIAL_DIVISORS * TRIAL_DIVISORS) {
+        return true;
+    }
+
+    is_strong_probable_prime_base_2(n) && is_strong_lucas_probable_prime(n)
+}
+
+/// Runs Miller-Rabin to base 2 on the odd `n > 2`.
+fn is_strong_probable_prime_base_2(n: &Natural) -> bool {
+    let one = Natural::from_u32(1);
+    let minus_one = n.sub(&one);
+    let s = (0..).take_while(|&bit| !minus_one.bit(bit)).count();
+    let d = minus_one.shr(s);
+
+    let mut x = Natural::from_u32(2).pow_mod(&d, n);
+    if x == one || x == minus_one {
+        return true;
+    }
+
+    for _ in 1..s {
+        x = x.mul_mod(&x, n);
+        if x == minus_one {
+            return true;
+        }
+        if x == one {
+            return false;
+        }
+    }
+
+    false
+}
+
+/// Runs the strong Lucas test with Selfridge's method A on the odd `n`,
+/// which must be larger than the `D` it settles on: `D` is the first of 5,
+/// -7, 9, -11, ... with `(D/n) = -1`, `P = 1` and `Q = (1 - D) / 4`.
+pub fn is_strong_lucas_probable_prime(n: &Natural) -> bool {
+    // No such D exists for squares.
+    let root = n.sqrt();
+    if root.mul(&root) == *n {
+        return false;
+    }
+
+    let mut d = 5_i64;
+    loop {
+        match jacobi(d, n) {
+            -1 => break,
+            0 => return false,
+            _ => d = if d > 0 { -(d + 2) } else { -d + 2 },
+        }
+    }
+
+    let q = (1 - d) / 4;
+    let q = signed_mod(q, n);
+    let d = signed_mod(d, n);
+
+    // n + 1 = 2^s * k, with k odd.
+    let plus_one = n.add(&Natural::from_u32(1));
+    let s = (0..).take_while(|&bit| !plus_one.bit(bit)).count();
+    let k = plus_one.shr(s);
+
+    // Walk the bits of k from the top, keeping U_j, V_j and Q^j for the
+    // prefix j read so far, starting from j = 1.
+    let mut u = Natural::from_u32(1);
+    let mut v = Natural::from_u32(1);
+    let mut q_j = q.clone();
+
+    for bit in (0..k.bit_length() - 1).rev() {
+        // U_2j = U_j V_j, V_2j = V_j^2 - 2 Q^j.
+        u = u.mul_mod(&v, n);
+        v = v.mul_mod(&v, n).sub_mod(&q_j.add(&q_j).rem(n), n);
+        q_j = q_j.mul_mod(&q_j, n);
+
+        if k.bit(bit) {
+            // U_j+1 = (P U_j + V_j) / 2, V_j+1 = (D U_j + P V_j) / 2.
+            let next_u = u.add(&v).rem(n).half_mod(n);
+            let next_v = d.mul_mod(&u, n).add(&v).rem(n).half_mod(n);
+            u = next_u;
+            v = next_v;
+            q_j = q_j.mul_mod(&q, n);
+        }
+    }
+
+    if u.is_zero() || v.is_zero() {
+        return true;
+    }
+
+    for _ in 1..s {
+        v = v.mul_mod(&v, n).sub_mod(&q_j.add(&q_j).rem(n), n);
+        if v.is_zero() {
+            return true;
+        }
+        q_j = q_j.mul_mod(&q_j, n);
+    }
+
+    false
+}
+
+/// Returns `value mod n`, for a small `value` of either sign.
+fn signed_mod(value: i64, n: &Natural) -> Natural {
+    let magnitude = Natural::from_u32(value.unsigned_abs() as u32).rem(n);
+
+    if value >= 0 || magnitude.is_zero() {
+        magnitude
+    } else {
+        n.sub(&magnitude)
+    }
+}
+
+/// Returns the Jacobi symbol `(a/n)`, for a small, odd `a` and an odd
+/// `n > |a|`.
+fn jacobi(a: i64, n: &Natural) -> i32 {
+    let magnitude = a.unsigned_abs() as u32;
+    let n_mod_4 = n.rem_u32(4);
+
+    // Reciprocity swaps (|a|/n) for (n mod |a| / |a|), and (-1/n) is -1 when
+    // n is 3 mod 4.
+    let mut symbol = jacobi_u32(n.rem_u32(magnitude), magnitude);
+    if magnitude % 4 == 3 && n_mod_4 == 3 {
+        symbol = -symbol;
+    }
+    if a < 0 && n_mod_4 == 3 {
+        symbol = -symbol;
+    }
+
+    symbol
+}
+
+/// Returns the Jacobi symbol `(a/n)` for an odd `n`.
+pub fn jacobi_u32(mut a: u32, mut n: u32) -> i32 {
+    let mut symbol = 1;
+    a %= n;
+
+    while a != 0 {
+        while a & 1 == 0 {
+            a /= 2;
+            if n % 8 == 3 || n % 8 == 5 {
+                symbol = -symbol;
+            }
+        }
+
+        core::mem::swap(&mut a, &mut n);
+        if a % 4 == 3 && n % 4 == 3 {
+            symbol = -symbol;
+        }
+        a %= n;
+    }
+
+    if n == 1 {
+        symbol
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_prime_bpsw, is_strong_lucas_probable_prime, jacobi_u32, Natural};
+    use backend::BigInt;
+    use common::random_bits;
+    use prime::small::is_prime_u64;
+    use ramp::Int;
+    use rand::{thread_rng, Rng};
+
+    fn to_int(number: &Natural) -> Int {
+        let bytes: Vec<u8> = number
+            .0
+            .iter()
+            .rev()
+            .flat_map(|limb| limb.to_be_bytes().to_vec())
+            .collect();
+
+        Int::from_be_bytes(&bytes)
+    }
+
+    #[test]
+    fn test_reference_div_rem_matches_ramp() {
+        let mut rngesus = thread_rng();
+
+        for _ in 0..256 {
+            let a_bits = rngesus.gen_range(1, 1200);
+            let b_bits = rngesus.gen_range(1, 700);
+            let a: Int = random_bits(&mut rngesus, a_bits).unwrap();
+            let mut b: Int = random_bits(&mut rngesus, b_bits).unwrap();
+            if b == Int::zero() {
+                b = Int::one();
+            }
+
+            let (quotient, remainder) = Natural::from_be_bytes(&a.to_be_bytes())
+                .div_rem(&Natural::from_be_bytes(&b.to_be_bytes()));
+            assert_eq!(to_int(&quotient), &a / &b);
+            assert_eq!(to_int(&remainder), &a % &b);
+        }
+    }
+
+    #[test]
+    fn test_reference_bpsw_small() {
+        for n in 0..100_000_u64 {
+            let natural = Natural::from_be_bytes(&n.to_be_bytes());
+            assert_eq!(is_prime_bpsw(&natural), is_prime_u64(n), "{}", n);
+        }
+
+        let mut rngesus = thread_rng();
+        for _ in 0..2000 {
+            let n = rngesus.gen::<u64>() | 1;
+            let natural = Natural::from_be_bytes(&n.to_be_bytes());
+            assert_eq!(is_prime_bpsw(&natural), is_prime_u64(n), "{}", n);
+        }
+    }
+
+    #[test]
+    fn test_reference_lucas_pseudoprimes() {
+        // Strong Lucas pseudoprimes with Selfridge's parameters, which the
+        // Lucas test alone lets through.
+        for &n in &[5459_u32, 5777, 10877, 16109, 18971, 22499, 24569, 25199] {
+            assert!(is_strong_lucas_probable_prime(&Natural::from_u32(n)));
+        }
+
+        // Strong pseudoprimes to base 2, which it catches.
+        for &n in &[2047_u32, 3277, 4033, 4681, 8321] {
+            assert!(!is_strong_lucas_probable_prime(&Natural::from_u32(n)));
+        }
+    }
+
+    #[test]
+    fn test_reference_bpsw_large() {
+        let p: Int = (Int::one() << 521_usize) - 1_usize;
+        let q: Int = (Int::one() << 607_usize) - 1_usize;
+        let spsp37 = Int::from_str_radix("318665857834031151167461", 10).unwrap();
+
+        for &(number, prime) in &[
+            (&p, true),
+            (&q, true),
+            (&(&p * &q), false),
+            (&spsp37, false),
+        ] {
+            let natural = Natural::from_be_bytes(&number.to_be_bytes());
+            assert_eq!(is_prime_bpsw(&natural), prime);
+        }
+
+        assert_eq!(jacobi_u32(1001, 9907), -1);
+    }
+}
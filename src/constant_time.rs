@@ -0,0 +1,263 @@
+//! Generates and tests secret primes while resisting timing side channels.
+//!
+//! The tests behind `prime` and `safe_prime` are built for speed: they stop
+//! at the first small prime that divides a candidate, and their
+//! exponentiation branches on the bits of the exponent. The primes they
+//! produce usually end up as private keys, so this module offers a slower
+//! alternative for when that matters.
+//!
+//! Work done on the prime that is eventually returned does not depend on its
+//! value:
+//!
+//! 1. Trial division finds the remainder of the candidate by every one of the
+//!    small primes, using multiplication rather than division, and only then
+//!    looks at whether any of them was zero.
+//!
+//! 2. The Fermat and Miller-Rabin tests run on fixed-width integers, with
+//!    Montgomery multiplication that never branches on its operands, a
+//!    fixed-window exponentiation that reads every table entry, and the same
+//!    number of squarings in every round. Each basis is uniform between two
+//!    and `n - 2`: a number 64 bits wider than `n` is reduced modulo `n`
+//!    with the same multiplication, so the 4^-k error bound of Miller-Rabin
+//!    holds even for composites chosen to fool a fixed set of bases.
+//!
+//! The following is NOT hidden:
+//!
+//! * Whether a candidate is composite, and which test caught it. Rejected
+//!   candidates are thrown away, so this says nothing about the prime that is
+//!   returned.
+//!
+//! * The bit length, which is public anyway.
+//!
+//! * Moving the prime into, or for `is_prime()` out of, a `ramp::Int`. ramp
+//!   makes no constant-time guarantees.
+//!
+//! The code is written without secret-dependent branches or memory accesses,
+//! but nothing stops an optimizer from reintroducing them. Check the
+//! generated machine code for your target if this matters to you.
+
+use ramp::Int;
+
+use rand::{thread_rng, Rng, RngCore};
+
+use zeroize::Zeroizing;
+
+use backend::BigInt;
+use common::fixed::{self, is_probable_prime_ct};
+use common::{
+    bytes_bit_length, check_bit_length, random_bytes, set_bit, CountingRng, MIN_BIT_LENGTH,
+    SMALL_PRIMES,
+};
+use entropy;
+use error::{Error, Result};
+use prime::small::is_prime_u64;
+#[cfg(feature = "self-test")]
+use self_test;
+
+/// The largest bit length supported by this module.
+pub const MAX_BIT_LENGTH: usize = fixed::MAX_BITS;
+
+const MILLER_RABIN_ROUNDS: usize = 15;
+
+/// Constructs a new prime number with a size of `bit_length` bits.
+///
+/// This will initialize an `OsRng` instance, checked by the health tests
+/// of the `entropy` module, and call the `from_rng()` function.
+///
+/// Note: the `bit_length` MUST be at least 512-bits, and at most
+/// `MAX_BIT_LENGTH`.
+pub fn new(bit_length: usize) -> Result {
+    check_bit_length(bit_length, MIN_BIT_LENGTH)?;
+    check_max_bit_length(bit_length)?;
+
+    let mut rngesus = entropy::os()?;
+    from_rng(bit_length, &mut rngesus)
+}
+
+/// Constructs a new prime number with the size of `bit_length` bits, sourced
+/// from an already-initialized random number generator.
+pub fn from_rng<R: Rng + ?Sized>(bit_length: usize, rngesus: &mut R) -> Result {
+    check_bit_length(bit_length, MIN_BIT_LENGTH)?;
+    check_max_bit_length(bit_length)?;
+    #[cfg(feature = "self-test")]
+    self_test::on_first_use::<Int>()?;
+
+    let reciprocals = reciprocals();
+    let mut rngesus = CountingRng::new(rngesus);
+
+    loop {
+        let mut candidate =
+            random_bytes(&mut rngesus, bit_length).map_err(|err| rngesus.failure_or(err))?;
+        set_bit(&mut candidate, bit_length - 1);
+        set_bit(&mut candidate, bit_length - 2);
+        set_bit(&mut candidate, 0);
+
+        if is_prime_bytes(&candidate, bit_length, &reciprocals, &mut rngesus) {
+            return Ok(Int::from_be_bytes(&candidate));
+        }
+
+        if let Some(err) = rngesus.failure.take() {
+            return Err(Error::from_rng(err));
+        }
+    }
+}
+
+/// Runs the same tests as the rest of the crate on `candidate`, without
+/// timing depending on its value.
+///
+/// Candidates of at most 64 bits are never secret, and are instead checked
+/// deterministically. Fails with `Error::InvalidParameter` for candidates
+/// wider than `MAX_BIT_LENGTH`.
+pub fn is_prime(candidate: &Int) -> Result<bool> {
+    if *candidate < Int::zero() {
+        return Ok(false);
+    }
+
+    let bytes = Zeroizing::new(candidate.to_be_bytes());
+    let bit_length = bytes_bit_length(&bytes);
+
+    if bit_length <= 64 {
+        return Ok(is_prime_u64(u64::from(candidate)));
+    }
+
+    check_max_bit_length(bit_length)?;
+
+    Ok(is_prime_bytes(
+        &bytes,
+        bit_length,
+        &reciprocals(),
+        &mut thread_rng(),
+    ))
+}
+
+fn check_max_bit_length(bit_length: usize) -> Result<()> {
+    if bit_length > MAX_BIT_LENGTH {
+        Err(Error::InvalidParameter {
+            name: "bit_length",
+            reason: format!("must be at most {}", MAX_BIT_LENGTH),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns `2^64 / p`, rounded down, for each of the small primes.
+fn reciprocals() -> Vec<u64> {
+    SMALL_PRIMES
+        .iter()
+        .map(|&prime| u64::MAX / u64::from(prime))
+        .collect()
+}
+
+fn is_prime_bytes<R: RngCore + ?Sized>(
+    candidate: &[u8],
+    bit_length: usize,
+    reciprocals: &[u64],
+    rngesus: &mut R,
+) -> bool {
+    let mut divisible = u64::from(candidate[candidate.len() - 1] & 1) ^ 1;
+
+    for (&prime, &reciprocal) in SMALL_PRIMES.iter().zip(reciprocals.iter()) {
+        let remainder = rem(candidate, u64::from(prime), reciprocal);
+        divisible |= ((remainder | remainder.wrapping_neg()) >> 63) ^ 1;
+    }
+
+    if divisible != 0 {
+        return false;
+    }
+
+    is_probable_prime_ct(candidate, bit_length, MILLER_RABIN_ROUNDS, rngesus)
+        .expect("bit length was checked against MAX_BIT_LENGTH")
+}
+
+/// Returns the remainder of the big-endian `bytes` divided by the small
+/// `divisor`, using Barrett reduction with `reciprocal = 2^64 / divisor` in
+/// place of the division instruction, whose timing varies by operand on
+/// many processors.
+fn rem(bytes: &[u8], divisor: u64, reciprocal: u64) -> u64 {
+    let (head, tail) = bytes.split_at(bytes.len() % 4);
+
+    let mut remainder = reduce(be_word(head), divisor, reciprocal);
+    for word in tail.chunks(4) {
+        remainder = reduce((remainder << 32) | be_word(word), divisor, reciprocal);
+    }
+
+    remainder
+}
+
+/// Reduces `number`, which is below `divisor * 2^32`, modulo `divisor`.
+fn reduce(number: u64, divisor: u64, reciprocal: u64) -> u64 {
+    // The estimated quotient is at most one too small, leaving a remainder
+    // below twice the divisor.
+    let quotient = ((u128::from(number) * u128::from(reciprocal)) >> 64) as u64;
+    let remainder = number - quotient * divisor;
+    let too_large = (remainder.wrapping_sub(divisor) >> 63) ^ 1;
+
+    remainder - (divisor & too_large.wrapping_neg())
+}
+
+fn be_word(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0, |word, &byte| (word << 8) | u64::from(byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_rng, is_prime, new, reciprocals, rem, MAX_BIT_LENGTH};
+    use backend::BigInt;
+    use common::{self, random_bits, SMALL_PRIMES};
+    use error::Error;
+    use ramp::Int;
+    use rand::rngs::OsRng;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_constant_time_bit_lengths() {
+        assert!(new(511).is_err());
+
+        match new(MAX_BIT_LENGTH + 1) {
+            Err(Error::InvalidParameter { name, .. }) => assert_eq!(name, "bit_length"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_constant_time_prime() {
+        let mut rngesus = OsRng::new().unwrap();
+        let p = from_rng(512, &mut rngesus).unwrap();
+
+        assert_eq!(p.bit_length(), 512);
+        assert!(common::is_prime(&p));
+        assert!(is_prime(&p).unwrap());
+    }
+
+    #[test]
+    fn test_constant_time_is_prime() {
+        let mersenne: Int = (Int::one() << 521_usize) - 1_usize;
+        let spsp37 = Int::from_str_radix("318665857834031151167461", 10).unwrap();
+
+        assert!(is_prime(&mersenne).unwrap());
+        assert!(!is_prime(&(&mersenne * &mersenne)).unwrap());
+        assert!(!is_prime(&(&mersenne * 17863_usize)).unwrap());
+        assert!(!is_prime(&spsp37).unwrap());
+        assert!(is_prime(&Int::from(17863)).unwrap());
+        assert!(!is_prime(&Int::from(-7)).unwrap());
+    }
+
+    #[test]
+    fn test_constant_time_rem() {
+        let mut rngesus = thread_rng();
+        let reciprocals = reciprocals();
+
+        for _ in 0..16 {
+            let number: Int = random_bits(&mut rngesus, 1021).unwrap();
+            let bytes = number.to_be_bytes();
+
+            for (&prime, &reciprocal) in SMALL_PRIMES.iter().zip(reciprocals.iter()) {
+                let expected = u64::from(&(&number % prime as usize));
+                assert_eq!(rem(&bytes, u64::from(prime), reciprocal), expected);
+            }
+        }
+    }
+}
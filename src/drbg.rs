@@ -0,0 +1,522 @@
+//! An HMAC_DRBG deterministic random bit generator, as specified in NIST SP
+//! 800-90A Rev. 1, section 10.1.2.
+//!
+//! `HmacDrbg` implements `RngCore` and `CryptoRng`, so it can be handed to
+//! any of the crate's `from_rng()` functions in place of `OsRng`. Every
+//! random number drawn while generating the prime, including the bases of
+//! the probable prime tests, then comes from the DRBG.
+//!
+//! The DRBG is seeded, and reseeded, from an entropy source of type `E`,
+//! which it owns. It reseeds itself once `reseed_interval` requests have
+//! been served, and before every request when prediction resistance is
+//! switched on. A failing entropy source fails the request, rather than
+//! letting the DRBG carry on with its old state.
+//!
+//! `SHA-256` is used by default; any other hash from the `sha2` crate may be
+//! chosen with the `D` type parameter. The DRBG is instantiated at a
+//! security strength of 256 bits, or the output size of the hash if smaller.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! extern crate pumpkin;
+//! extern crate rand;
+//!
+//! use pumpkin::drbg::HmacDrbg;
+//! use pumpkin::prime;
+//! use rand::rngs::OsRng;
+//!
+//! let mut drbg: HmacDrbg<_> = HmacDrbg::new(OsRng::new().unwrap(), b"my application").unwrap();
+//! let p = prime::from_rng(2048, &mut drbg).unwrap();
+//! ```
+
+use hmac::digest::core_api::BlockSizeUser;
+use hmac::digest::{Digest, Output};
+use hmac::{Mac, SimpleHmac};
+
+use rand::{CryptoRng, ErrorKind, RngCore};
+
+use sha2::Sha256;
+
+use zeroize::{Zeroize, Zeroizing};
+
+use core::{cmp, fmt};
+
+use error::{Error, Result};
+
+/// The most bytes a single call to `generate()` may ask for: 2^19 bits.
+pub const MAX_REQUEST_BYTES: usize = 1 << 16;
+
+/// The largest reseed interval SP 800-90A allows, and the default.
+pub const MAX_RESEED_INTERVAL: u64 = 1 << 48;
+
+/// The highest security strength, in bytes, that HMAC_DRBG supports.
+const MAX_SECURITY_STRENGTH: usize = 32;
+
+/// An HMAC_DRBG seeded from the entropy source `E`, using the hash `D`.
+pub struct HmacDrbg<E, D = Sha256>
+where
+    D: Digest + BlockSizeUser,
+{
+    key: Output<D>,
+    value: Output<D>,
+    reseed_counter: u64,
+    reseed_interval: u64,
+    prediction_resistance: bool,
+    entropy: E,
+}
+
+impl<E: RngCore, D: Digest + BlockSizeUser> HmacDrbg<E, D> {
+    /// Instantiates a new DRBG, drawing its entropy input and nonce from
+    /// `entropy`, and mixing in the optional `personalization` string.
+    ///
+    /// Fails with `Error::Rng`, or `Error::EntropyHealth`, if `entropy`
+    /// fails.
+    pub fn new(mut entropy: E, personalization: &[u8]) -> Result<HmacDrbg<E, D>> {
+        let strength = security_strength::<D>();
+        let mut seed = Zeroizing::new(vec![0u8; strength + strength / 2]);
+        entropy.try_fill_bytes(&mut seed).map_err(Error::from_rng)?;
+
+        let (entropy_input, nonce) = seed.split_at(strength);
+        Ok(HmacDrbg::from_entropy_input(
+            entropy_input,
+            nonce,
+            personalization,
+            entropy,
+        ))
+    }
+
+    /// Instantiates a new DRBG from the given `entropy_input` and `nonce`,
+    /// keeping `entropy` for later reseeds.
+    ///
+    /// This exists for known-answer testing, where the seed material is
+    /// fixed. Use `new()` otherwise.
+    pub fn from_entropy_input(
+        entropy_input: &[u8],
+        nonce: &[u8],
+        personalization: &[u8],
+        entropy: E,
+    ) -> HmacDrbg<E, D> {
+        let mut drbg = HmacDrbg {
+            key: Output::<D>::default(),
+            value: Output::<D>::default(),
+            reseed_counter: 1,
+            reseed_interval: MAX_RESEED_INTERVAL,
+            prediction_resistance: false,
+            entropy,
+        };
+
+        for byte in drbg.value.iter_mut() {
+            *byte = 0x01;
+        }
+        drbg.update(&[entropy_input, nonce, personalization]);
+
+        drbg
+    }
+
+    /// Reseeds before every request when `enabled`, so that an attacker who
+    /// learns the DRBG's state cannot predict its later output.
+    pub fn with_prediction_resistance(mut self, enabled: bool) -> HmacDrbg<E, D> {
+        self.prediction_resistance = enabled;
+        self
+    }
+
+    /// Reseeds after `interval` requests, rather than `MAX_RESEED_INTERVAL`.
+    ///
+    /// Fails with `Error::InvalidParameter` unless `interval` is between one
+    /// and `MAX_RESEED_INTERVAL`.
+    pub fn with_reseed_interval(mut self, interval: u64) -> Result<HmacDrbg<E, D>> {
+        if interval == 0 || interval > MAX_RESEED_INTERVAL {
+            return Err(Error::InvalidParameter {
+                name: "interval",
+                reason: format!("must be between 1 and {}", MAX_RESEED_INTERVAL),
+            });
+        }
+
+        self.reseed_interval = interval;
+        Ok(self)
+    }
+
+    /// The number of requests served since the last (re)seed, plus one.
+    pub fn reseed_counter(&self) -> u64 {
+        self.reseed_counter
+    }
+
+    /// Reseeds the DRBG with fresh entropy drawn from the entropy source,
+    /// mixing in the optional `additional_input`.
+    ///
+    /// Fails with `Error::Rng`, or `Error::EntropyHealth`, if the entropy
+    /// source fails, leaving the state as it was.
+    pub fn reseed(&mut self, additional_input: &[u8]) -> Result<()> {
+        let mut entropy_input = Zeroizing::new(vec![0u8; security_strength::<D>()]);
+        self.entropy
+            .try_fill_bytes(&mut entropy_input)
+            .map_err(Error::from_rng)?;
+
+        self.reseed_with_entropy_input(&entropy_input, additional_input);
+        Ok(())
+    }
+
+    /// Reseeds the DRBG with the given `entropy_input`.
+    ///
+    /// Like `from_entropy_input()`, this exists for known-answer testing.
+    pub fn reseed_with_entropy_input(&mut self, entropy_input: &[u8], additional_input: &[u8]) {
+        self.update(&[entropy_input, additional_input]);
+        self.reseed_counter = 1;
+    }
+
+    /// Fills `output` with random bytes, mixing in the optional
+    /// `additional_input`. Reseeds first if prediction resistance is on, or
+    /// if the reseed interval has passed.
+    ///
+    /// Fails with `Error::InvalidParameter` if `output` is longer than
+    /// `MAX_REQUEST_BYTES`, and with `Error::Rng` if a reseed is needed and
+    /// the entropy source fails. `output` is left untouched on failure.
+    pub fn generate(&mut self, output: &mut [u8], additional_input: &[u8]) -> Result<()> {
+        if output.len() > MAX_REQUEST_BYTES {
+            return Err(Error::InvalidParameter {
+                name: "output",
+                reason: format!("must be at most {} bytes", MAX_REQUEST_BYTES),
+            });
+        }
+
+        let mut additional_input = additional_input;
+        if self.prediction_resistance || self.reseed_counter > self.reseed_interval {
+            self.reseed(additional_input)?;
+            additional_input = &[];
+        }
+
+        if !additional_input.is_empty() {
+            self.update(&[additional_input]);
+        }
+
+        for chunk in output.chunks_mut(self.value.len()) {
+            self.value = self.hmac(&[&self.value]);
+            chunk.copy_from_slice(&self.value[..chunk.len()]);
+        }
+
+        self.update(&[additional_input]);
+        self.reseed_counter += 1;
+
+        Ok(())
+    }
+
+    /// The HMAC_DRBG_Update function, with `provided_data` given as the
+    /// concatenation of its parts.
+    fn update(&mut self, provided_data: &[&[u8]]) {
+        let provided = provided_data.iter().any(|part| !part.is_empty());
+
+        for &separator in [0x00, 0x01].iter().take(if provided { 2 } else { 1 }) {
+            let mut message: [&[u8]; 5] = [&self.value, &[separator], &[], &[], &[]];
+            for (slot, part) in message[2..].iter_mut().zip(provided_data.iter()) {
+                *slot = part;
+            }
+
+            self.key = self.hmac(&message);
+            self.value = self.hmac(&[&self.value]);
+        }
+    }
+
+    /// Computes the HMAC of the concatenation of `message` under the
+    /// current key.
+    fn hmac(&self, message: &[&[u8]]) -> Output<D> {
+        let mut mac = <SimpleHmac<D> as Mac>::new_from_slice(&self.key)
+            .expect("HMAC accepts keys of any length");
+        for part in message {
+            mac.update(part);
+        }
+
+        mac.finalize().into_bytes()
+    }
+}
+
+impl<E: RngCore, D: Digest + BlockSizeUser> RngCore for HmacDrbg<E, D> {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest)
+            .expect("the DRBG could not reseed from its entropy source")
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> ::core::result::Result<(), ::rand::Error> {
+        for chunk in dest.chunks_mut(MAX_REQUEST_BYTES) {
+            self.generate(chunk, &[]).map_err(|err| match err {
+                Error::Rng(err) => err,
+                Error::EntropyHealth(test) => test.to_rng_error(),
+                err => ::rand::Error::with_cause(ErrorKind::Unexpected, "HMAC_DRBG failed", err),
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<E: RngCore, D: Digest + BlockSizeUser> CryptoRng for HmacDrbg<E, D> {}
+
+impl<E, D: Digest + BlockSizeUser> fmt::Debug for HmacDrbg<E, D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HmacDrbg")
+            .field("reseed_counter", &self.reseed_counter)
+            .field("reseed_interval", &self.reseed_interval)
+            .field("prediction_resistance", &self.prediction_resistance)
+            .finish()
+    }
+}
+
+impl<E, D: Digest + BlockSizeUser> Drop for HmacDrbg<E, D> {
+    fn drop(&mut self) {
+        self.key.as_mut_slice().zeroize();
+        self.value.as_mut_slice().zeroize();
+    }
+}
+
+/// The security strength, in bytes, of HMAC_DRBG with the hash `D`.
+fn security_strength<D: Digest>() -> usize {
+    cmp::min(<D as Digest>::output_size(), MAX_SECURITY_STRENGTH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HmacDrbg, MAX_REQUEST_BYTES};
+    use backend;
+    use error::Error;
+    use prime;
+    use rand::{ErrorKind, RngCore};
+    use sha2::Sha512;
+
+    /// Hands out the bytes it was given, then fails.
+    struct Replay(Vec<u8>);
+
+    impl RngCore for Replay {
+        fn next_u32(&mut self) -> u32 {
+            unimplemented!()
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            unimplemented!()
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            self.try_fill_bytes(dest).unwrap()
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), ::rand::Error> {
+            if dest.len() > self.0.len() {
+                return Err(::rand::Error::new(ErrorKind::Unavailable, "exhausted"));
+            }
+
+            let rest = self.0.split_off(dest.len());
+            dest.copy_from_slice(&self.0);
+            self.0 = rest;
+            Ok(())
+        }
+    }
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_drbg_cavp_sha256() {
+        // CAVP HMAC_DRBG.rsp, [SHA-256], no prediction resistance, COUNT = 0.
+        let mut drbg: HmacDrbg<_> = HmacDrbg::from_entropy_input(
+            &unhex("ca851911349384bffe89de1cbdc46e6831e44d34a4fb935ee285dd14b71a7488"),
+            &unhex("659ba96c601dc69fc902940805ec0ca8"),
+            &[],
+            Replay(vec![]),
+        );
+
+        let mut output = [0u8; 128];
+        drbg.generate(&mut output, &[]).unwrap();
+        drbg.generate(&mut output, &[]).unwrap();
+
+        assert_eq!(
+            output.to_vec(),
+            unhex(concat!(
+                "e528e9abf2dece54d47c7e75e5fe302149f817ea9fb4bee6f4199697d04d5b89",
+                "d54fbb978a15b5c443c9ec21036d2460b6f73ebad0dc2aba6e624abf07745bc1",
+                "07694bb7547bb0995f70de25d6b29e2d3011bb19d27676c07162c8b5ccde0668",
+                "961df86803482cb37ed6d5c0bb8d50cf1f50d476aa0458bdaba806f48be9dcb8",
+            ))
+        );
+        assert_eq!(drbg.reseed_counter(), 3);
+    }
+
+    #[test]
+    fn test_drbg_cavp_sha256_additional_input() {
+        // CAVP HMAC_DRBG.rsp, [SHA-256], no prediction resistance,
+        // AdditionalInputLen = 256, COUNT = 0.
+        let mut drbg: HmacDrbg<_> = HmacDrbg::from_entropy_input(
+            &unhex("d3cc4d1acf3dde0c4bd2290d262337042dc632948223d3a2eaab87da44295fbd"),
+            &unhex("0109b0e729f457328aa18569a9224921"),
+            &[],
+            Replay(vec![]),
+        );
+
+        let mut output = [0u8; 128];
+        drbg.generate(
+            &mut output,
+            &unhex("3c311848183c9a212a26f27f8c6647e40375e466a0857cc39c4e47575d53f1f6"),
+        )
+        .unwrap();
+        drbg.generate(
+            &mut output,
+            &unhex("fcb9abd19ccfbccef88c9c39bfb3dd7b1c12266c9808992e305bc3cff566e4e4"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            output.to_vec(),
+            unhex(concat!(
+                "9c7b758b212cd0fcecd5daa489821712e3cdea4467b560ef5ddc24ab47749a1f",
+                "1ffdbbb118f4e62fcfca3371b8fbfc5b0646b83e06bfbbab5fac30ea09ea2bc7",
+                "6f1ea568c9be0444b2cc90517b20ca825f2d0eccd88e7175538b85d90ab39018",
+                "3ca6395535d34473af6b5a5b88f5a59ee7561573337ea819da0dcc3573a22974",
+            ))
+        );
+    }
+
+    #[test]
+    fn test_drbg_cavp_prediction_resistance() {
+        // CAVP HMAC_DRBG_pr.rsp, [SHA-512], PredictionResistance = True,
+        // PersonalizationStringLen = 256, AdditionalInputLen = 256, COUNT = 0.
+        let mut entropy = unhex("4cc19fae5a456f8a53a656d23a0b665d6ddf7f43020a5febbb552714e447565d");
+        entropy.extend(unhex(
+            "637386b3ab33f78fd9751c7b7e67e1e15f6e50ddc548a1eb5813f6d0d48381bf",
+        ));
+
+        let mut drbg: HmacDrbg<_, Sha512> = HmacDrbg::from_entropy_input(
+            &unhex("3aca6b55561521007c9ece085e9a6635e346fa804335d6ad42ebd6814c017fa8"),
+            &unhex("aa7fd3c3dd5d03d9b8efc7f70574581f"),
+            &unhex("4bc9a485ec840d377ae4504aa1df41e444c4231687f3d7851c26c275bc687463"),
+            Replay(entropy),
+        )
+        .with_prediction_resistance(true);
+
+        let mut output = [0u8; 256];
+        drbg.generate(
+            &mut output,
+            &unhex("b39c43539fdc24343085cbb65b8d36c54732476d781104c355c391a951313a30"),
+        )
+        .unwrap();
+        drbg.generate(
+            &mut output,
+            &unhex("b6850edd4622675ef5a507eab911e249d63fcf62f330cc8a16bb2ccc5858de5d"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            output.to_vec(),
+            unhex(concat!(
+                "546664042bef33064da28a5718f2c2e5f72d7725e3fbe87ad2ee90fbfe6c114e",
+                "d36440fbbccf29698b4360bc4ad74650de13825838106adc53002bc389ee9006",
+                "91649b972f3187b84d05cecc8fd034497dd99c6c997d1914b4ef838d84abf23f",
+                "ae7f3ac9efdcdc04c003ac642c5126b00f9f24bf1431a4f19ef0b5f3d230aab3",
+                "fdf091ba31b7ddcacdf2566f2cfab30f55b3123e733829b697b7c8b248420ab9",
+                "8ba6f11b017175256368e8d8361102c9e6d57386becbeabda092dd57aec65bc2",
+                "0ebee78eea7294571e168c454066d256b81bb8b7bb469207a18ebedbb4348fbe",
+                "97a4d86d2bd095c41f6de59aa0800e131e98181886a2633cdcc550914d83b327",
+            ))
+        );
+
+        // The entropy source has run dry, so the next request must fail.
+        match drbg.generate(&mut output, &[]) {
+            Err(Error::Rng(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_drbg_cavp_sha512_reseed() {
+        // CAVP HMAC_DRBG.rsp, [SHA-512], PredictionResistance = False,
+        // PersonalizationStringLen = 256, AdditionalInputLen = 256, COUNT = 0.
+        let mut drbg: HmacDrbg<_, Sha512> = HmacDrbg::from_entropy_input(
+            &unhex("da740cbc36057a8e282ae717fe7dfbb245e9e5d49908a0119c5dbcf0a1f2d5ab"),
+            &unhex("46561ff612217ba3ff91baa06d4b5440"),
+            &unhex("fc227293523ecb5b1e28c87863626627d958acc558a672b148ce19e2abd2dde4"),
+            Replay(unhex(
+                "1d61d4d8a41c3254b92104fd555adae0569d1835bb52657ec7fbba0fe03579c5",
+            )),
+        );
+
+        let mut output = [0u8; 256];
+        drbg.reseed(&unhex(
+            "b9ed8e35ad018a375b61189c8d365b00507cb1b4510d21cac212356b5bbaa8b2",
+        ))
+        .unwrap();
+        assert_eq!(drbg.reseed_counter(), 1);
+        drbg.generate(
+            &mut output,
+            &unhex("b7998998eaf9e5d34e64ff7f03de765b31f407899d20535573e670c1b402c26a"),
+        )
+        .unwrap();
+        drbg.generate(
+            &mut output,
+            &unhex("2089d49d63e0c4df58879d0cb1ba998e5b3d1a7786b785e7cf13ca5ea5e33cfd"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            output.to_vec(),
+            unhex(concat!(
+                "5b70f3e4da95264233efbab155b828d4e231b67cc92757feca407cc9615a6608",
+                "71cb07ad1a2e9a99412feda8ee34dc9c57fa08d3f8225b30d29887d20907d123",
+                "30fffd14d1697ba0756d37491b0a8814106e46c8677d49d9157109c402ad0c24",
+                "7a2f50cd5d99e538c850b906937a05dbb8888d984bc77f6ca00b0e3bc97b16d6",
+                "d25814a54aa12143afddd8b2263690565d545f4137e593bb3ca88a37b0aadf79",
+                "726b95c61906257e6dc47acd5b6b7e4b534243b13c16ad5a0a1163c0099fce43",
+                "f428cd27c3e6463cf5e9a9621f4b3d0b3d4654316f4707675df39278d5783823",
+                "049477dcce8c57fdbd576711c91301e9bd6bb0d3e72dc46d480ed8f61fd63811",
+            ))
+        );
+        assert_eq!(drbg.reseed_counter(), 3);
+    }
+
+    #[test]
+    fn test_drbg_reseed_interval() {
+        let mut drbg: HmacDrbg<_> = HmacDrbg::new(Replay(vec![7; 48 + 32]), &[])
+            .unwrap()
+            .with_reseed_interval(2)
+            .unwrap();
+
+        let mut output = [0u8; 16];
+        drbg.generate(&mut output, &[]).unwrap();
+        drbg.generate(&mut output, &[]).unwrap();
+        assert_eq!(drbg.reseed_counter(), 3);
+
+        drbg.generate(&mut output, &[]).unwrap();
+        assert_eq!(drbg.reseed_counter(), 2);
+
+        assert!(drbg.generate(&mut [0; MAX_REQUEST_BYTES + 1], &[]).is_err());
+
+        let drbg: HmacDrbg<_> = HmacDrbg::new(Replay(vec![7; 48]), &[]).unwrap();
+        assert!(drbg.with_reseed_interval(0).is_err());
+    }
+
+    #[test]
+    fn test_drbg_prime() {
+        let seed = vec![0x5a; 48];
+        let mut first: HmacDrbg<_> = HmacDrbg::new(Replay(seed.clone()), &[]).unwrap();
+        let mut second: HmacDrbg<_> = HmacDrbg::new(Replay(seed), &[]).unwrap();
+
+        let p = prime::from_rng(512, &mut first).unwrap();
+        assert!(backend::is_prime(&p));
+        assert_eq!(prime::from_rng(512, &mut second).unwrap(), p);
+
+        let debug = format!("{:?}", first);
+        assert!(debug.starts_with("HmacDrbg { reseed_counter: "));
+    }
+}
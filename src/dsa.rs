@@ -0,0 +1,620 @@
+//! Generates and validates DSA domain parameters `(p, q, g)`, where `q` is
+//! an `N`-bit prime dividing `p - 1` for an `L`-bit prime `p`, and `g`
+//! generates the subgroup of order `q` modulo `p`.
+//!
+//! `from_rng()` follows FIPS 186-4 appendix A.1.1.2: `q` and `p` are
+//! derived from a random `domain_parameter_seed` with the hash `D`, and
+//! `counter` records how many candidates for `p` were tried. `g` is the
+//! verifiable canonical generator of appendix A.2.3, derived from the seed
+//! and an `index` that tells apart several generators for the same `p` and
+//! `q`. `VerifiableParameters::verify()` derives all three again, as
+//! appendices A.1.1.3 and A.2.4 describe. Only the `(L, N)` pairs that the
+//! standard approves are accepted: `(1024, 160)`, `(2048, 224)`,
+//! `(2048, 256)` and `(3072, 256)`.
+//!
+//! `schnorr_from_rng()` generates a Schnorr group of any size instead, from
+//! random primes, with the unverifiable generator of appendix A.2.1.
+//!
+//! `validate()` checks parameters from elsewhere, whatever their origin:
+//! `p` and `q` are prime, `q` divides `p - 1`, and `g` has order `q`.
+
+use ramp::Int;
+
+use hmac::digest::Digest;
+
+use rand::{thread_rng, Rng, RngCore};
+
+use backend::BigInt;
+use common::{
+    check_bit_length, gen_prime_unchecked, is_prime, is_prime_with_rng, random_bits, random_range,
+};
+use entropy;
+use error::{Error, Result};
+use provable::seed_plus;
+use report::GenerationReport;
+
+/// The `(L, N)` pairs approved by FIPS 186-4.
+const APPROVED_LENGTHS: [(usize, usize); 4] = [(1024, 160), (2048, 224), (2048, 256), (3072, 256)];
+
+/// The string that appendix A.2.3 hashes between the seed and the index.
+const GGEN: &[u8] = b"ggen";
+
+/// DSA domain parameters.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DomainParameters {
+    /// The prime modulus.
+    pub p: Int,
+    /// The prime order of the subgroup, which divides `p - 1`.
+    pub q: Int,
+    /// The generator of the subgroup of order `q`.
+    pub g: Int,
+}
+
+/// Domain parameters from `from_rng()`, with everything needed to derive
+/// them again.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifiableParameters {
+    /// The parameters themselves.
+    pub parameters: DomainParameters,
+    /// The seed `p`, `q` and `g` were derived from.
+    pub domain_parameter_seed: Vec<u8>,
+    /// The number of candidates for `p` tried before it, from 0.
+    pub counter: u32,
+    /// The index of the generator `g`.
+    pub index: u8,
+}
+
+impl VerifiableParameters {
+    /// Derives the parameters again from `domain_parameter_seed`, with the
+    /// hash `D`, and checks that every recorded value matches, then checks
+    /// them with `validate()`.
+    ///
+    /// Fails with `Error::InvalidParameter` if the lengths of `p` and `q`
+    /// are not an approved pair or `D` is too short for `q`, and with
+    /// `Error::Verification` if any value differs.
+    pub fn verify<D: Digest>(&self) -> Result<()> {
+        let parameters = &self.parameters;
+        let (l, n) = (
+            parameters.p.bit_length() as usize,
+            parameters.q.bit_length() as usize,
+        );
+        check_lengths::<D>(l, n)?;
+
+        let seed = &self.domain_parameter_seed;
+        let derived = if seed.len() * 8 < n {
+            None
+        } else {
+            derive_primes::<D, _>(l, n, seed, &mut thread_rng())
+        };
+        let matches = match derived {
+            Some((ref p, ref q, counter)) => {
+                *p == parameters.p
+                    && *q == parameters.q
+                    && counter == self.counter
+                    && derive_generator::<D>(p, q, seed, self.index).ok()
+                        == Some(parameters.g.clone())
+            }
+            None => false,
+        };
+        if !matches {
+            return Err(Error::Verification {
+                what: "the seeded domain parameters",
+            });
+        }
+
+        validate(parameters)
+    }
+}
+
+/// Generates domain parameters of an `l`-bit `p` and an `n`-bit `q`, with
+/// the generator of index `index`.
+///
+/// This will initialize an `OsRng` instance, checked by the health tests
+/// of the `entropy` module, and call the `from_rng()` function.
+pub fn new<D: Digest>(l: usize, n: usize, index: u8) -> Result<VerifiableParameters> {
+    check_lengths::<D>(l, n)?;
+
+    let mut rngesus = entropy::os()?;
+    from_rng::<D, _>(l, n, index, &mut rngesus)
+}
+
+/// Generates domain parameters exactly like `new()`, sourced from an
+/// already-initialized random number generator, which draws the
+/// `n`-bit seed and the bases of the primality tests.
+///
+/// Fails with `Error::InvalidParameter` if `(l, n)` is not an approved
+/// pair, or the output of `D` is shorter than `n` bits.
+pub fn from_rng<D: Digest, R: Rng + ?Sized>(
+    l: usize,
+    n: usize,
+    index: u8,
+    rngesus: &mut R,
+) -> Result<VerifiableParameters> {
+    check_lengths::<D>(l, n)?;
+
+    #[cfg(feature = "tracing")]
+    let _span = debug_span!("dsa", l = l as u64, n = n as u64).entered();
+
+    loop {
+        let mut seed = vec![0; n / 8];
+        rngesus.try_fill_bytes(&mut seed).map_err(Error::from_rng)?;
+
+        if let Some((p, q, counter)) = derive_primes::<D, _>(l, n, &seed, rngesus) {
+            let g = derive_generator::<D>(&p, &q, &seed, index)?;
+
+            return Ok(VerifiableParameters {
+                parameters: DomainParameters { p, q, g },
+                domain_parameter_seed: seed,
+                counter,
+                index,
+            });
+        }
+    }
+}
+
+/// Derives the verifiable canonical generator of index `index` for `p` and
+/// `q` from `domain_parameter_seed`, with the hash `D`, as in appendix
+/// A.2.3.
+///
+/// Fails with `Error::InvalidParameter` if `q` does not divide `p - 1`,
+/// and with `Error::BudgetExhausted` if all 65535 values of the counter
+/// give `g < 2`.
+pub fn derive_generator<D: Digest>(
+    p: &Int,
+    q: &Int,
+    domain_parameter_seed: &[u8],
+    index: u8,
+) -> Result<Int> {
+    let minus_one = p - 1_usize;
+    if *q <= 1_i32 || &minus_one % q != 0_i32 {
+        return Err(Error::InvalidParameter {
+            name: "q",
+            reason: "must divide p - 1".to_string(),
+        });
+    }
+    let e = minus_one / q;
+
+    for count in 1..=u16::MAX {
+        let mut u = domain_parameter_seed.to_vec();
+        u.extend_from_slice(GGEN);
+        u.push(index);
+        u.extend_from_slice(&count.to_be_bytes());
+
+        let w = Int::from_be_bytes(&D::digest(&u));
+        let g = w.pow_mod(&e, p);
+        if g >= 2_i32 {
+            return Ok(g);
+        }
+    }
+
+    Err(Error::BudgetExhausted {
+        attempts: u64::from(u16::MAX),
+    })
+}
+
+/// Generates a Schnorr group of an `l`-bit `p` and an `n`-bit `q`, for any
+/// lengths with `2 <= n < l`.
+///
+/// This will initialize an `OsRng` instance, checked by the health tests
+/// of the `entropy` module, and call the `schnorr_from_rng()` function.
+pub fn schnorr(l: usize, n: usize) -> Result<DomainParameters> {
+    check_schnorr_lengths(l, n)?;
+
+    let mut rngesus = entropy::os()?;
+    schnorr_from_rng(l, n, &mut rngesus)
+}
+
+/// Generates a Schnorr group exactly like `schnorr()`, sourced from an
+/// already-initialized random number generator.
+///
+/// `q` is a random prime, and `p` the first prime found among random
+/// `l`-bit numbers that are `1` modulo `2 q`. After `4 l` candidates, a new
+/// `q` is drawn. `g` is `h^((p - 1) / q)` for a random `h`, redrawn while
+/// that is 1.
+///
+/// Fails with `Error::BitLength` if `n` is below 2, and with
+/// `Error::InvalidParameter` if `l` does not exceed `n`.
+pub fn schnorr_from_rng<R: Rng + ?Sized>(
+    l: usize,
+    n: usize,
+    rngesus: &mut R,
+) -> Result<DomainParameters> {
+    check_schnorr_lengths(l, n)?;
+
+    #[cfg(feature = "tracing")]
+    let _span = debug_span!("schnorr", l = l as u64, n = n as u64).entered();
+
+    let mut report = GenerationReport::default();
+    let one = Int::one();
+
+    loop {
+        let q: Int = gen_prime_unchecked(n, rngesus, &mut report)?;
+        let twice_q: Int = &q << 1;
+
+        for _ in 0..4 * l {
+            let mut x: Int = random_bits(rngesus, l)?;
+            x.set_bit(l as u32 - 1, true);
+            let p = &x - (&x % &twice_q) + 1_usize;
+
+            if p.bit_length() as usize == l && is_prime_with_rng(&p, rngesus) {
+                let e = (&p - &one) / &q;
+                let two = Int::from(2);
+                let g = loop {
+                    let h = random_range(&two, &(&p - 1_usize), rngesus)?;
+                    let g = h.pow_mod(&e, &p);
+                    if g != one {
+                        break g;
+                    }
+                };
+
+                return Ok(DomainParameters { p, q, g });
+            }
+        }
+    }
+}
+
+/// Checks domain parameters of any origin: `p` and `q` are probable
+/// primes, `q` divides `p - 1`, and `g` lies in `[2, p - 1]` with
+/// `g^q = 1` modulo `p`, so that it generates the subgroup of order `q`.
+///
+/// Fails with `Error::Verification` if any of these does not hold.
+pub fn validate(parameters: &DomainParameters) -> Result<()> {
+    let DomainParameters {
+        ref p,
+        ref q,
+        ref g,
+    } = *parameters;
+    let one = Int::one();
+
+    let holds = *q > 1_i32
+        && *p > *q
+        && (p - &one) % q == 0_i32
+        && *g > one
+        && *g < *p
+        && g.pow_mod(q, p) == one
+        && is_prime(q)
+        && is_prime(p);
+
+    if holds {
+        Ok(())
+    } else {
+        Err(Error::Verification {
+            what: "the domain parameters",
+        })
+    }
+}
+
+/// Derives `q` from `seed` and searches `p`, as in steps 6 to 11 of
+/// appendix A.1.1.2. Returns them with the counter of `p`, or `None` if `q`
+/// is not prime or no `p` turned up within `4 l` candidates.
+fn derive_primes<D: Digest, R: RngCore + ?Sized>(
+    l: usize,
+    n: usize,
+    seed: &[u8],
+    rngesus: &mut R,
+) -> Option<(Int, Int, u32)> {
+    let outlen = <D as Digest>::output_size() * 8;
+    let top_q = Int::one() << (n - 1);
+    let top_p = Int::one() << (l - 1);
+
+    // q = 2^(N - 1) + U + 1 - (U mod 2), for U = Hash(seed) mod 2^(N - 1).
+    let u = Int::from_be_bytes(&D::digest(seed)) % &top_q;
+    let q = &top_q + &u + 1_usize - (&u % 2_usize);
+    if !is_prime_with_rng(&q, rngesus) {
+        return None;
+    }
+
+    let blocks = l.div_ceil(outlen) - 1;
+    let last_bits = l - 1 - blocks * outlen;
+    let twice_q: Int = &q << 1;
+    let mut offset = 1;
+
+    for counter in 0..4 * l as u32 {
+        // W is the hashes of seed + offset + j for j up to blocks, the last
+        // one cut down to last_bits bits.
+        let mut w = Int::zero();
+        for j in (0..=blocks).rev() {
+            let mut v = Int::from_be_bytes(&D::digest(seed_plus(seed, offset + j as u64)));
+            if j == blocks {
+                v %= Int::one() << last_bits;
+            }
+            w = (w << outlen) + v;
+        }
+
+        let x = w + &top_p;
+        let c = &x % &twice_q;
+        let p = x - (c - 1_usize);
+        if p >= top_p && is_prime_with_rng(&p, rngesus) {
+            return Some((p, q, counter));
+        }
+
+        offset += blocks as u64 + 1;
+    }
+
+    None
+}
+
+/// Fails with `Error::InvalidParameter` unless `(l, n)` is approved, and
+/// the output of `D` is at least `n` bits.
+fn check_lengths<D: Digest>(l: usize, n: usize) -> Result<()> {
+    if !APPROVED_LENGTHS.contains(&(l, n)) {
+        return Err(Error::InvalidParameter {
+            name: "l and n",
+            reason: "must be a pair approved by FIPS 186-4".to_string(),
+        });
+    }
+    if <D as Digest>::output_size() * 8 < n {
+        return Err(Error::InvalidParameter {
+            name: "D",
+            reason: format!("must output at least {} bits", n),
+        });
+    }
+
+    Ok(())
+}
+
+/// Fails unless `2 <= n < l`.
+fn check_schnorr_lengths(l: usize, n: usize) -> Result<()> {
+    check_bit_length(n, 2)?;
+    if l <= n {
+        return Err(Error::InvalidParameter {
+            name: "l",
+            reason: "must exceed n".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        derive_generator, derive_primes, from_rng, schnorr_from_rng, validate, DomainParameters,
+        VerifiableParameters,
+    };
+    use error::Error;
+    use hmac::digest::Digest;
+    use ramp::Int;
+    use rand::thread_rng;
+    use sha2::{Sha224, Sha256, Sha512};
+
+    #[test]
+    fn test_dsa_parameters() {
+        let seeded = from_rng::<Sha256, _>(1024, 160, 1, &mut thread_rng()).unwrap();
+        let parameters = &seeded.parameters;
+        assert_eq!(parameters.p.bit_length(), 1024);
+        assert_eq!(parameters.q.bit_length(), 160);
+        assert_eq!(seeded.domain_parameter_seed.len(), 20);
+        assert!(seeded.counter < 4 * 1024);
+        assert!(validate(parameters).is_ok());
+        assert!(seeded.verify::<Sha256>().is_ok());
+
+        // Every recorded value is checked.
+        let mut tampered = seeded.clone();
+        tampered.counter += 1;
+        assert!(tampered.verify::<Sha256>().is_err());
+
+        let mut tampered = seeded.clone();
+        tampered.index = 2;
+        assert!(tampered.verify::<Sha256>().is_err());
+
+        let mut tampered = seeded.clone();
+        tampered.domain_parameter_seed[0] ^= 1;
+        assert!(tampered.verify::<Sha256>().is_err());
+
+        let mut tampered = seeded.clone();
+        tampered.parameters.g = derive_generator::<Sha256>(
+            &parameters.p,
+            &parameters.q,
+            &seeded.domain_parameter_seed,
+            2,
+        )
+        .unwrap();
+        assert!(validate(&tampered.parameters).is_ok());
+        assert!(tampered.verify::<Sha256>().is_err());
+
+        match seeded.verify::<Sha224>() {
+            Err(Error::Verification { what }) => assert_eq!(what, "the seeded domain parameters"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    // (L, N, seed, counter, p, q, g of index 1), with SHA-256, from a
+    // separate implementation of appendices A.1.1.2 and A.2.3 rather than
+    // from the CAVP response files. They are the only check of the
+    // generator against values from elsewhere.
+    const DERIVED: [(usize, usize, &str, u32, &str, &str, &str); 2] = [
+        (
+            1024,
+            160,
+            "5A41FAA80E5E6D33DE471C250162A47B4D969458",
+            245,
+            "E0E0D697EB3919CB75F5EABE17124D96ABDD9716E4BC8B073CB25A340B903853DF94138C7F35FCA5\
+             59E9A4DCABAC337FFBED830BD2231A7852F550D2781202A26E068ACE9C01AC7667EA017F7CAEBCB3\
+             D99D153CE5D44013C1372CC99BD4C15179027C3EB1D58CBB453754013CA9519FB4168A66B0E766A7\
+             E286ECD8FA546183",
+            "A6E2411E528BDC529C1101D4D0BC03E7CDC1DE23",
+            "CA68123CF706842E3DE27983E5401355BDB210B9D2CE56B8F7C9A0819FB33F91788B5C081D7474AF\
+             F2329821F884C19AA7CEC2E0ED20692E6807377CAEA8B343FD3ADA89C593F35689D25CDCBF62A3D1\
+             ECD484648A24F5237FAD7FBB7005AFDD74E600A29D46F557D94AF0C9BD71CC8D7A76B984A5E36DD1\
+             1D3366FC4467F6D0",
+        ),
+        (
+            2048,
+            256,
+            "04D8D2BFFA791B2B24C04436EA1F8CC0A408F617A8399B0633E86C801DC64331",
+            1137,
+            "C49EACCA5BF2FD7AE81FAD3879A7A8619C78E2C1AFA6BFBF55D41FEDA6002A0ADA91763841DF5001\
+             5590558FACF851EF56A4E79715F9EA8BB1B8177F620E206735736CAD69FF337F8C6C8C38CC8F7CC9\
+             4C89A08DB632BBAC0CC21C87C86F8D29D8323ABA5EE116C5C3DD2A89264287053A7A16007674B8B7\
+             A985E9150E98E0B0C641C8F3779702C5AC874A3F51A05543D8EDE4E507945C2CB9E20631DE0E324C\
+             F5ED5FC6CBE8B366B9D235B98DC0E930109C93A2593720EBA7D0727E734B607DCBA2D8D4D9ED26E8\
+             BA755C71F837F8B202CB1C5F479706C76123D766BA075AF75E9B464BECEB11AB67AD1F3629A60648\
+             A4E021A787B75472B9147C51C5A93B4B",
+            "C0AAF7737681650CD466E88FB7FDD32B79D150BE08BBE14EB398B8FFBB04C3AF",
+            "2D7A3B97312C7D1DAD0BE509AEA85789C3BCE6EB17BBEE504D2E142DC58849CB254360BF5622B4C1\
+             09E6FC73B8CA2B7F5A925791F08E1D5BC27BCBA85127C09B5EB754DBEBAF06D611D37B173D24AE03\
+             EDDE87173BE5AEC67158338BDAAADD20BDBD6182AD1339A6CCBC0F15516EB6EE6CA7E8FF43C1B2C0\
+             F0B619583DA05DDD383F16CA65838A8FB14D75FAA58A73DDE2E65E6BAF3CE557C73305F4537586DE\
+             BDB19AB8ED6B8375F3920C546D1245DC2A90FE66D067D2760A9F5B959C4C3FE30F200FE35B4B3233\
+             4DC756446CD71F7C4961938257D12F095C06C4B2B9EEFE0018C2045B5D18C9F3EFC09A4C4CF31D95\
+             2BAAEBF1CC84F2783812CCEADF8315AB",
+        ),
+    ];
+
+    // (L, N, seed, counter, p, q) for appendix A.1.1.2, from the
+    // validation data in OpenSSL 3.1's tests: PQGVer entries with SHA-224
+    // and SHA-512 from test/ffc_internal_test.c, and ACVP PQVer entries
+    // with SHA-256 from test/acvp_test.inc.
+    type Vector = (usize, usize, &'static str, u32, &'static str, &'static str);
+
+    const VALIDATION_SHA224: Vector = (
+        2048,
+        224,
+        "D2B136D85B8EA4B26AAB4E858B49F9DDE6A1CDAD4952E9B3361706CF",
+        2878,
+        "935793621B9A109BC1560F2471764ED3ED78787ABF8971678E03D85BCD228F7074FF2205070C4C60\
+             ED41E19E9CAA3E195C3D8058B27F5F89ECB519DB0611E9785CF9A09E7062147BDA92BFB26B016FB8\
+             689C893689727949933D14B22DBBF0DF94450B5FF17537EB49B92DCEB7F49577C2E9391C4E0C4062\
+             330AE6296FBAEF02DD0DE404017040B9C97E2F1037E9DEB0F6EB717F9C3516F30DC4E802376CDDB3\
+             8D2D1E2813228940E5FA1667D6DA12A2388325CC26C12774FEF67AB6A1E4E8DF5DD29C2FECEA08CA\
+             48DB184B12EE169BA600A018987DCE6C6DF8FC95511B0A40B6FCE5E2B026534CD7FEAA6DBCDDC061\
+             65E48944186FD539CF756D29CCF840AB",
+        "F25E4E9A15A813DFA31790C6D65EB1FB31F8B5B14BA76DDE57766F11",
+    );
+
+    const VALIDATION_SHA256: Vector = (
+        3072,
+        256,
+        "33DC43AFC4515C3B8B8A0D5DA284DE6DCC6CFD423798FB66ABD373961FC5D146",
+        1956,
+        "EFC795EB1E1C8F5E4A85CD2066C7B96C4EC4E73B7B8E0E8C00F52E68F5C28947A57BA6A330BCFA25\
+             29BDE24D050B6D2D495053EF8CBEC3EC92C145E395397258FD93230637D6561F7592AD15A988253F\
+             D647B5B132012D7055B95DED1B40397874A6DF4BE4868B56461EDB04D2D250E95D88A88455E3F3B7\
+             07549E98039F3186EB0D26973031346435564035EAE50090BD2093FCAD709AF5B8A4ADECFE64F42C\
+             112568270E5C8157649A5086A369611E0D62E94D441E1EE16D8F106782B66AD00859F3BAE829E060\
+             1F3EBAAB6EB65BAFCC765D707F3AAA7E27236F8EF806C13EAEBE227193EC9A333CA477D47679105A\
+             F40752669DC5FDDAA1E7A245270854B93BEC07FBE0F44B7CB1042B0E653AF7655765CF36282A1C57\
+             10280226F745AA1B2EE325EA28A1841EA1A3AB5225D464B2A8A5FD2F4890288F8B107F6F80A94BB3\
+             C05B27E9909053A83088D49B0962CD99616314DFC35A60BEA340AB293EB202199D9775340D713BEC\
+             F11323E6CA3584FF274AE01159EB1D8CFFF391903CE94331",
+        "CB74E657370F7A610B09CE9178063C7F20F5D11E1DC243BA89C84A498338E12D",
+    );
+
+    // Rejected: q was swapped for another prime, which does not divide
+    // p - 1.
+    const REJECTED_SHA512: Vector = (
+        3072,
+        256,
+        "3524B559D5275810F6A27C9A0DC2708AB0414A840BFE66F53ABF4AA9CBFCA622",
+        1604,
+        "9A828B8DEAD05623882D5D41424C135A15815902C500822801EE8F99FD6A95F20FAE347729CCC750\
+             0E03EFB04DE51000A87BCE8CC6B20174231B7FE8F9712839CF1804B295612D11716BDD0D0BF0E697\
+             52299D45B123DAB0D5CB51718E409C9713EA1F4B325D2774818D478A08CEF4D128A20F9B2EC9A30E\
+             5DDE47196D5F98E08E7F608F25A7A4EBB9F324A49EC1BD14277C27C84F5FEDFD86C8F1D782E2EBE5\
+             D2BEB06528AB999ECDD522F81B3B01E9203DE49822FEFC097E9520DAB6122C945CEA7471BD19AC78\
+             430251B85F061DEAC8A43BC978A32B09DC7674C42314482E842BA382C1BA0B392A9F247BD6C2EA5A\
+             B6BD15822185E06B124F8D6475EB7EA1DBE09D25AE3BE99B217F9A3D66D0521D398BEBFCECBE7220\
+             5ADF1B00F10EEDC6786FC9ABE4D6818BCCF6D46A316208D938218FDA9EB12B9CC0BEF79A432D0759\
+             460ED5234EAA4A04C2DE33A634BAAC4F78D8CA76CE5ED4F6854C6A60085D0E348BF2B6E3B751CA43\
+             AA687B0A6EEACE1E2C348E0FE2CC38F29A98EFE67FF662BB",
+        "C1DBC121504963A3776D4C92ED589E98EAAC7A901324F7CDD7E6D48FF0454BF7",
+    );
+
+    // The seed of the rejected ACVP PQVer entry, which does not hash to a
+    // prime q.
+    const REJECTED_SEED: &str = "EEA402700B89B796525C00C48E14450F6A1800F72452410E3341D291C3167D5D";
+
+    fn unhex(digits: &str) -> Vec<u8> {
+        (0..digits.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_dsa_derivation() {
+        let hex = |digits: &str| Int::from_str_radix(digits, 16).unwrap();
+
+        for &(l, n, seed, counter, p, q, g) in &DERIVED {
+            let seed = unhex(seed);
+            let (p, q, g) = (hex(p), hex(q), hex(g));
+
+            let derived = derive_primes::<Sha256, _>(l, n, &seed, &mut thread_rng());
+            assert_eq!(derived, Some((p.clone(), q.clone(), counter)));
+            assert_eq!(derive_generator::<Sha256>(&p, &q, &seed, 1).unwrap(), g);
+
+            let seeded = VerifiableParameters {
+                parameters: DomainParameters { p, q, g },
+                domain_parameter_seed: seed,
+                counter,
+                index: 1,
+            };
+            assert!(seeded.verify::<Sha256>().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_dsa_validation_vectors() {
+        fn matches<D: Digest>(&(l, n, seed, counter, p, q): &Vector) -> bool {
+            let hex = |digits: &str| Int::from_str_radix(digits, 16).unwrap();
+            let derived = derive_primes::<D, _>(l, n, &unhex(seed), &mut thread_rng());
+            derived == Some((hex(p), hex(q), counter))
+        }
+
+        assert!(matches::<Sha224>(&VALIDATION_SHA224));
+        assert!(matches::<Sha256>(&VALIDATION_SHA256));
+        assert!(!matches::<Sha512>(&REJECTED_SHA512));
+
+        let seed = unhex(REJECTED_SEED);
+        assert_eq!(
+            derive_primes::<Sha256, _>(3072, 256, &seed, &mut thread_rng()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_dsa_lengths() {
+        match from_rng::<Sha256, _>(1024, 224, 1, &mut thread_rng()) {
+            Err(Error::InvalidParameter { name, .. }) => assert_eq!(name, "l and n"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match from_rng::<Sha224, _>(2048, 256, 1, &mut thread_rng()) {
+            Err(Error::InvalidParameter { name, .. }) => assert_eq!(name, "D"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match schnorr_from_rng(160, 160, &mut thread_rng()) {
+            Err(Error::InvalidParameter { name, .. }) => assert_eq!(name, "l"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_dsa_schnorr() {
+        let parameters = schnorr_from_rng(600, 100, &mut thread_rng()).unwrap();
+        assert_eq!(parameters.p.bit_length(), 600);
+        assert_eq!(parameters.q.bit_length(), 100);
+        assert!(validate(&parameters).is_ok());
+
+        // g = -1 has order 2, and p and q that do not fit together fail.
+        let p = &parameters.p;
+        let q = &parameters.q;
+        let whole = DomainParameters {
+            g: p - 1_usize,
+            ..parameters.clone()
+        };
+        assert!(validate(&whole).is_err());
+
+        let composite = DomainParameters {
+            p: p * Int::from(3),
+            ..parameters.clone()
+        };
+        assert!(validate(&composite).is_err());
+
+        let unrelated = DomainParameters {
+            q: q + 2_usize,
+            ..parameters.clone()
+        };
+        assert!(validate(&unrelated).is_err());
+    }
+}
@@ -0,0 +1,442 @@
+//! Hilbert class polynomials of imaginary quadratic discriminants.
+//!
+//! The roots of the class polynomial `H_D` of a fundamental discriminant
+//! `D < 0` are the values `j((-b + sqrt(D)) / 2a)` of the modular function
+//! `j`, one for each reduced form `(a, b, c)` of discriminant `D`. They are
+//! computed here in fixed-point complex arithmetic over `Int`, with enough
+//! precision that the coefficients of their product round to the right
+//! integers.
+
+use ramp::Int;
+
+use std::f64::consts::PI;
+use std::sync::OnceLock;
+
+/// The largest `|D|` considered.
+const MAX_DISCRIMINANT: u64 = 200_000;
+
+/// The largest class number considered, which is the degree of `H_D`.
+const MAX_CLASS_NUMBER: usize = 50;
+
+/// The bits of precision kept beyond what the coefficients need.
+const GUARD_BITS: usize = 64;
+
+/// A fundamental discriminant `D = -d`, with its reduced forms.
+#[derive(Clone, Debug)]
+pub struct Discriminant {
+    /// The absolute value of the discriminant.
+    pub d: u64,
+    /// The reduced forms `(a, b)` with `b >= 0`. Each one with `0 < b < a <
+    /// c` stands for the form `(a, -b)` as well, whose root is the complex
+    /// conjugate.
+    forms: Vec<(u64, u64, bool)>,
+}
+
+impl Discriminant {
+    /// Returns the class number of the discriminant.
+    pub fn class_number(&self) -> usize {
+        self.forms
+            .iter()
+            .map(|&(_, _, paired)| if paired { 2 } else { 1 })
+            .sum()
+    }
+}
+
+/// Returns the fundamental discriminants `D < 0` with `|D|` up to
+/// `MAX_DISCRIMINANT` and class number up to `MAX_CLASS_NUMBER`, ordered by
+/// class number and then by `|D|`, so the cheapest come first: `D = -3` and
+/// `D = -4` lead the list.
+///
+/// The list is built on the first call, and shared from then on.
+pub fn discriminants() -> &'static [Discriminant] {
+    static DISCRIMINANTS: OnceLock<Vec<Discriminant>> = OnceLock::new();
+
+    DISCRIMINANTS.get_or_init(list_discriminants)
+}
+
+fn list_discriminants() -> Vec<Discriminant> {
+    let mut discriminants: Vec<Discriminant> = (3..=MAX_DISCRIMINANT)
+        .filter(|&d| is_fundamental(d))
+        .filter_map(|d| {
+            let discriminant = Discriminant {
+                d,
+                forms: reduced_forms(d, MAX_CLASS_NUMBER)?,
+            };
+            Some(discriminant)
+        })
+        .collect();
+
+    discriminants.sort_by_key(|discriminant| (discriminant.class_number(), discriminant.d));
+    discriminants
+}
+
+/// Computes the class polynomial of `discriminant`, with the coefficients
+/// listed from the constant term up. The polynomial is monic.
+pub fn class_polynomial(discriminant: &Discriminant) -> Vec<Int> {
+    let d = discriminant.d;
+
+    // log2 |j| is close to pi sqrt(d) / (a ln 2), and the coefficients are
+    // at most the product of (|j| + 1) over the roots.
+    let bits: f64 = discriminant
+        .forms
+        .iter()
+        .map(|&(a, _, paired)| {
+            let root_bits = PI * (d as f64).sqrt() / (a as f64 * 2f64.ln()) + 12.0;
+            if paired {
+                2.0 * root_bits
+            } else {
+                root_bits
+            }
+        })
+        .sum();
+    let precision = bits.ceil() as usize + 2 * discriminant.class_number() + GUARD_BITS;
+
+    let fixed = Fixed::new(precision);
+    let mut polynomial = vec![Complex::one(&fixed)];
+    for &(a, b, paired) in &discriminant.forms {
+        let root = fixed.j(d, a, b);
+
+        if paired {
+            let conjugate = Complex {
+                re: root.re.clone(),
+                im: -root.im.clone(),
+            };
+            polynomial = fixed.times_linear(&polynomial, &conjugate);
+        }
+        polynomial = fixed.times_linear(&polynomial, &root);
+    }
+
+    polynomial
+        .iter()
+        .map(|coefficient| fixed.round(&coefficient.re))
+        .collect()
+}
+
+/// Checks whether `-d` is a fundamental discriminant.
+fn is_fundamental(d: u64) -> bool {
+    match d % 4 {
+        3 => is_squarefree(d),
+        0 => (d / 4) % 4 != 3 && !(d / 4).is_multiple_of(4) && is_squarefree(d / 4),
+        _ => false,
+    }
+}
+
+fn is_squarefree(n: u64) -> bool {
+    (2..)
+        .take_while(|p| p * p <= n)
+        .all(|p| !n.is_multiple_of(p * p))
+}
+
+/// Lists the primitive reduced forms of discriminant `-d`, giving up with
+/// `None` once there are more than `max_forms` of them.
+fn reduced_forms(d: u64, max_forms: usize) -> Option<Vec<(u64, u64, bool)>> {
+    let mut forms = Vec::new();
+    let mut count = 0;
+
+    // Reduced forms have |b| <= a <= c, so 3 b^2 <= d.
+    let mut b = d % 2;
+    while 3 * b * b <= d {
+        let ac = (b * b + d) / 4;
+        let mut a = b.max(1);
+
+        while a * a <= ac {
+            if ac.is_multiple_of(a) {
+                let c = ac / a;
+                if gcd(gcd(a, b), c) == 1 {
+                    let paired = b > 0 && b != a && a != c;
+                    count += if paired { 2 } else { 1 };
+                    if count > max_forms {
+                        return None;
+                    }
+
+                    forms.push((a, b, paired));
+                }
+            }
+            a += 1;
+        }
+        b += 2;
+    }
+
+    Some(forms)
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+    a
+}
+
+/// A complex number in fixed point.
+#[derive(Clone, Debug)]
+struct Complex {
+    re: Int,
+    im: Int,
+}
+
+impl Complex {
+    fn one(fixed: &Fixed) -> Complex {
+        Complex {
+            re: fixed.one.clone(),
+            im: Int::zero(),
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.re == Int::zero() && self.im == Int::zero()
+    }
+}
+
+/// Fixed-point arithmetic, on numbers scaled by `2^precision`.
+struct Fixed {
+    precision: usize,
+    one: Int,
+    pi: Int,
+}
+
+impl Fixed {
+    fn new(precision: usize) -> Fixed {
+        let one = Int::one() << precision;
+
+        // Machin's formula: pi = 16 atan(1/5) - 4 atan(1/239).
+        let pi = arctan_inverse(5, &one) * 16_usize - arctan_inverse(239, &one) * 4_usize;
+
+        Fixed { precision, one, pi }
+    }
+
+    fn mul(&self, x: &Complex, y: &Complex) -> Complex {
+        Complex {
+            re: (&x.re * &y.re - &x.im * &y.im) >> self.precision,
+            im: (&x.re * &y.im + &x.im * &y.re) >> self.precision,
+        }
+    }
+
+    fn div(&self, x: &Complex, y: &Complex) -> Complex {
+        let norm = (&y.re * &y.re + &y.im * &y.im) >> self.precision;
+
+        Complex {
+            re: (&x.re * &y.re + &x.im * &y.im) / &norm,
+            im: (&x.im * &y.re - &x.re * &y.im) / &norm,
+        }
+    }
+
+    /// Returns `x^24`.
+    fn pow_24(&self, x: &Complex) -> Complex {
+        let x2 = self.mul(x, x);
+        let x4 = self.mul(&x2, &x2);
+        let x8 = self.mul(&x4, &x4);
+        let x16 = self.mul(&x8, &x8);
+
+        self.mul(&x16, &x8)
+    }
+
+    /// Rounds `x` to the nearest integer.
+    fn round(&self, x: &Int) -> Int {
+        let half = Int::one() << (self.precision - 1);
+        let magnitude = (x.clone().abs() + half) >> self.precision;
+
+        if *x < Int::zero() {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+
+    /// Returns `e^x`, for a real `x`.
+    fn exp(&self, x: &Int) -> Int {
+        // e^x = (e^(x / 2^k))^(2^k), with x / 2^k below 2^-8.
+        let halvings = (x.bit_length() as usize + 8).saturating_sub(self.precision);
+        let reduced = x >> halvings;
+
+        let mut sum = self.one.clone();
+        let mut term = self.one.clone();
+        for k in 1_usize.. {
+            term = ((term * &reduced) >> self.precision) / k;
+            if term == Int::zero() {
+                break;
+            }
+            sum += &term;
+        }
+
+        for _ in 0..halvings {
+            sum = sum.square() >> self.precision;
+        }
+        sum
+    }
+
+    /// Returns `cos(x)` and `sin(x)`, for a real `x` with `|x| <= pi`.
+    fn cos_sin(&self, x: &Int) -> (Int, Int) {
+        let square = x.square() >> self.precision;
+        let mut cos = self.one.clone();
+        let mut sin = x.clone();
+        let mut term = x.clone();
+
+        for k in 1_usize.. {
+            // term = x^k / k!, with the sign of its series.
+            term = -((term * &square) >> self.precision) / (2 * k * (2 * k + 1));
+            if term == Int::zero() {
+                break;
+            }
+            sin += &term;
+        }
+
+        term = self.one.clone();
+        for k in 1_usize.. {
+            term = -((term * &square) >> self.precision) / (2 * k * (2 * k - 1));
+            if term == Int::zero() {
+                break;
+            }
+            cos += &term;
+        }
+
+        (cos, sin)
+    }
+
+    /// Returns Euler's function `prod (1 - q^n)`, from the pentagonal number
+    /// theorem: `1 + sum (-1)^n (q^(n (3n - 1) / 2) + q^(n (3n + 1) / 2))`.
+    fn euler(&self, q: &Complex) -> Complex {
+        let mut sum = Complex::one(self);
+        let mut q_n = Complex::one(self);
+        let mut pentagonal = Complex::one(self);
+
+        for n in 1_usize.. {
+            // q^(n (3n - 1) / 2) = q^((n - 1) (3n - 4) / 2) q^(3n - 2), which
+            // is the last one times (q^(n - 1))^3 q.
+            let cube = self.mul(&self.mul(&q_n, &q_n), &q_n);
+            pentagonal = self.mul(&self.mul(&pentagonal, &cube), q);
+            q_n = self.mul(&q_n, q);
+            let next = self.mul(&pentagonal, &q_n);
+
+            if pentagonal.is_zero() {
+                break;
+            }
+            if n % 2 == 1 {
+                sum.re -= &pentagonal.re + &next.re;
+                sum.im -= &pentagonal.im + &next.im;
+            } else {
+                sum.re += &pentagonal.re + &next.re;
+                sum.im += &pentagonal.im + &next.im;
+            }
+        }
+
+        sum
+    }
+
+    /// Returns `j((-b + sqrt(-d)) / 2a)`.
+    fn j(&self, d: u64, a: u64, b: u64) -> Complex {
+        // q = e^(2 pi i tau) = e^(-pi sqrt(d) / a) e^(-i pi b / a).
+        let sqrt_d = (Int::from(d) << (2 * self.precision))
+            .sqrt_rem()
+            .map(|(root, _)| root)
+            .unwrap_or_else(Int::zero);
+        let radius = ((&self.pi * &sqrt_d) >> self.precision) / a as usize;
+        let (cos, sin) = self.cos_sin(&((&self.pi * b as usize) / a as usize));
+
+        let small = self.exp(&-radius.clone());
+        let large = self.exp(&radius);
+        let q = Complex {
+            re: (&small * &cos) >> self.precision,
+            im: -((&small * &sin) >> self.precision),
+        };
+        let q_inverse = Complex {
+            re: (&large * &cos) >> self.precision,
+            im: (&large * &sin) >> self.precision,
+        };
+
+        // With f = Delta(2 tau) / Delta(tau) = q (E(q^2) / E(q))^24, for
+        // Euler's function E, j = (256 f + 1)^3 / f. The factor q is kept
+        // apart, so that 1 / q keeps its full relative precision.
+        let ratio = self.pow_24(&self.div(&self.euler(&self.mul(&q, &q)), &self.euler(&q)));
+        let f = self.mul(&q, &ratio);
+        let base = Complex {
+            re: &f.re * 256_usize + &self.one,
+            im: &f.im * 256_usize,
+        };
+        let cube = self.mul(&self.mul(&base, &base), &base);
+
+        self.div(&self.mul(&cube, &q_inverse), &ratio)
+    }
+
+    /// Multiplies `polynomial` by `x - root`.
+    fn times_linear(&self, polynomial: &[Complex], root: &Complex) -> Vec<Complex> {
+        let mut product = vec![
+            Complex {
+                re: Int::zero(),
+                im: Int::zero(),
+            };
+            polynomial.len() + 1
+        ];
+
+        for (i, coefficient) in polynomial.iter().enumerate() {
+            let scaled = self.mul(coefficient, root);
+            product[i].re -= &scaled.re;
+            product[i].im -= &scaled.im;
+            product[i + 1].re += &coefficient.re;
+            product[i + 1].im += &coefficient.im;
+        }
+
+        product
+    }
+}
+
+/// Returns `atan(1 / x)` in fixed point, scaled by `one`.
+fn arctan_inverse(x: usize, one: &Int) -> Int {
+    let square = x * x;
+    let mut power = one / x;
+    let mut sum = power.clone();
+
+    for k in 1_usize.. {
+        power /= square;
+        let term = &power / (2 * k + 1);
+        if term == Int::zero() {
+            break;
+        }
+        if k % 2 == 1 {
+            sum -= term;
+        } else {
+            sum += term;
+        }
+    }
+
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{class_polynomial, discriminants, reduced_forms};
+
+    #[test]
+    fn test_class_polynomial() {
+        let all = discriminants();
+        assert_eq!(
+            all.iter()
+                .take_while(|discriminant| discriminant.class_number() == 1)
+                .map(|discriminant| discriminant.d)
+                .collect::<Vec<_>>(),
+            vec![3, 4, 7, 8, 11, 19, 43, 67, 163]
+        );
+
+        let polynomial = |d| {
+            class_polynomial(all.iter().find(|discriminant| discriminant.d == d).unwrap())
+                .iter()
+                .map(|coefficient| coefficient.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(polynomial(3), vec!["0", "1"]);
+        assert_eq!(polynomial(4), vec!["-1728", "1"]);
+        assert_eq!(polynomial(7), vec!["3375", "1"]);
+        assert_eq!(polynomial(163), vec!["262537412640768000", "1"]);
+        assert_eq!(polynomial(20), vec!["-681472000", "-1264000", "1"]);
+        assert_eq!(
+            polynomial(23),
+            vec!["12771880859375", "-5151296875", "3491750", "1"]
+        );
+
+        // -56 has the forms (1, 0, 14), (2, 0, 7), (3, 2, 5) and (3, -2, 5).
+        assert_eq!(reduced_forms(56, 20).unwrap().len(), 3);
+        assert!(reduced_forms(56, 3).is_none());
+    }
+}
@@ -0,0 +1,270 @@
+//! Elliptic curves in short Weierstrass form over the integers modulo `n`.
+//!
+//! `n` need not be prime, as the point of ECPP is to find out whether it
+//! is. The group law is computed as if `n` were prime, and every operation
+//! fails with `None` instead when it has to invert a number that shares a
+//! factor with `n`. Whenever an operation succeeds, its result reduces to
+//! the right one modulo every prime factor of `n`, which is what the proofs
+//! of `certificate::Ecpp` rely on.
+
+use ramp::Int;
+
+use common::mod_inverse;
+
+/// The curve `y^2 = x^3 + a x + b` over the integers modulo `n`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Curve {
+    a: Int,
+    b: Int,
+    n: Int,
+}
+
+/// A point of a `Curve`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Point {
+    /// The point at infinity, the identity of the group.
+    Infinity,
+    /// The point `(x, y)`, with both coordinates in `[0, n)`.
+    Affine(Int, Int),
+}
+
+impl Curve {
+    /// Constructs the curve `y^2 = x^3 + a x + b` modulo the odd `n`.
+    ///
+    /// Returns `None` unless `4 a^3 + 27 b^2`, the discriminant up to a
+    /// constant, is invertible modulo `n`, so that the curve is not
+    /// singular modulo any of its prime factors.
+    pub fn new(a: &Int, b: &Int, n: &Int) -> Option<Curve> {
+        let curve = Curve {
+            a: reduce(a.clone(), n),
+            b: reduce(b.clone(), n),
+            n: n.clone(),
+        };
+
+        let discriminant = curve.a.pow(3) * 4_usize + curve.b.square() * 27_usize;
+        if n.is_even() || discriminant.gcd(n) != Int::one() {
+            return None;
+        }
+
+        Some(curve)
+    }
+
+    /// Returns the coefficient `a`.
+    pub fn a(&self) -> &Int {
+        &self.a
+    }
+
+    /// Returns the coefficient `b`.
+    pub fn b(&self) -> &Int {
+        &self.b
+    }
+
+    /// Returns the modulus `n`.
+    pub fn n(&self) -> &Int {
+        &self.n
+    }
+
+    /// Checks whether `point` lies on the curve, with its coordinates in
+    /// `[0, n)`.
+    pub fn contains(&self, point: &Point) -> bool {
+        match *point {
+            Point::Infinity => true,
+            Point::Affine(ref x, ref y) => {
+                let in_range = |coordinate: &Int| *coordinate >= 0_i32 && coordinate < &self.n;
+                let rhs = (x.pow(3) + &self.a * x + &self.b) % &self.n;
+
+                in_range(x) && in_range(y) && y.square() % &self.n == rhs
+            }
+        }
+    }
+
+    /// Returns `-point`.
+    pub fn negate(&self, point: &Point) -> Point {
+        match *point {
+            Point::Infinity => Point::Infinity,
+            Point::Affine(ref x, ref y) => Point::Affine(x.clone(), reduce(-y, &self.n)),
+        }
+    }
+
+    /// Returns `p + q`, or `None` if that needs an inverse that does not
+    /// exist.
+    pub fn add(&self, p: &Point, q: &Point) -> Option<Point> {
+        let (x1, y1, x2, y2) = match (p, q) {
+            (Point::Infinity, _) => return Some(q.clone()),
+            (_, Point::Infinity) => return Some(p.clone()),
+            (Point::Affine(x1, y1), Point::Affine(x2, y2)) => (x1, y1, x2, y2),
+        };
+        let n = &self.n;
+
+        let slope = if x1 != x2 {
+            (y2 - y1) * mod_inverse(&reduce(x2 - x1, n), n)? % n
+        } else if reduce(y1 + y2, n) == Int::zero() {
+            return Some(Point::Infinity);
+        } else if y1 == y2 {
+            (x1.square() * 3_usize + &self.a) * mod_inverse(&(y1 * 2_usize), n)? % n
+        } else {
+            // y1 = y2 modulo some factors of n, and y1 = -y2 modulo others.
+            return None;
+        };
+
+        let x3 = reduce(slope.square() - x1 - x2, n);
+        let y3 = reduce(slope * (x1 - &x3) - y1, n);
+
+        Some(Point::Affine(x3, y3))
+    }
+
+    /// Returns `k point`, for `k >= 0`, or `None` if that needs an inverse
+    /// that does not exist.
+    ///
+    /// `(k - 1) point` is computed in Jacobian coordinates, whose formulas
+    /// give `Z = 0` modulo any prime factor of `n` where they meet a case
+    /// they do not handle, and keep it zero from then on. Inverting the
+    /// final `Z` thus checks every step at once. The last addition is
+    /// affine, so that `O` is only ever returned for `P + (-P)`. Should the
+    /// multiple pass through `O` before that, as it can when `k` exceeds
+    /// the order of `point`, the product is computed again in affine
+    /// coordinates alone.
+    pub fn mul(&self, point: &Point, k: &Int) -> Option<Point> {
+        let (x, y) = match *point {
+            Point::Infinity => return Some(Point::Infinity),
+            Point::Affine(ref x, ref y) => (x, y),
+        };
+        if *k == 0_i32 {
+            return Some(Point::Infinity);
+        }
+
+        let n = &self.n;
+        let minus_one = k - 1_usize;
+        if minus_one == 0_i32 {
+            return Some(point.clone());
+        }
+
+        let mut product = (x.clone(), y.clone(), Int::one());
+        for bit in (0..minus_one.bit_length() - 1).rev() {
+            product = self.double_jacobian(&product);
+            if minus_one.bit(bit) {
+                product = self.add_jacobian(&product, x, y);
+            }
+        }
+
+        let (x1, y1, z1) = product;
+        let z_inverse = match mod_inverse(&z1, n) {
+            Some(z_inverse) => z_inverse,
+            None if z1 == 0_i32 => return self.mul_affine(point, k),
+            None => return None,
+        };
+        let z_inverse_squared = z_inverse.square() % n;
+        let affine = Point::Affine(
+            x1 * &z_inverse_squared % n,
+            y1 * z_inverse_squared % n * z_inverse % n,
+        );
+
+        self.add(&affine, point)
+    }
+
+    /// Returns `k point` by double-and-add in affine coordinates.
+    fn mul_affine(&self, point: &Point, k: &Int) -> Option<Point> {
+        let mut product = Point::Infinity;
+
+        for bit in (0..k.bit_length()).rev() {
+            product = self.add(&product, &product)?;
+            if k.bit(bit) {
+                product = self.add(&product, point)?;
+            }
+        }
+
+        Some(product)
+    }
+
+    /// Doubles `(x, y, z)` in Jacobian coordinates.
+    fn double_jacobian(&self, point: &(Int, Int, Int)) -> (Int, Int, Int) {
+        let n = &self.n;
+        let (ref x, ref y, ref z) = *point;
+
+        let y_squared = y.square() % n;
+        let z_squared = z.square() % n;
+        let m = (x.square() * 3_usize + &self.a * z_squared.square()) % n;
+        let s = x * &y_squared * 4_usize % n;
+        let x3 = reduce(m.square() - &s * 2_usize, n);
+        let y3 = reduce(m * (s - &x3) - y_squared.square() * 8_usize, n);
+        let z3 = y * z * 2_usize % n;
+
+        (x3, y3, z3)
+    }
+
+    /// Adds the affine `(x2, y2)` to `(x1, y1, z1)` in Jacobian coordinates.
+    fn add_jacobian(&self, point: &(Int, Int, Int), x2: &Int, y2: &Int) -> (Int, Int, Int) {
+        let n = &self.n;
+        let (ref x1, ref y1, ref z1) = *point;
+
+        let z1_squared = z1.square() % n;
+        let h = reduce(x2 * &z1_squared - x1, n);
+        let r = reduce(y2 * z1_squared * z1 % n - y1, n);
+        let h_squared = h.square() % n;
+        let h_cubed = &h_squared * &h % n;
+        let v = x1 * h_squared % n;
+        let x3 = reduce(r.square() - &h_cubed - &v * 2_usize, n);
+        let y3 = reduce(r * (v - &x3) - y1 * h_cubed, n);
+        let z3 = z1 * h % n;
+
+        (x3, y3, z3)
+    }
+}
+
+/// Reduces `x` into `[0, n)`.
+fn reduce(x: Int, n: &Int) -> Int {
+    let x = x % n;
+    if x < Int::zero() {
+        x + n
+    } else {
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Curve, Point};
+    use ramp::Int;
+
+    #[test]
+    fn test_curve() {
+        // y^2 = x^3 + 2x + 3 over F_97 has 100 points, and (3, 6) has order 5.
+        let n = Int::from(97);
+        let curve = Curve::new(&Int::from(2), &Int::from(3), &n).unwrap();
+        let p = Point::Affine(Int::from(3), Int::from(6));
+        assert!(curve.contains(&p));
+        assert!(!curve.contains(&Point::Affine(Int::from(3), Int::from(7))));
+
+        let doubled = curve.add(&p, &p).unwrap();
+        assert_eq!(doubled, Point::Affine(Int::from(80), Int::from(10)));
+        assert!(curve.contains(&doubled));
+        assert_eq!(curve.mul(&p, &Int::from(2)).unwrap(), doubled);
+        assert_eq!(curve.mul(&p, &Int::from(5)).unwrap(), Point::Infinity);
+        assert_eq!(curve.mul(&p, &Int::from(6)).unwrap(), p);
+        assert_eq!(curve.add(&p, &curve.negate(&p)).unwrap(), Point::Infinity);
+        assert_eq!(curve.mul(&p, &Int::zero()).unwrap(), Point::Infinity);
+        assert_eq!(curve.mul(&p, &Int::one()).unwrap(), p);
+        let mut sum = Point::Infinity;
+        for k in 0..12 {
+            assert_eq!(curve.mul(&p, &Int::from(k)).unwrap(), sum);
+            sum = curve.add(&sum, &p).unwrap();
+        }
+        let q = Point::Affine(Int::from(0), Int::from(10));
+        assert!(curve.contains(&q));
+        let mut sum = Point::Infinity;
+        for k in 0..120 {
+            assert_eq!(curve.mul(&q, &Int::from(k)).unwrap(), sum);
+            sum = curve.add(&sum, &q).unwrap();
+        }
+
+        // y^2 = x^3 + 3 is singular modulo 3.
+        assert!(Curve::new(&Int::zero(), &Int::from(3), &Int::from(3 * 97)).is_none());
+
+        // Modulo 91 = 7 * 13, adding points that agree modulo 7 fails.
+        let composite = Curve::new(&Int::from(1), &Int::from(1), &Int::from(91)).unwrap();
+        let p = Point::Affine(Int::zero(), Int::one());
+        let q = Point::Affine(Int::from(7), Int::from(13));
+        assert!(composite.contains(&q));
+        assert!(composite.add(&p, &q).is_none());
+    }
+}
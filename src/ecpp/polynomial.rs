@@ -0,0 +1,190 @@
+//! Polynomials over the integers modulo `n`, as far as finding a root of a
+//! class polynomial needs them.
+//!
+//! Polynomials are vectors of coefficients from the constant term up, with
+//! no zero leading coefficient, so that the zero polynomial is empty. Every
+//! operation that divides returns `None` if a leading coefficient is not
+//! invertible, which can only happen if `n` is composite.
+
+use ramp::Int;
+
+use rand::Rng;
+
+use common::{mod_inverse, random_range};
+use error::Result;
+
+/// The number of random splits tried before giving up on a root.
+const MAX_SPLITS: usize = 64;
+
+/// Finds a root modulo the probable prime `n` of `polynomial`, whose
+/// coefficients may be negative or exceed `n`.
+///
+/// Returns `None` if there is no root, or `n` turned out to be composite.
+pub fn find_root<R: Rng + ?Sized>(
+    polynomial: &[Int],
+    n: &Int,
+    rngesus: &mut R,
+) -> Result<Option<Int>> {
+    let polynomial = reduce(polynomial.to_vec(), n);
+    let x = vec![Int::zero(), Int::one()];
+
+    // The roots are those of gcd(x^n - x, polynomial).
+    let mut roots = match pow_rem(&x, n, &polynomial, n) {
+        Some(power) => match gcd(sub(&power, &x, n), polynomial.clone(), n) {
+            Some(roots) => roots,
+            None => return Ok(None),
+        },
+        None => return Ok(None),
+    };
+    if roots.len() < 2 {
+        return Ok(None);
+    }
+
+    // gcd((x + delta)^((n - 1) / 2) - 1, roots) takes the roots r for which
+    // r + delta is a square, about half of them for random delta.
+    let half = (n - 1_usize) >> 1;
+    let mut splits = 0;
+    while roots.len() > 2 {
+        splits += 1;
+        if splits > MAX_SPLITS {
+            return Ok(None);
+        }
+
+        let delta = random_range(&Int::zero(), n, rngesus)?;
+        let power = match pow_rem(&[delta, Int::one()], &half, &roots, n) {
+            Some(power) => sub(&power, &[Int::one()], n),
+            None => return Ok(None),
+        };
+        let factor = match gcd(power, roots.clone(), n) {
+            Some(factor) => factor,
+            None => return Ok(None),
+        };
+
+        if factor.len() > 1 && factor.len() < roots.len() {
+            roots = factor;
+        }
+    }
+
+    // The monic x + c has the root -c.
+    Ok(Some((n - &roots[0]) % n))
+}
+
+/// Reduces the coefficients into `[0, n)`, and trims the leading zeros.
+fn reduce(mut polynomial: Vec<Int>, n: &Int) -> Vec<Int> {
+    for coefficient in &mut polynomial {
+        *coefficient = &*coefficient % n;
+        if *coefficient < Int::zero() {
+            *coefficient += n;
+        }
+    }
+    while polynomial.last() == Some(&Int::zero()) {
+        polynomial.pop();
+    }
+
+    polynomial
+}
+
+fn sub(x: &[Int], y: &[Int], n: &Int) -> Vec<Int> {
+    let mut difference = x.to_vec();
+    difference.resize(x.len().max(y.len()), Int::zero());
+    for (coefficient, subtrahend) in difference.iter_mut().zip(y) {
+        *coefficient -= subtrahend;
+    }
+
+    reduce(difference, n)
+}
+
+/// Returns `x` modulo `modulus`.
+fn rem(x: Vec<Int>, modulus: &[Int], n: &Int) -> Option<Vec<Int>> {
+    let degree = modulus.len() - 1;
+    let inverse = mod_inverse(&modulus[degree], n)?;
+    let mut remainder = x;
+
+    while remainder.len() > degree {
+        let shift = remainder.len() - 1 - degree;
+        let factor = remainder[remainder.len() - 1].clone() * &inverse % n;
+        for (i, coefficient) in modulus.iter().enumerate() {
+            remainder[shift + i] -= &factor * coefficient;
+        }
+        remainder = reduce(remainder, n);
+    }
+
+    Some(remainder)
+}
+
+/// Returns `x y` modulo `modulus`.
+fn mul_rem(x: &[Int], y: &[Int], modulus: &[Int], n: &Int) -> Option<Vec<Int>> {
+    if x.is_empty() || y.is_empty() {
+        return Some(Vec::new());
+    }
+
+    let mut product = vec![Int::zero(); x.len() + y.len() - 1];
+    for (i, a) in x.iter().enumerate() {
+        for (j, b) in y.iter().enumerate() {
+            product[i + j] += a * b;
+        }
+    }
+
+    rem(reduce(product, n), modulus, n)
+}
+
+/// Returns `base^exponent` modulo `modulus`.
+fn pow_rem(base: &[Int], exponent: &Int, modulus: &[Int], n: &Int) -> Option<Vec<Int>> {
+    let base = rem(base.to_vec(), modulus, n)?;
+    let mut power = rem(vec![Int::one()], modulus, n)?;
+
+    for bit in (0..exponent.bit_length()).rev() {
+        power = mul_rem(&power, &power, modulus, n)?;
+        if exponent.bit(bit) {
+            power = mul_rem(&power, &base, modulus, n)?;
+        }
+    }
+
+    Some(power)
+}
+
+/// Returns the monic greatest common divisor of `x` and `y`.
+fn gcd(mut x: Vec<Int>, mut y: Vec<Int>, n: &Int) -> Option<Vec<Int>> {
+    while !y.is_empty() {
+        let remainder = rem(x, &y, n)?;
+        x = y;
+        y = remainder;
+    }
+
+    if let Some(leading) = x.last().cloned() {
+        let inverse = mod_inverse(&leading, n)?;
+        for coefficient in &mut x {
+            *coefficient = &*coefficient * &inverse % n;
+        }
+    }
+
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_root;
+    use ramp::Int;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_polynomial_find_root() {
+        let n = Int::from(1_000_003);
+        // (x - 5) (x - 12) (x + 7) (x^2 + 1), where -1 is not a square.
+        let polynomial: Vec<Int> = [420, -59, 410, -58, -10, 1]
+            .iter()
+            .map(|&c: &i64| Int::from(c))
+            .collect();
+
+        let root = find_root(&polynomial, &n, &mut thread_rng())
+            .unwrap()
+            .unwrap();
+        assert!([5, 12, 1_000_003 - 7].iter().any(|&r| root == r));
+
+        // x^2 + 1 has no roots.
+        let irreducible = vec![Int::one(), Int::zero(), Int::one()];
+        assert!(find_root(&irreducible, &n, &mut thread_rng())
+            .unwrap()
+            .is_none());
+    }
+}
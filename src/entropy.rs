@@ -0,0 +1,494 @@
+//! Entropy sources, checked continuously by the health tests of NIST SP
+//! 800-90B, section 4.4.
+//!
+//! `HealthTested` wraps any random number generator and runs every byte it
+//! produces through two tests:
+//!
+//! 1. The repetition count test, which trips when the same byte comes up too
+//!    many times in a row. It catches a source that has become stuck.
+//!
+//! 2. The adaptive proportion test, which trips when one byte value takes up
+//!    too large a share of a 512-byte window. It catches a source that has
+//!    lost much of its entropy, without being stuck outright.
+//!
+//! The cutoffs are derived from the claimed min-entropy per byte, with a
+//! false positive rate of 2^-40 per byte. Before handing out anything,
+//! `HealthTested` runs 1024 bytes through both tests as its start-up test.
+//!
+//! Once a test trips, the source fails closed: every later request fails with
+//! `Error::EntropyHealth`, and `health()` reports which test tripped. A new
+//! `HealthTested` must be constructed to recover.
+//!
+//! The health tests catch a source that is broken. They cannot catch one that
+//! produces perfectly healthy-looking output that some other machine also
+//! produces, such as a virtual machine restored from the same snapshot.
+//!
+//! The sources in this module are:
+//!
+//! * `rand::rngs::OsRng`, which asks the operating system directly, through
+//!   `getrandom(2)` where it is available. `os()` wraps it, and is what the
+//!   `new()` functions throughout this crate use.
+//!
+//! * `FileSource`, which reads from a file or device, such as `/dev/urandom`.
+//!
+//! * `CallbackSource`, which calls a closure.
+
+use rand::{CryptoRng, ErrorKind, RngCore};
+
+#[cfg(feature = "std")]
+use rand::rngs::OsRng;
+
+use core::fmt;
+
+#[cfg(feature = "std")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::io::Read;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use error::{Error, Result};
+
+/// The number of bytes run through the tests before any are handed out.
+const STARTUP_BYTES: usize = 1024;
+
+/// The window of the adaptive proportion test, in bytes.
+const WINDOW: usize = 512;
+
+/// The repetition count test's cutoff for a claimed min-entropy of `1..=8`
+/// bits per byte, at a false positive rate of 2^-40: `1 + ceil(40 / bits)`.
+const REPETITION_COUNT_CUTOFFS: [usize; 8] = [41, 21, 15, 11, 9, 8, 7, 6];
+
+/// The adaptive proportion test's cutoff for a claimed min-entropy of
+/// `1..=8` bits per byte, at a false positive rate of 2^-40.
+const ADAPTIVE_PROPORTION_CUTOFFS: [usize; 8] = [336, 201, 123, 78, 51, 35, 26, 19];
+
+/// A health test of SP 800-90B.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HealthTest {
+    /// The repetition count test.
+    RepetitionCount,
+
+    /// The adaptive proportion test.
+    AdaptiveProportion,
+}
+
+impl HealthTest {
+    /// The message of the `rand::Error` reporting a failure of this test.
+    fn message(self) -> &'static str {
+        match self {
+            HealthTest::RepetitionCount => "entropy source failed the repetition count test",
+            HealthTest::AdaptiveProportion => "entropy source failed the adaptive proportion test",
+        }
+    }
+
+    /// Recognizes a `rand::Error` raised by `HealthTested`, which is how
+    /// health failures travel through `RngCore`.
+    pub(crate) fn from_rng_error(err: &::rand::Error) -> Option<HealthTest> {
+        [HealthTest::RepetitionCount, HealthTest::AdaptiveProportion]
+            .iter()
+            .cloned()
+            .find(|test| err.msg == test.message())
+    }
+
+    pub(crate) fn to_rng_error(self) -> ::rand::Error {
+        ::rand::Error::new(ErrorKind::Unavailable, self.message())
+    }
+}
+
+impl fmt::Display for HealthTest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HealthTest::RepetitionCount => write!(f, "repetition count"),
+            HealthTest::AdaptiveProportion => write!(f, "adaptive proportion"),
+        }
+    }
+}
+
+/// The state of a `HealthTested` source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Health {
+    /// Every test has passed so far.
+    Healthy,
+
+    /// The given test tripped, and the source refuses to produce anything.
+    Failed(HealthTest),
+}
+
+/// Wraps the entropy source `S`, running the SP 800-90B health tests on
+/// everything it produces.
+pub struct HealthTested<S> {
+    source: S,
+    health: Health,
+    repetition_cutoff: usize,
+    repetition_last: u8,
+    repetition_count: usize,
+    proportion_cutoff: usize,
+    proportion_first: u8,
+    proportion_count: usize,
+    proportion_seen: usize,
+}
+
+impl<S: RngCore> HealthTested<S> {
+    /// Wraps `source`, claiming that every byte it produces carries a full 8
+    /// bits of min-entropy, as a conditioned source like the operating
+    /// system's does. Runs the start-up test before returning.
+    ///
+    /// Fails with `Error::EntropyHealth` if the start-up test trips, and with
+    /// `Error::Rng` if `source` fails.
+    pub fn new(source: S) -> Result<HealthTested<S>> {
+        HealthTested::with_min_entropy(source, 8)
+    }
+
+    /// Wraps `source`, claiming `bits` bits of min-entropy per byte, between
+    /// one and eight. Lower claims raise the cutoffs, which suits raw noise
+    /// sources.
+    ///
+    /// Fails like `new()`, or with `Error::InvalidParameter` if `bits` is out
+    /// of range.
+    pub fn with_min_entropy(source: S, bits: usize) -> Result<HealthTested<S>> {
+        if !(1..=8).contains(&bits) {
+            return Err(Error::InvalidParameter {
+                name: "bits",
+                reason: format!("must be between 1 and 8, got {}", bits),
+            });
+        }
+
+        let mut tested = HealthTested {
+            source,
+            health: Health::Healthy,
+            repetition_cutoff: REPETITION_COUNT_CUTOFFS[bits - 1],
+            repetition_last: 0,
+            repetition_count: 0,
+            proportion_cutoff: ADAPTIVE_PROPORTION_CUTOFFS[bits - 1],
+            proportion_first: 0,
+            proportion_count: 0,
+            proportion_seen: 0,
+        };
+
+        let mut startup = [0u8; STARTUP_BYTES];
+        let result = tested.fill_tested(&mut startup);
+        startup.iter_mut().for_each(|byte| *byte = 0);
+
+        result.map(|()| tested)
+    }
+
+    /// Reports whether any health test has tripped.
+    pub fn health(&self) -> Health {
+        self.health
+    }
+
+    /// Fills `dest` from the source, running every byte through the tests.
+    /// `dest` is cleared if a test trips.
+    fn fill_tested(&mut self, dest: &mut [u8]) -> Result<()> {
+        if let Health::Failed(test) = self.health {
+            return Err(Error::EntropyHealth(test));
+        }
+
+        self.source.try_fill_bytes(dest).map_err(Error::from_rng)?;
+
+        for i in 0..dest.len() {
+            if let Err(test) = self.test(dest[i]) {
+                self.health = Health::Failed(test);
+                dest.iter_mut().for_each(|byte| *byte = 0);
+
+                return Err(Error::EntropyHealth(test));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn test(&mut self, byte: u8) -> ::core::result::Result<(), HealthTest> {
+        if self.repetition_count > 0 && byte == self.repetition_last {
+            self.repetition_count += 1;
+            if self.repetition_count >= self.repetition_cutoff {
+                return Err(HealthTest::RepetitionCount);
+            }
+        } else {
+            self.repetition_last = byte;
+            self.repetition_count = 1;
+        }
+
+        if self.proportion_seen == WINDOW {
+            self.proportion_seen = 0;
+        }
+        if self.proportion_seen == 0 {
+            self.proportion_first = byte;
+            self.proportion_count = 0;
+        }
+
+        self.proportion_seen += 1;
+        if byte == self.proportion_first {
+            self.proportion_count += 1;
+            if self.proportion_count >= self.proportion_cutoff {
+                return Err(HealthTest::AdaptiveProportion);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<S: RngCore> RngCore for HealthTested<S> {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest)
+            .expect("the entropy source failed")
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> ::core::result::Result<(), ::rand::Error> {
+        self.fill_tested(dest).map_err(|err| match err {
+            Error::EntropyHealth(test) => test.to_rng_error(),
+            Error::Rng(err) => err,
+            err => ::rand::Error::with_cause(ErrorKind::Unexpected, "entropy source failed", err),
+        })
+    }
+}
+
+impl<S: RngCore + CryptoRng> CryptoRng for HealthTested<S> {}
+
+impl<S> fmt::Debug for HealthTested<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HealthTested")
+            .field("health", &self.health)
+            .field("repetition_cutoff", &self.repetition_cutoff)
+            .field("proportion_cutoff", &self.proportion_cutoff)
+            .finish()
+    }
+}
+
+/// Opens the operating system's random number generator, behind the health
+/// tests.
+#[cfg(feature = "std")]
+pub fn os() -> Result<HealthTested<OsRng>> {
+    HealthTested::new(OsRng::new()?)
+}
+
+/// Reads entropy from a file or device.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct FileSource {
+    file: File,
+}
+
+#[cfg(feature = "std")]
+impl FileSource {
+    /// Opens the file at `path`.
+    ///
+    /// Fails with `Error::Rng` if it cannot be opened.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<FileSource> {
+        File::open(path)
+            .map(|file| FileSource { file })
+            .map_err(|err| {
+                Error::Rng(::rand::Error::with_cause(
+                    ErrorKind::Unavailable,
+                    "could not open the entropy source",
+                    err,
+                ))
+            })
+    }
+
+    /// Opens `/dev/urandom`.
+    pub fn urandom() -> Result<FileSource> {
+        FileSource::open("/dev/urandom")
+    }
+}
+
+#[cfg(feature = "std")]
+impl RngCore for FileSource {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest)
+            .expect("could not read from the entropy source")
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> ::core::result::Result<(), ::rand::Error> {
+        self.file.read_exact(dest).map_err(|err| {
+            ::rand::Error::with_cause(
+                ErrorKind::Unavailable,
+                "could not read from the entropy source",
+                err,
+            )
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl CryptoRng for FileSource {}
+
+/// Draws entropy by calling a closure, which fills the buffer it is given
+/// or fails.
+pub struct CallbackSource<F> {
+    callback: F,
+}
+
+impl<F> CallbackSource<F>
+where
+    F: FnMut(&mut [u8]) -> ::core::result::Result<(), ::rand::Error>,
+{
+    /// Wraps `callback`.
+    pub fn new(callback: F) -> CallbackSource<F> {
+        CallbackSource { callback }
+    }
+}
+
+impl<F> RngCore for CallbackSource<F>
+where
+    F: FnMut(&mut [u8]) -> ::core::result::Result<(), ::rand::Error>,
+{
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest)
+            .expect("the entropy callback failed")
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> ::core::result::Result<(), ::rand::Error> {
+        (self.callback)(dest)
+    }
+}
+
+impl<F> CryptoRng for CallbackSource<F> where
+    F: FnMut(&mut [u8]) -> ::core::result::Result<(), ::rand::Error>
+{
+}
+
+impl<F> fmt::Debug for CallbackSource<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("CallbackSource")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{os, CallbackSource, FileSource, Health, HealthTest, HealthTested};
+    use error::Error;
+    use prime;
+    use rand::rngs::OsRng;
+    use rand::RngCore;
+
+    fn stuck() -> CallbackSource<impl FnMut(&mut [u8]) -> Result<(), ::rand::Error>> {
+        CallbackSource::new(|dest: &mut [u8]| {
+            dest.iter_mut().for_each(|byte| *byte = 0x2a);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_entropy_os() {
+        let mut source = os().unwrap();
+        let mut bytes = [0u8; 4096];
+        source.try_fill_bytes(&mut bytes).unwrap();
+
+        assert_eq!(source.health(), Health::Healthy);
+        assert!(prime::from_rng(512, &mut source).is_ok());
+    }
+
+    #[test]
+    fn test_entropy_urandom() {
+        let mut source = HealthTested::new(FileSource::urandom().unwrap()).unwrap();
+        let mut bytes = [0u8; 64];
+        source.try_fill_bytes(&mut bytes).unwrap();
+
+        assert!(FileSource::open("/does/not/exist").is_err());
+    }
+
+    #[test]
+    fn test_entropy_stuck_source_fails_startup() {
+        match HealthTested::new(stuck()) {
+            Err(Error::EntropyHealth(HealthTest::RepetitionCount)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_entropy_fails_closed() {
+        let mut healthy = OsRng::new().unwrap();
+        let mut stuck_after = 4096;
+        let source = CallbackSource::new(move |dest: &mut [u8]| {
+            healthy.fill_bytes(dest);
+            for byte in dest.iter_mut() {
+                if stuck_after == 0 {
+                    *byte = 0;
+                } else {
+                    stuck_after -= 1;
+                }
+            }
+            Ok(())
+        });
+
+        let mut source = HealthTested::new(source).unwrap();
+        match prime::from_rng(4096, &mut source) {
+            Err(Error::EntropyHealth(HealthTest::RepetitionCount)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        assert_eq!(source.health(), Health::Failed(HealthTest::RepetitionCount));
+        assert!(source.try_fill_bytes(&mut [0u8; 16]).is_err());
+        match prime::small::gen_prime_u64(64, &mut source) {
+            Err(Error::EntropyHealth(HealthTest::RepetitionCount)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_entropy_adaptive_proportion() {
+        // Every other byte is zero, which never repeats four times running
+        // but takes up half of every window.
+        let mut healthy = OsRng::new().unwrap();
+        let mut index = 0;
+        let source = CallbackSource::new(move |dest: &mut [u8]| {
+            healthy.fill_bytes(dest);
+            for byte in dest.iter_mut() {
+                if index % 2 == 0 {
+                    *byte = 0;
+                } else {
+                    *byte |= 1;
+                }
+                index += 1;
+            }
+            Ok(())
+        });
+
+        match HealthTested::new(source) {
+            Err(Error::EntropyHealth(HealthTest::AdaptiveProportion)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        assert!(HealthTested::with_min_entropy(stuck(), 0).is_err());
+        assert!(HealthTested::with_min_entropy(stuck(), 9).is_err());
+    }
+}
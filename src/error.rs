@@ -1,21 +1,104 @@
-//! More coming soon
+//! Errors returned while generating or checking prime numbers.
+//!
+//! Every fallible function in this crate returns `error::Result`, whose
+//! `Error` can be matched on to find out exactly what went wrong. Errors that
+//! wrap a lower-level failure, such as the random number generator's, expose
+//! it through `std::error::Error::source()`.
 
+#[cfg(feature = "std")]
 use ramp::Int;
 
 use rand;
-use std::{error, fmt, result};
 
-/// Goddamn docs
-pub type Result = result::Result<Int, Error>;
+use entropy::HealthTest;
+use self_test::Check;
 
-/// More goddamn docs
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::{fmt, result};
+
+#[cfg(not(feature = "std"))]
+use core::error;
+#[cfg(feature = "std")]
+use std::error;
+
+/// The result type used throughout this crate. Defaults to `Int`, the type
+/// of the numbers that the generators return.
+#[cfg(feature = "std")]
+pub type Result<T = Int> = result::Result<T, Error>;
+
+/// The result type used throughout this crate.
+#[cfg(not(feature = "std"))]
+pub type Result<T> = result::Result<T, Error>;
+
+/// The ways in which generating or checking a prime number can fail.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
-    /// Docs for a variant, are you kidding me??
+    /// The operating system's random number generator could not be
+    /// initialized.
     OsRngInitialization(rand::Error),
 
-    /// Jesus fuck, people
-    BitLength(usize),
+    /// The random number generator failed part way through generation.
+    Rng(rand::Error),
+
+    /// The entropy source failed one of its health tests, and refuses to
+    /// produce anything more. See the `entropy` module.
+    EntropyHealth(HealthTest),
+
+    /// The requested bit length is smaller than the generator supports.
+    BitLength {
+        /// The bit length that was asked for.
+        length: usize,
+        /// The smallest bit length the generator accepts.
+        minimum: usize,
+    },
+
+    /// A parameter other than the bit length was rejected.
+    InvalidParameter {
+        /// The name of the offending parameter.
+        name: &'static str,
+        /// Why the parameter was rejected.
+        reason: String,
+    },
+
+    /// Generation gave up after making the given number of attempts.
+    BudgetExhausted {
+        /// The number of attempts made before giving up.
+        attempts: u64,
+    },
+
+    /// A value handed to the crate failed verification, e.g. a number
+    /// claimed to be prime turned out to be composite.
+    Verification {
+        /// What was being verified.
+        what: &'static str,
+    },
+
+    /// Two independent computations that must agree did not, as when a
+    /// generated prime fails the second, independent primality test. This
+    /// points to a fault or a broken build, and nothing is retried.
+    Inconsistent {
+        /// What the computations disagreed on.
+        what: &'static str,
+    },
+
+    /// One of the known-answer tests of the `self_test` module gave the wrong
+    /// answer. The build cannot be trusted to produce primes.
+    SelfTest {
+        /// The group of vectors that failed.
+        check: Check,
+        /// The index of the failing vector within the group.
+        vector: usize,
+    },
+
+    /// A value could not be parsed or decoded.
+    Parse {
+        /// What was being parsed.
+        what: &'static str,
+        /// The underlying parse failure, if there is one.
+        source: Option<Box<dyn error::Error + Send + Sync>>,
+    },
 }
 
 impl fmt::Display for Error {
@@ -24,20 +107,55 @@ impl fmt::Display for Error {
             Error::OsRngInitialization(ref err) => {
                 write!(f, "Error initializing the random number generator: {}", err)
             }
-            Error::BitLength(length) => write!(
+            Error::Rng(ref err) => write!(f, "The random number generator failed: {}", err),
+            Error::EntropyHealth(test) => {
+                write!(f, "The entropy source failed the {} health test", test)
+            }
+            Error::BitLength { length, minimum } => write!(
                 f,
-                "The given bit length is too small; must be at least 512: {}",
-                length
+                "The given bit length is too small; must be at least {}: {}",
+                minimum, length
             ),
+            Error::InvalidParameter { name, ref reason } => {
+                write!(f, "Invalid value for `{}`: {}", name, reason)
+            }
+            Error::BudgetExhausted { attempts } => {
+                write!(f, "Gave up after {} attempts", attempts)
+            }
+            Error::Verification { what } => write!(f, "Verification failed: {}", what),
+            Error::Inconsistent { what } => {
+                write!(f, "Independent computations disagreed on {}", what)
+            }
+            Error::SelfTest { check, vector } => {
+                write!(f, "The {} self-test failed on vector {}", check, vector)
+            }
+            Error::Parse { what, .. } => write!(f, "Could not parse {}", what),
         }
     }
 }
 
 impl error::Error for Error {
-    fn description(&self) -> &str {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match *self {
-            Error::OsRngInitialization(ref err) => err.description(),
-            Error::BitLength(_) => "The given bit length was less than 512",
+            // `rand::Error` only implements the error trait with `std`.
+            #[cfg(feature = "std")]
+            Error::OsRngInitialization(ref err) | Error::Rng(ref err) => Some(err),
+            Error::Parse {
+                source: Some(ref err),
+                ..
+            } => Some(&**err),
+            _ => None,
+        }
+    }
+}
+
+impl Error {
+    /// Wraps an error from a random number generator, recognizing the
+    /// health test failures of `entropy::HealthTested`.
+    pub(crate) fn from_rng(err: rand::Error) -> Error {
+        match HealthTest::from_rng_error(&err) {
+            Some(test) => Error::EntropyHealth(test),
+            None => Error::Rng(err),
         }
     }
 }
@@ -0,0 +1,155 @@
+//! Generates prime numbers of sizes that are NOT safe to use for
+//! cryptography.
+//!
+//! The generators in `prime` and `safe_prime` refuse anything shorter than
+//! 512 bits. Small primes still have plenty of legitimate uses, though: test
+//! fixtures, hash table moduli, toy protocol demonstrations, NTT moduli and
+//! so on. The functions in this module accept any bit length of at least
+//! two, and are deliberately kept apart from the rest of the crate so that
+//! reaching for them is an explicit decision.
+//!
+//! Primality is checked with the same tests as the rest of the crate. On top
+//! of that, every prime of at most `DETERMINISTIC_BIT_LENGTH` bits is
+//! confirmed with a deterministic Miller-Rabin test, making it a proven
+//! prime rather than a probable one.
+//!
+//! Note that a 2-bit request always yields 3, since candidates are forced to
+//! be odd.
+
+use ramp::Int;
+
+use rand::Rng;
+
+use std::time::Instant;
+
+use backend::BigInt;
+use common::{self, check_bit_length, gen_prime_unchecked, miller_rabin_with_bases};
+use entropy;
+use error::Result;
+use report::GenerationReport;
+
+/// The smallest bit length accepted by this module.
+pub const MIN_BIT_LENGTH: usize = 2;
+
+/// The largest bit length for which primality is proven deterministically.
+pub const DETERMINISTIC_BIT_LENGTH: usize = 81;
+
+// Testing against the first thirteen primes as bases is deterministic for
+// every candidate below 3,317,044,064,679,887,385,961,981 (Sorenson and
+// Webster, 2015), which is greater than 2^81.
+static DETERMINISTIC_BASES: [u32; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+
+/// Constructs a new prime number with a size of `bit_length` bits, where
+/// `bit_length` may be anything from two upwards.
+///
+/// This will initialize an `OsRng` instance, checked by the health tests
+/// of the `entropy` module, and call the `from_rng()` function.
+pub fn new(bit_length: usize) -> Result {
+    check_bit_length(bit_length, MIN_BIT_LENGTH)?;
+
+    let mut rngesus = entropy::os()?;
+    from_rng(bit_length, &mut rngesus)
+}
+
+/// Constructs a new prime number with the size of `bit_length` bits, sourced
+/// from an already-initialized random number generator.
+pub fn from_rng<R: Rng + ?Sized>(bit_length: usize, rngesus: &mut R) -> Result {
+    from_rng_recorded(bit_length, rngesus, &mut GenerationReport::default())
+}
+
+/// Constructs a new prime number exactly like `from_rng()`, additionally
+/// returning a `GenerationReport` describing the work that went into it.
+pub fn from_rng_with_report<R: Rng + ?Sized>(
+    bit_length: usize,
+    rngesus: &mut R,
+) -> Result<(Int, GenerationReport)> {
+    let started = Instant::now();
+    let mut report = GenerationReport::default();
+    let prime = from_rng_recorded(bit_length, rngesus, &mut report)?;
+    report.total_time = started.elapsed();
+
+    #[cfg(feature = "tracing")]
+    report.trace("hazmat_prime");
+
+    Ok((prime, report))
+}
+
+/// Checks whether `candidate` is prime. For candidates of at most
+/// `DETERMINISTIC_BIT_LENGTH` bits the answer is exact; above that, this
+/// behaves exactly like the probabilistic checks used by the rest of the
+/// crate.
+pub fn is_prime(candidate: &Int) -> bool {
+    if !common::is_prime(candidate) {
+        return false;
+    }
+
+    candidate.bit_length() as usize > DETERMINISTIC_BIT_LENGTH
+        || miller_rabin_with_bases(candidate, &DETERMINISTIC_BASES)
+}
+
+fn from_rng_recorded<R: Rng + ?Sized>(
+    bit_length: usize,
+    rngesus: &mut R,
+    report: &mut GenerationReport,
+) -> Result {
+    check_bit_length(bit_length, MIN_BIT_LENGTH)?;
+
+    loop {
+        let mut candidate = gen_prime_unchecked(bit_length, rngesus, report)?;
+
+        if bit_length > DETERMINISTIC_BIT_LENGTH
+            || miller_rabin_with_bases(&candidate, &DETERMINISTIC_BASES)
+        {
+            return Ok(candidate);
+        }
+
+        candidate.wipe();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_rng, is_prime, new, DETERMINISTIC_BIT_LENGTH};
+    use ramp::Int;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn test_hazmat_bit_length_too_small() {
+        assert!(new(1).is_err());
+    }
+
+    #[test]
+    fn test_hazmat_every_small_bit_length() {
+        let mut rngesus = OsRng::new().unwrap();
+
+        for bit_length in 2..(DETERMINISTIC_BIT_LENGTH + 20) {
+            let p = from_rng(bit_length, &mut rngesus).unwrap();
+            assert_eq!(p.bit_length() as usize, bit_length);
+            assert!(is_prime(&p));
+        }
+    }
+
+    #[test]
+    fn test_hazmat_is_prime_small_numbers() {
+        let primes: Vec<usize> = (0..200)
+            .filter(|&n| n > 1 && (2..n).all(|d| n % d != 0))
+            .collect();
+
+        for n in 0..200 {
+            assert_eq!(is_prime(&Int::from(n)), primes.contains(&n), "{}", n);
+        }
+    }
+
+    #[test]
+    fn test_hazmat_is_prime_strong_pseudoprimes() {
+        // Strong pseudoprimes to every prime base up to 7 and up to 37
+        // respectively.
+        let spsp7 = Int::from(3215031751_u64);
+        let spsp37 = Int::from_str_radix("318665857834031151167461", 10).unwrap();
+        assert!(!is_prime(&spsp7));
+        assert!(!is_prime(&spsp37));
+
+        let mersenne = Int::from((1_u64 << 61) - 1);
+        assert!(is_prime(&mersenne));
+    }
+}
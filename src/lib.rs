@@ -1,4 +1,4 @@
-#![feature(test)]
+#![cfg_attr(not(feature = "std"), no_std)]
 #![deny(
     missing_docs,
     missing_debug_implementations,
@@ -32,8 +32,109 @@
 //! The preceding steps mirror those used by GnuPG, a leading PGP implementation
 //! used by thousands of users all across the world.
 //!
+//! Each generator also has a `from_rng_with_report()` variant, which returns
+//! a `report::GenerationReport` alongside the prime, detailing how many
+//! candidates were drawn, which test rejected them and how long each stage
+//! took. Enabling the `tracing` feature emits the same data as `tracing`
+//! spans and events.
+//!
 //! The prime numbers must be AT LEAST 512-bits long. Attempting to generate a
-//! number less than 512-bits long will cause a panic.
+//! number less than 512-bits long will return an `Error::BitLength`. Smaller
+//! primes, which are unsuitable for cryptography, are available from the
+//! separate `hazmat` module.
+//!
+//! The generators return `ramp::Int`. The `backend` module runs the same
+//! tests and generator over other big integer types, such as `num-bigint`'s
+//! `BigUint`, selected with cargo features.
+//!
+//! Primes that will become private keys can instead be generated with the
+//! `constant_time` module, whose tests are hardened against timing side
+//! channels at some cost in speed. The `secret` module wraps them in a
+//! `SecretPrime`, which is wiped when dropped and redacted from `Debug`.
+//!
+//! The `rsa` module generates RSA key pairs as FIPS 186-5 appendix B.3.3
+//! describes, from `p` and `q` through to the CRT values, and checks the
+//! consistency of the result. Its primes can instead be built on auxiliary
+//! primes, as appendix A.1.6 describes, with `Method::AuxiliaryPrimes`.
+//!
+//! The `provable` module derives provable primes from a seed with the
+//! Shawe-Taylor construction, returning everything needed to derive them
+//! again. `rsa::Method::ProvablePrimes` uses it for RSA factors. Its
+//! `maurer()` returns random provable primes with a certificate, which
+//! `certificate::verify_certificate()` checks without any randomness. The
+//! `certificate` module also covers Pratt and Brillhart-Lehmer-Selfridge
+//! certificates, builds them with `certify()` for any prime whose `p - 1` is
+//! factored, and reads and writes them as text. `safe_prime::new_certified()`
+//! returns provable safe primes, whose certificate proves both `p` and
+//! `(p - 1) / 2` prime, as `safe_prime::verify_certified()` checks.
+//!
+//! The `dsa` module generates DSA domain parameters `(p, q, g)` from a seed,
+//! as FIPS 186-4 appendices A.1.1.2 and A.2.3 describe, so that anyone can
+//! derive them again with `VerifiableParameters::verify()`. It also
+//! generates Schnorr groups of any size, and `dsa::validate()` checks
+//! parameters received from elsewhere.
+//!
+//! The `strong_prime` module generates the strong primes of ANSI X9.31
+//! with Gordon's algorithm: `p - 1` has a large prime factor `r`, `p + 1` a
+//! large prime factor `s`, and `r - 1` a large prime factor `t`. They come
+//! with their auxiliary primes, which `strong_prime::is_strong_prime()`
+//! takes as a hint to check a strong prime from elsewhere.
+//!
+//! Primes from elsewhere, such as imported Diffie-Hellman groups, can be
+//! proven with `ecpp::prove()`, which runs elliptic curve primality proving
+//! on any probable prime and returns a chain of `certificate::Ecpp`
+//! certificates. The `ecpp::curve` module holds its elliptic curve
+//! arithmetic. Proofs published by Primo, in its certificate format, are
+//! parsed and checked by the `primo` module.
+//!
+//! Where primes must come from an approved deterministic random bit
+//! generator, the `drbg` module provides an SP 800-90A HMAC_DRBG that can be
+//! passed to any `from_rng()` function.
+//!
+//! The `new()` functions draw from the operating system's generator through
+//! the `entropy` module, which runs the continuous health tests of SP
+//! 800-90B and refuses to generate once the source looks broken. It can also
+//! wrap `/dev/urandom`, any other file, or a callback.
+//!
+//! For long-lived keys, `prime::from_rng_verified()` and
+//! `backend::from_rng_verified()` confirm each prime with an independent
+//! Baillie-PSW test on plain reference arithmetic, and fail with
+//! `Error::Inconsistent` instead of retrying if the two tests disagree.
+//!
+//! The `statistics` module measures how the generated primes are
+//! distributed, with chi-square tests of their residues modulo small primes,
+//! the frequencies of their top and bottom bits, and a comparison against the
+//! ideal distribution of random primes of the same size.
+//!
+//! `self_test::self_test()` checks the primality tests, modular
+//! exponentiation and seeded generation against known-answer vectors, and
+//! `self_test::run()` does the same for any backend. Enabling the `self-test`
+//! feature runs them automatically before the first prime is generated, and
+//! fails every generator with `Error::SelfTest` if they do not pass.
+//!
+//! The `adversarial` module goes the other way, and constructs composites
+//! built to fool probabilistic tests: Carmichael numbers, strong
+//! pseudoprimes to chosen bases, and Lucas pseudoprimes. `to_json()` exports
+//! them as Wycheproof-style test vectors for testing other libraries.
+//!
+//! ## `no_std`
+//!
+//! The `std` feature is enabled by default. Without it the crate builds with
+//! `no_std` and `alloc` only, for targets such as enclaves and bare-metal
+//! devices, and keeps the part of it that does not depend on `ramp::Int` or
+//! on the operating system:
+//!
+//! - the `backend` module, which tests and generates primes of the
+//!   `num-bigint` and `crypto-bigint` types from a caller-supplied random
+//!   number generator, via `backend::from_rng()` and
+//!   `backend::is_prime_with_rng()`,
+//! - `drbg` and `entropy`, minus the sources that need an operating system,
+//! - `self_test::run()`,
+//! - `error` and `report`, where every duration in a report is zero as
+//!   there is no clock to read.
+//!
+//! The modules returning `ramp::Int`, along with everything that seeds itself
+//! from `OsRng` or the thread-local generator, require `std`.
 //!
 //! ## Example
 //!
@@ -44,56 +145,74 @@
 //!
 //! fn main() {
 //!     // Generate 2, 2048-bit primes
-//!     let p = prime::new(2048);
-//!     let q = prime::new(2048);
+//!     let p = prime::new(2048).unwrap();
+//!     let q = prime::new(2048).unwrap();
 //!
 //!     let n = p * q;
 //!     println!("{}", n); // Some 4096-bit composite number
 //! }
 //! ```
 
+#[macro_use]
+extern crate alloc;
+// `no_std` puts `core` in the crate root already.
+#[cfg(feature = "std")]
+extern crate core;
 #[allow(unused_imports)]
 #[macro_use]
 extern crate custom_derive;
+extern crate hmac;
 #[allow(unused_imports)]
 #[macro_use]
 extern crate newtype_derive;
+#[cfg(feature = "crypto-bigint")]
+extern crate crypto_bigint;
+#[cfg(feature = "num-bigint")]
+extern crate num_bigint;
+#[cfg(feature = "std")]
 extern crate ramp;
 extern crate rand;
-extern crate test;
+#[cfg(feature = "rug")]
+extern crate rug;
+extern crate sha2;
+#[cfg(feature = "tracing")]
+#[macro_use]
+extern crate tracing;
+extern crate zeroize;
 
+#[cfg(feature = "std")]
+pub mod adversarial;
+pub mod backend;
+#[cfg(feature = "std")]
+pub mod certificate;
 mod common;
+#[cfg(feature = "std")]
+pub mod constant_time;
+pub mod drbg;
+#[cfg(feature = "std")]
+pub mod dsa;
+#[cfg(feature = "std")]
+pub mod ecpp;
+pub mod entropy;
 pub mod error;
+#[cfg(feature = "std")]
+pub mod hazmat;
+#[cfg(feature = "std")]
 pub mod prime;
+#[cfg(feature = "std")]
+pub mod primo;
+#[cfg(feature = "std")]
+pub mod provable;
+pub mod report;
+#[cfg(feature = "std")]
+pub mod rsa;
+#[cfg(feature = "std")]
 pub mod safe_prime;
+#[cfg(feature = "std")]
+pub mod secret;
+pub mod self_test;
+#[cfg(feature = "std")]
+pub mod statistics;
+#[cfg(feature = "std")]
+pub mod strong_prime;
 
-#[cfg(test)]
-mod tests {
-    use super::{prime, safe_prime};
-    use rand::rngs::OsRng;
-    use test::Bencher;
-
-    #[bench]
-    fn bench_generate_512_bit_prime(b: &mut Bencher) {
-        let mut rngesus = OsRng::new().unwrap();
-        b.iter(|| prime::from_rng(512, &mut rngesus));
-    }
-
-    #[bench]
-    fn bench_generate_1024_bit_prime(b: &mut Bencher) {
-        let mut rngesus = OsRng::new().unwrap();
-        b.iter(|| prime::from_rng(1024, &mut rngesus));
-    }
-
-    #[bench]
-    fn bench_generate_2048_bit_prime(b: &mut Bencher) {
-        let mut rngesus = OsRng::new().unwrap();
-        b.iter(|| prime::from_rng(2048, &mut rngesus));
-    }
-
-    #[bench]
-    fn bench_generate_512_bit_safe_prime(b: &mut Bencher) {
-        let mut rngesus = OsRng::new().unwrap();
-        b.iter(|| safe_prime::from_rng(512, &mut rngesus));
-    }
-}
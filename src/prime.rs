@@ -1,28 +1,55 @@
 //! Generates cryptographically secure prime numbers.
 
-use rand::rngs::OsRng;
+pub mod small;
 
 pub use common::gen_prime as from_rng;
-use error::{Error, Result};
+pub use common::gen_prime_with_report as from_rng_with_report;
+use common::{self, check_bit_length, MIN_BIT_LENGTH};
+use entropy;
+use error::Result;
+use rand::Rng;
+use report::GenerationReport;
 
 /// Constructs a new prime number with a size of `bit_length` bits.
 ///
-/// This will initialize an `OsRng` instance and call the
-/// `from_rng()` function.
+/// This will initialize an `OsRng` instance, checked by the health tests
+/// of the `entropy` module, and call the `from_rng()` function.
 ///
 /// Note: the `bit_length` MUST be at least 512-bits.
 pub fn new(bit_length: usize) -> Result {
-    if bit_length < 512 {
-        Err(Error::BitLength(bit_length))
-    } else {
-        let mut rngesus = OsRng::new()?;
-        Ok(from_rng(bit_length, &mut rngesus)?)
-    }
+    check_bit_length(bit_length, MIN_BIT_LENGTH)?;
+
+    let mut rngesus = entropy::os()?;
+    from_rng(bit_length, &mut rngesus)
+}
+
+/// Constructs a new prime number like `new()`, confirmed by a second,
+/// independent test as described for `from_rng_verified()`.
+pub fn new_verified(bit_length: usize) -> Result {
+    check_bit_length(bit_length, MIN_BIT_LENGTH)?;
+
+    let mut rngesus = entropy::os()?;
+    from_rng_verified(bit_length, &mut rngesus)
+}
+
+/// Constructs a new prime number like `from_rng()`, then confirms it with
+/// the Baillie-PSW test, run on plain arithmetic that shares nothing with
+/// the Montgomery multiplication and backend that found it. This guards
+/// long-lived keys against fault injection and arithmetic bugs.
+///
+/// Fails with `Error::Inconsistent`, rather than trying another candidate,
+/// if the two tests ever disagree.
+pub fn from_rng_verified<R: Rng + ?Sized>(bit_length: usize, rngesus: &mut R) -> Result {
+    check_bit_length(bit_length, MIN_BIT_LENGTH)?;
+    common::gen_prime_verified(bit_length, rngesus, &mut GenerationReport::default())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::new;
+    use super::{from_rng_verified, from_rng_with_report, new, new_verified};
+    use backend;
+    use error::Error;
+    use rand::rngs::OsRng;
 
     #[test]
     fn test_prime_bit_length_too_small() {
@@ -36,6 +63,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_prime_bit_length_error() {
+        match new(256) {
+            Err(Error::BitLength { length, minimum }) => {
+                assert_eq!(length, 256);
+                assert_eq!(minimum, 512);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
     #[test]
     fn test_prime() {
         let p = new(512);
@@ -47,4 +85,38 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_prime_exact_bit_length() {
+        let p = new(512).unwrap();
+        assert_eq!(p.bit_length(), 512);
+    }
+
+    #[test]
+    fn test_prime_with_report() {
+        let mut rngesus = OsRng::new().unwrap();
+        let (_, report) = from_rng_with_report(512, &mut rngesus).unwrap();
+
+        assert!(report.candidates > 0);
+        assert!(report.rng_bytes >= report.candidates * 64);
+        assert!(report.miller_rabin_rounds >= 15);
+        assert_eq!(
+            report.candidates - 1,
+            report.trial_division_rejections
+                + report.fermat_rejections
+                + report.miller_rabin_rejections
+        );
+    }
+
+    #[test]
+    fn test_prime_verified() {
+        let p = new_verified(512).unwrap();
+        assert_eq!(p.bit_length(), 512);
+        assert!(backend::is_prime_bpsw(&p));
+
+        let mut rngesus = OsRng::new().unwrap();
+        assert!(from_rng_verified(256, &mut rngesus).is_err());
+        let q = from_rng_verified(1024, &mut rngesus).unwrap();
+        assert!(backend::is_prime(&q));
+    }
 }
@@ -0,0 +1,567 @@
+//! Primality testing and generation for numbers that fit in a machine word.
+//!
+//! Going through `ramp::Int` is wasteful when the numbers involved fit in a
+//! `u32`, `u64` or `u128`, as is the case for hash table moduli, Rabin-Karp
+//! fingerprints or NTT parameters. Every check in this module is
+//! deterministic:
+//!
+//! * `u32` candidates are checked with Miller-Rabin to the bases 2, 7 and
+//!   61, which is known to be sufficient below 4,759,123,141.
+//! * `u64` candidates are checked with Miller-Rabin to the seven bases found
+//!   by Jim Sinclair, which are sufficient for every 64-bit number.
+//! * `u128` candidates are checked with the Baillie-PSW test, which has no
+//!   known counterexample and has been verified to have none below 2^64.
+//!
+//! Like the `hazmat` module, these numbers are far too small to be used as
+//! cryptographic secrets.
+
+use rand::Rng;
+
+use core::mem;
+
+use error::{Error, Result};
+
+static SMALL_PRIMES: [u32; 25] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+];
+
+static U32_BASES: [u64; 3] = [2, 7, 61];
+
+static U64_BASES: [u64; 7] = [2, 325, 9375, 28178, 450775, 9780504, 1795265022];
+
+/// Checks whether `n` is prime.
+pub fn is_prime_u32(n: u32) -> bool {
+    match trial_division(u64::from(n)) {
+        Some(result) => result,
+        None => U32_BASES
+            .iter()
+            .all(|&basis| is_strong_probable_prime_u64(u64::from(n), basis)),
+    }
+}
+
+/// Checks whether `n` is prime.
+pub fn is_prime_u64(n: u64) -> bool {
+    match trial_division(n) {
+        Some(result) => result,
+        None => U64_BASES
+            .iter()
+            .all(|&basis| is_strong_probable_prime_u64(n, basis)),
+    }
+}
+
+/// Checks whether `n` is prime.
+pub fn is_prime_u128(n: u128) -> bool {
+    if n <= u128::from(u64::MAX) {
+        return is_prime_u64(n as u64);
+    }
+
+    if SMALL_PRIMES.iter().any(|&p| n.is_multiple_of(u128::from(p))) {
+        return false;
+    }
+
+    let montgomery = Montgomery::new(n);
+    montgomery.is_strong_probable_prime(2) && montgomery.is_strong_lucas_probable_prime()
+}
+
+macro_rules! small_prime_functions {
+    ($t:ty, $bits:expr, $is_prime:ident, $next_prime:ident, $gen_prime:ident, $random:ident) => {
+        /// Returns the smallest prime greater than `n`, or `None` if there
+        /// is no such prime of this width.
+        pub fn $next_prime(n: $t) -> Option<$t> {
+            if n < 2 {
+                return Some(2);
+            }
+
+            let mut candidate = if n.is_multiple_of(2) { n + 1 } else { n.checked_add(2)? };
+            while !$is_prime(candidate) {
+                candidate = candidate.checked_add(2)?;
+            }
+
+            Some(candidate)
+        }
+
+        /// Constructs a new prime number of exactly `bit_length` bits,
+        /// sourced from an already-initialized random number generator.
+        pub fn $gen_prime<R: Rng + ?Sized>(bit_length: u32, rngesus: &mut R) -> Result<$t> {
+            if !(2..=$bits).contains(&bit_length) {
+                return Err(Error::InvalidParameter {
+                    name: "bit_length",
+                    reason: format!("must be between 2 and {}, got {}", $bits, bit_length),
+                });
+            }
+
+            let mask = <$t>::MAX >> ($bits - bit_length);
+            let top_bits = 3 << (bit_length - 2);
+
+            loop {
+                let candidate = ($random(rngesus)? & mask) | top_bits | 1;
+
+                if $is_prime(candidate) {
+                    return Ok(candidate);
+                }
+            }
+        }
+    };
+}
+
+small_prime_functions!(u32, 32, is_prime_u32, next_prime_u32, gen_prime_u32, random_u32);
+small_prime_functions!(u64, 64, is_prime_u64, next_prime_u64, gen_prime_u64, random_u64);
+small_prime_functions!(u128, 128, is_prime_u128, next_prime_u128, gen_prime_u128, random_u128);
+
+fn random_u32<R: Rng + ?Sized>(rngesus: &mut R) -> Result<u32> {
+    random_u64(rngesus).map(|n| n as u32)
+}
+
+fn random_u64<R: Rng + ?Sized>(rngesus: &mut R) -> Result<u64> {
+    let mut bytes = [0u8; 8];
+    rngesus.try_fill_bytes(&mut bytes).map_err(Error::from_rng)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn random_u128<R: Rng + ?Sized>(rngesus: &mut R) -> Result<u128> {
+    let mut bytes = [0u8; 16];
+    rngesus.try_fill_bytes(&mut bytes).map_err(Error::from_rng)?;
+    Ok(u128::from_le_bytes(bytes))
+}
+
+/// Settles primality for numbers that are small, or that have a small
+/// factor, returning `None` when Miller-Rabin needs to decide.
+fn trial_division(n: u64) -> Option<bool> {
+    for &p in SMALL_PRIMES.iter() {
+        let p = u64::from(p);
+        if n.is_multiple_of(p) {
+            return Some(n == p);
+        }
+    }
+
+    if n < 97 * 97 {
+        Some(n > 1)
+    } else {
+        None
+    }
+}
+
+fn mul_mod_u64(a: u64, b: u64, modulus: u64) -> u64 {
+    (u128::from(a) * u128::from(b) % u128::from(modulus)) as u64
+}
+
+fn is_strong_probable_prime_u64(n: u64, basis: u64) -> bool {
+    let basis = basis % n;
+    if basis == 0 {
+        return true;
+    }
+
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+
+    let mut x = 1;
+    let mut power = basis;
+    let mut exponent = d;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            x = mul_mod_u64(x, power, n);
+        }
+        power = mul_mod_u64(power, power, n);
+        exponent >>= 1;
+    }
+
+    if x == 1 || x == n - 1 {
+        return true;
+    }
+
+    for _ in 1..s {
+        x = mul_mod_u64(x, x, n);
+        if x == n - 1 {
+            return true;
+        }
+    }
+
+    false
+}
+
+fn is_square(n: u128) -> bool {
+    let mut root = (n as f64).sqrt() as u128;
+    while root.checked_mul(root).is_none_or(|square| square > n) {
+        root -= 1;
+    }
+    while (root + 1).checked_mul(root + 1).is_some_and(|square| square <= n) {
+        root += 1;
+    }
+
+    root * root == n
+}
+
+/// Montgomery arithmetic modulo an odd 128-bit number, using two 64-bit
+/// limbs and `R = 2^128`. Every value handled by these methods is kept in
+/// Montgomery form.
+struct Montgomery {
+    modulus: u128,
+    inverse: u64,
+    one: u128,
+    r_squared: u128,
+}
+
+impl Montgomery {
+    fn new(modulus: u128) -> Montgomery {
+        // Newton's iteration doubles the number of correct low bits each
+        // time, and an odd number is its own inverse modulo 8.
+        let low = modulus as u64;
+        let mut inverse = low;
+        for _ in 0..5 {
+            inverse = inverse.wrapping_mul(2u64.wrapping_sub(low.wrapping_mul(inverse)));
+        }
+
+        let one = 0u128.wrapping_sub(modulus) % modulus;
+        let mut r_squared = one;
+        for _ in 0..128 {
+            r_squared = add_mod(r_squared, r_squared, modulus);
+        }
+
+        Montgomery {
+            modulus,
+            inverse: inverse.wrapping_neg(),
+            one,
+            r_squared,
+        }
+    }
+
+    fn to_montgomery(&self, n: u128) -> u128 {
+        self.mul(n % self.modulus, self.r_squared)
+    }
+
+    fn add(&self, a: u128, b: u128) -> u128 {
+        add_mod(a, b, self.modulus)
+    }
+
+    fn sub(&self, a: u128, b: u128) -> u128 {
+        if a >= b {
+            a - b
+        } else {
+            self.modulus - (b - a)
+        }
+    }
+
+    fn half(&self, a: u128) -> u128 {
+        if a & 1 == 0 {
+            a >> 1
+        } else {
+            (a >> 1) + (self.modulus >> 1) + 1
+        }
+    }
+
+    fn mul(&self, a: u128, b: u128) -> u128 {
+        let a = [a as u64, (a >> 64) as u64];
+        let b = [b as u64, (b >> 64) as u64];
+        let n = [self.modulus as u64, (self.modulus >> 64) as u64];
+        let mut t = [0u64; 4];
+
+        for &b_i in &b {
+            let mut carry = 0u128;
+            for j in 0..2 {
+                let sum = u128::from(t[j]) + u128::from(a[j]) * u128::from(b_i) + carry;
+                t[j] = sum as u64;
+                carry = sum >> 64;
+            }
+            let sum = u128::from(t[2]) + carry;
+            t[2] = sum as u64;
+            t[3] = (sum >> 64) as u64;
+
+            let m = t[0].wrapping_mul(self.inverse);
+            let mut carry = (u128::from(t[0]) + u128::from(m) * u128::from(n[0])) >> 64;
+            let sum = u128::from(t[1]) + u128::from(m) * u128::from(n[1]) + carry;
+            t[0] = sum as u64;
+            carry = sum >> 64;
+            let sum = u128::from(t[2]) + carry;
+            t[1] = sum as u64;
+            t[2] = t[3] + (sum >> 64) as u64;
+        }
+
+        let result = u128::from(t[0]) | u128::from(t[1]) << 64;
+        if t[2] != 0 || result >= self.modulus {
+            result.wrapping_sub(self.modulus)
+        } else {
+            result
+        }
+    }
+
+    fn pow(&self, base: u128, mut exponent: u128) -> u128 {
+        let mut result = self.one;
+        let mut power = base;
+
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = self.mul(result, power);
+            }
+            power = self.mul(power, power);
+            exponent >>= 1;
+        }
+
+        result
+    }
+
+    fn is_strong_probable_prime(&self, basis: u128) -> bool {
+        let minus_one = self.modulus - self.one;
+        let s = (self.modulus - 1).trailing_zeros();
+        let d = (self.modulus - 1) >> s;
+
+        let mut x = self.pow(self.to_montgomery(basis), d);
+        if x == self.one || x == minus_one {
+            return true;
+        }
+
+        for _ in 1..s {
+            x = self.mul(x, x);
+            if x == minus_one {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Runs the strong Lucas probable prime test with the parameters chosen
+    /// by Selfridge's method A: `D` is the first of 5, -7, 9, -11, ... with
+    /// Jacobi symbol `(D / n) = -1`, `P = 1` and `Q = (1 - D) / 4`.
+    fn is_strong_lucas_probable_prime(&self) -> bool {
+        let n = self.modulus;
+
+        // No suitable D exists for perfect squares.
+        if is_square(n) {
+            return false;
+        }
+
+        let mut magnitude = 5u128;
+        let mut negative = false;
+        loop {
+            let d = if negative { n - magnitude % n } else { magnitude % n };
+            match jacobi(d, n) {
+                -1 => break,
+                0 if !magnitude.is_multiple_of(n) => return false,
+                _ => {}
+            }
+            magnitude += 2;
+            negative = !negative;
+        }
+
+        let d = if negative {
+            self.sub(0, self.to_montgomery(magnitude))
+        } else {
+            self.to_montgomery(magnitude)
+        };
+        // Q = (1 - D) / 4, which is an integer since D = 1 (mod 4).
+        let q = if negative {
+            self.to_montgomery((1 + magnitude) / 4)
+        } else {
+            self.sub(0, self.to_montgomery((magnitude - 1) / 4))
+        };
+
+        // Write n + 1 = d * 2^s. Since n is odd and below 2^128, n + 1 only
+        // overflows for n = 2^128 - 1, which is divisible by 3.
+        let n_plus_one = n + 1;
+        let s = n_plus_one.trailing_zeros();
+        let exponent = n_plus_one >> s;
+
+        // Compute U_d, V_d and Q^d with a left-to-right binary ladder.
+        let mut u = self.one;
+        let mut v = self.one;
+        let mut q_k = q;
+        let bits = 128 - exponent.leading_zeros();
+        for i in (0..bits - 1).rev() {
+            u = self.mul(u, v);
+            v = self.sub(self.mul(v, v), self.add(q_k, q_k));
+            q_k = self.mul(q_k, q_k);
+
+            if (exponent >> i) & 1 == 1 {
+                let next_u = self.half(self.add(u, v));
+                let next_v = self.half(self.add(self.mul(d, u), v));
+                u = next_u;
+                v = next_v;
+                q_k = self.mul(q_k, q);
+            }
+        }
+
+        if u == 0 || v == 0 {
+            return true;
+        }
+
+        for _ in 1..s {
+            v = self.sub(self.mul(v, v), self.add(q_k, q_k));
+            if v == 0 {
+                return true;
+            }
+            q_k = self.mul(q_k, q_k);
+        }
+
+        false
+    }
+}
+
+fn add_mod(a: u128, b: u128, modulus: u128) -> u128 {
+    if a >= modulus - b {
+        a - (modulus - b)
+    } else {
+        a + b
+    }
+}
+
+/// Computes the Jacobi symbol `(a / n)` for an odd `n`, with the binary
+/// algorithm: factors of two are pulled out of `a`, and the larger of the
+/// two odd numbers is replaced by their difference.
+fn jacobi(a: u128, n: u128) -> i32 {
+    let (mut a, mut n) = (a % n, n);
+    let mut symbol = 1;
+
+    while a != 0 {
+        let zeros = a.trailing_zeros();
+        a >>= zeros;
+        if zeros % 2 == 1 && (n % 8 == 3 || n % 8 == 5) {
+            symbol = -symbol;
+        }
+
+        // Quadratic reciprocity, as both are odd.
+        if a < n {
+            mem::swap(&mut a, &mut n);
+            if a % 4 == 3 && n % 4 == 3 {
+                symbol = -symbol;
+            }
+        }
+        a -= n;
+    }
+
+    if n == 1 {
+        symbol
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        gen_prime_u128, gen_prime_u32, gen_prime_u64, is_prime_u128, is_prime_u32, is_prime_u64,
+        jacobi, next_prime_u128, next_prime_u32, next_prime_u64, Montgomery,
+    };
+    use common;
+    use ramp::Int;
+    use rand::rngs::OsRng;
+    use rand::Rng;
+
+    fn is_prime_naive(n: u64) -> bool {
+        n > 1 && (2..).take_while(|d| d * d <= n).all(|d| !n.is_multiple_of(d))
+    }
+
+    #[test]
+    fn test_small_matches_trial_division() {
+        for n in 0..100_000u32 {
+            let expected = is_prime_naive(u64::from(n));
+            assert_eq!(is_prime_u32(n), expected, "{}", n);
+            assert_eq!(is_prime_u64(u64::from(n)), expected, "{}", n);
+            assert_eq!(is_prime_u128(u128::from(n)), expected, "{}", n);
+        }
+    }
+
+    #[test]
+    fn test_small_pseudoprimes() {
+        // Carmichael numbers and strong pseudoprimes to several bases.
+        for &n in &[561u64, 41041, 825265, 3215031751, 2152302898747, 3474749660383] {
+            assert!(!is_prime_u64(n), "{}", n);
+        }
+        assert!(!is_prime_u32(3215031751));
+        assert!(!is_prime_u64(3825123056546413051));
+        assert!(!is_prime_u128(318665857834031151167461));
+        assert!(!is_prime_u128(3317044064679887385961981));
+    }
+
+    #[test]
+    fn test_small_lucas_matches_miller_rabin() {
+        let mut n = (1u64 << 63) + 1;
+        for _ in 0..2_000 {
+            let montgomery = Montgomery::new(u128::from(n));
+            let bpsw = montgomery.is_strong_probable_prime(2)
+                && montgomery.is_strong_lucas_probable_prime();
+            assert_eq!(bpsw, is_prime_u64(n), "{}", n);
+            n += 2;
+        }
+    }
+
+    #[test]
+    fn test_small_strong_lucas_pseudoprimes() {
+        // The strong Lucas pseudoprimes for Selfridge's parameters pass the
+        // Lucas half of Baillie-PSW on their own, but not the base 2 half.
+        for &n in &[5459u128, 5777, 10877, 16109, 18971, 22499, 24569, 25199, 40309, 58519] {
+            let montgomery = Montgomery::new(n);
+            assert!(montgomery.is_strong_lucas_probable_prime(), "{}", n);
+            assert!(!montgomery.is_strong_probable_prime(2), "{}", n);
+        }
+    }
+
+    #[test]
+    fn test_small_known_u128_primes() {
+        assert!(is_prime_u128((1 << 127) - 1));
+        assert!(is_prime_u128((1 << 89) - 1));
+        assert!(is_prime_u128(u128::MAX - 158));
+        assert!(!is_prime_u128(((1 << 61) - 1) * ((1 << 67) - 1)));
+        assert!(!is_prime_u128(((1 << 61) - 1) * ((1 << 61) - 1)));
+        assert!(!is_prime_u128(u128::MAX));
+    }
+
+    #[test]
+    fn test_small_next_prime() {
+        assert_eq!(next_prime_u32(0), Some(2));
+        assert_eq!(next_prime_u32(2), Some(3));
+        assert_eq!(next_prime_u32(13), Some(17));
+        assert_eq!(next_prime_u32(u32::MAX - 5), Some(4294967291));
+        assert_eq!(next_prime_u32(u32::MAX - 4), None);
+        assert_eq!(next_prime_u64(u64::from(u32::MAX)), Some(4294967311));
+        assert_eq!(next_prime_u64(u64::MAX - 58), None);
+        assert_eq!(
+            next_prime_u128(u128::from(u64::MAX)),
+            Some(18446744073709551629)
+        );
+    }
+
+    #[test]
+    fn test_small_jacobi() {
+        let mut rngesus = OsRng::new().unwrap();
+        let expected = |a: u128, n: u128| common::jacobi(&Int::from(a), &Int::from(n));
+
+        for n in (1..200).step_by(2) {
+            for a in 0..2 * n {
+                assert_eq!(jacobi(a, n), expected(a, n), "({} / {})", a, n);
+            }
+        }
+
+        for _ in 0..1000 {
+            let a: u128 = rngesus.gen();
+            let n = rngesus.gen::<u128>() | 1;
+            assert_eq!(jacobi(a, n), expected(a, n), "({} / {})", a, n);
+        }
+        assert_eq!(jacobi(u128::MAX, u128::MAX), 0);
+    }
+
+    #[test]
+    fn test_small_gen_prime() {
+        let mut rngesus = OsRng::new().unwrap();
+
+        for bit_length in 2..=32 {
+            let p = gen_prime_u32(bit_length, &mut rngesus).unwrap();
+            assert_eq!(32 - p.leading_zeros(), bit_length);
+            assert!(is_prime_naive(u64::from(p)));
+        }
+
+        for bit_length in 2..=64 {
+            let p = gen_prime_u64(bit_length, &mut rngesus).unwrap();
+            assert_eq!(64 - p.leading_zeros(), bit_length);
+            assert!(is_prime_u64(p));
+        }
+
+        for bit_length in 2..=128 {
+            let p = gen_prime_u128(bit_length, &mut rngesus).unwrap();
+            assert_eq!(128 - p.leading_zeros(), bit_length);
+            assert!(is_prime_u128(p));
+        }
+
+        assert!(gen_prime_u32(1, &mut rngesus).is_err());
+        assert!(gen_prime_u32(33, &mut rngesus).is_err());
+    }
+}
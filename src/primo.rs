@@ -0,0 +1,588 @@
+//! Primality certificates in the format of Primo, Marcel Martin's ECPP
+//! program, which is how most proofs for large primes are published.
+//!
+//! A Primo certificate is an INI-style file. It proves the number `N` of
+//! its `[Candidate]` section prime in a chain of tests, numbered from
+//! `[1]` to the `TestCount` of its `[PRIMO - Primality Certificate]`
+//! section. Each test proves its `N` prime once a smaller number `R` is,
+//! and `R` is the `N` of the next test. The `R` of the last test is small
+//! enough to check directly. Numbers are written in decimal, or in
+//! hexadecimal after a `$`.
+//!
+//! Only format 4, the one that current versions of Primo write, is read.
+//! Older formats lay the tests out in other fields, and there is no sample
+//! of them to test against, so the parser rejects them with `Error::Parse`
+//! rather than guessing at their meaning. Format 4 has four types of tests:
+//!
+//! * Type 1 gives `S` and `B` for an `N - 1` test: `N - 1 = S R`, with
+//!   `B^(N - 1) = 1` and `gcd(B^S - 1, N) = 1` modulo `N`, so that every
+//!   prime factor of `N` is `1` modulo `R`.
+//!
+//! * Type 2 gives `S` and `Q` for an `N + 1` test: `N + 1 = S R`, and the
+//!   Lucas sequence `U` with parameters `P` and `Q` has `U(N + 1) = 0` and
+//!   `gcd(U(S), N) = 1` modulo `N`, where `P^2 - 4 Q` is not a square. `P`
+//!   is `1` unless the test gives it too.
+//!
+//! * Types 3 and 4 give `S`, `W` and a parameter `T` for an ECPP test on
+//!   a curve with `N + 1 - W = S R` points. Type 3 gives the curve
+//!   `y^2 = x^3 + A x + B`, and type 4 only its `j`-invariant `J`, for the
+//!   curve with `A = 3 J (1728 - J)` and `B = 2 J (1728 - J)^2`. Writing
+//!   `L = T^3 + A T + B`, the point `P = (T L, L^2)` lies on the curve
+//!   `y^2 = x^3 + A L^2 x + B L^3`, and `S P != O` but `R S P = O` modulo
+//!   `N`.
+//!
+//! `verify()` checks every test, along with the size of `R` that each one
+//! needs, and the last `R` with a deterministic test. Everything else in
+//! the file, such as the running times and Primo's own signature, is
+//! ignored.
+
+use ramp::Int;
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use certificate::min_ecpp_factor;
+use common::jacobi;
+use ecpp::curve::{Curve, Point};
+use error::{Error, Result};
+use prime::small::is_prime_u64;
+
+/// The section that starts every Primo certificate.
+const HEADER: &str = "PRIMO - Primality Certificate";
+
+/// The only format that is read.
+const FORMAT: &str = "4";
+
+/// A Primo certificate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Certificate {
+    /// The candidate that the certificate proves prime.
+    pub n: Int,
+    /// The tests, from the candidate down.
+    pub tests: Vec<Test>,
+}
+
+/// One test of a Primo certificate, with the numbers it gives.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Test {
+    /// A type 1, `N - 1` test.
+    NMinusOne {
+        /// The cofactor `S` of `R` in `N - 1`.
+        s: Int,
+        /// The base `B`.
+        b: Int,
+    },
+    /// A type 2, `N + 1` test.
+    NPlusOne {
+        /// The cofactor `S` of `R` in `N + 1`.
+        s: Int,
+        /// The Lucas parameter `P`.
+        p: Int,
+        /// The Lucas parameter `Q`.
+        q: Int,
+    },
+    /// A type 3 ECPP test, on a curve given by its coefficients.
+    Curve {
+        /// The cofactor `S` of `R` in the number of points.
+        s: Int,
+        /// The number of points is `N + 1 - W`.
+        w: Int,
+        /// The coefficient `A`.
+        a: Int,
+        /// The coefficient `B`.
+        b: Int,
+        /// The parameter `T` of the point.
+        t: Int,
+    },
+    /// A type 4 ECPP test, on a curve given by its `j`-invariant.
+    JInvariant {
+        /// The cofactor `S` of `R` in the number of points.
+        s: Int,
+        /// The number of points is `N + 1 - W`.
+        w: Int,
+        /// The `j`-invariant `J`.
+        j: Int,
+        /// The parameter `T` of the point.
+        t: Int,
+    },
+}
+
+impl FromStr for Certificate {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Certificate> {
+        let sections = parse_sections(text)?;
+        let header = sections.get(HEADER).ok_or_else(parse_error)?;
+        if header.get("Format").map(String::as_str) != Some(FORMAT) {
+            return Err(parse_error());
+        }
+        let count: usize = header
+            .get("TestCount")
+            .and_then(|count| count.parse().ok())
+            .ok_or_else(parse_error)?;
+
+        let n = parse_field(sections.get("Candidate").ok_or_else(parse_error)?, "N")?;
+        let tests = (1..=count)
+            .map(|i| {
+                let section = sections.get(&i.to_string()).ok_or_else(parse_error)?;
+                parse_test(section)
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Certificate { n, tests })
+    }
+}
+
+/// Checks that `certificate` proves its candidate prime.
+///
+/// Fails with `Error::Verification` if any test does not hold, or the last
+/// `R` is not a prime below 2^64.
+pub fn verify(certificate: &Certificate) -> Result<()> {
+    let mut n = certificate.n.clone();
+
+    for test in &certificate.tests {
+        n = match verify_test(test, &n) {
+            Some(r) => r,
+            None => {
+                return Err(Error::Verification {
+                    what: "a test of the Primo certificate",
+                })
+            }
+        };
+    }
+
+    if n.bit_length() > 64 || !is_prime_u64(u64::from(&n)) {
+        return Err(Error::Verification {
+            what: "the last prime of the Primo certificate",
+        });
+    }
+
+    Ok(())
+}
+
+/// Checks that `test` proves `n` prime if `R` is, and returns `R`.
+fn verify_test(test: &Test, n: &Int) -> Option<Int> {
+    if *n <= 3_i32 || n.is_even() || n % 3_usize == 0_i32 {
+        return None;
+    }
+
+    match *test {
+        Test::NMinusOne { ref s, ref b } => verify_n_minus_one(n, s, b),
+        Test::NPlusOne {
+            ref s,
+            ref p,
+            ref q,
+        } => verify_n_plus_one(n, s, p, q),
+        Test::Curve {
+            ref s,
+            ref w,
+            ref a,
+            ref b,
+            ref t,
+        } => verify_curve(n, s, w, a, b, t),
+        Test::JInvariant {
+            ref s,
+            ref w,
+            ref j,
+            ref t,
+        } => {
+            let k = Int::from(1728) - j;
+            let a = j * &k * 3_usize;
+            let b = j * k.square() * 2_usize;
+            verify_curve(n, s, w, &a, &b, t)
+        }
+    }
+}
+
+fn verify_n_minus_one(n: &Int, s: &Int, b: &Int) -> Option<Int> {
+    let one = Int::one();
+    let r = divide(&(n - &one), s)?;
+    let b = reduce(b.clone(), n);
+
+    // Every prime factor of n is at least r + 1, so one is at most sqrt(n).
+    let holds = (&r + &one).square() > *n
+        && b.pow_mod(&(n - &one), n) == one
+        && reduce(b.pow_mod(s, n) - &one, n).gcd(n) == one;
+
+    if holds {
+        Some(r)
+    } else {
+        None
+    }
+}
+
+fn verify_n_plus_one(n: &Int, s: &Int, p: &Int, q: &Int) -> Option<Int> {
+    let one = Int::one();
+    let r = divide(&(n + &one), s)?;
+
+    // Every prime factor of n is at least r - 1, so one is at most sqrt(n).
+    let d = p.square() - q * 4_usize;
+    let holds = (&r - &one).square() > *n
+        && reduce(q * &d * 2_usize, n).gcd(n) == one
+        && jacobi(&d, n) == -1
+        && lucas_u(p, q, &(n + &one), n) == 0_i32
+        && lucas_u(p, q, s, n).gcd(n) == one;
+
+    if holds {
+        Some(r)
+    } else {
+        None
+    }
+}
+
+fn verify_curve(n: &Int, s: &Int, w: &Int, a: &Int, b: &Int, t: &Int) -> Option<Int> {
+    let r = divide(&(n + 1_usize - w), s)?;
+    if r <= min_ecpp_factor(n) {
+        return None;
+    }
+
+    // (T L, L^2) lies on y^2 = x^3 + A L^2 x + B L^3, which is singular
+    // modulo any factor of n that divides L.
+    let l = reduce(t.pow(3) + a * t + b, n);
+    let l_squared = l.square();
+    let curve = Curve::new(&(a * &l_squared), &(b * &l_squared * &l), n)?;
+    let point = Point::Affine(reduce(t * &l, n), reduce(l_squared, n));
+
+    match curve.mul(&point, s)? {
+        Point::Infinity => None,
+        cofactor_point => {
+            if curve.mul(&cofactor_point, &r)? == Point::Infinity {
+                Some(r)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Returns `m / s` if `s` is positive and divides `m` exactly.
+fn divide(m: &Int, s: &Int) -> Option<Int> {
+    if *s <= 0_i32 || m % s != 0_i32 {
+        return None;
+    }
+
+    Some(m / s)
+}
+
+/// Returns the term `U(k)` of the Lucas sequence with parameters `p` and
+/// `q`, modulo the odd `n`.
+fn lucas_u(p: &Int, q: &Int, k: &Int, n: &Int) -> Int {
+    let p = reduce(p.clone(), n);
+    let q = reduce(q.clone(), n);
+    let d = reduce(p.square() - &q * 4_usize, n);
+    let half = |x: Int| {
+        let x = reduce(x, n);
+        if x.is_even() {
+            x >> 1
+        } else {
+            (x + n) >> 1
+        }
+    };
+
+    // U(i), V(i) and Q^i, for the leading bits i of k.
+    let (mut u, mut v, mut q_power) = (Int::zero(), Int::from(2), Int::one());
+    for bit in (0..k.bit_length()).rev() {
+        u = &u * &v % n;
+        v = reduce(v.square() - &q_power * 2_usize, n);
+        q_power = q_power.square() % n;
+
+        if k.bit(bit) {
+            let next_u = half(&p * &u + &v);
+            v = half(&d * &u + &p * &v);
+            u = next_u;
+            q_power = q_power * &q % n;
+        }
+    }
+
+    u
+}
+
+/// Reduces `x` into `[0, n)`.
+fn reduce(x: Int, n: &Int) -> Int {
+    let x = x % n;
+    if x < Int::zero() {
+        x + n
+    } else {
+        x
+    }
+}
+
+/// Splits `text` into its sections, each a map from keys to values.
+fn parse_sections(text: &str) -> Result<HashMap<String, HashMap<String, String>>> {
+    let mut sections = HashMap::new();
+    let mut current: Option<String> = None;
+
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            let name = line[1..line.len() - 1].trim().to_string();
+            if sections.contains_key(&name) {
+                return Err(parse_error());
+            }
+            sections.insert(name.clone(), HashMap::new());
+            current = Some(name);
+        } else if let Some(equals) = line.find('=') {
+            let (key, value) = (line[..equals].trim(), line[equals + 1..].trim());
+            let section = current
+                .as_ref()
+                .and_then(|name| sections.get_mut(name))
+                .ok_or_else(parse_error)?;
+            section.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    Ok(sections)
+}
+
+/// Parses one `[i]` section.
+fn parse_test(section: &HashMap<String, String>) -> Result<Test> {
+    let field = |key: &str| parse_field(section, key);
+
+    match section.get("Type").map(String::as_str) {
+        Some("1") => Ok(Test::NMinusOne {
+            s: field("S")?,
+            b: field("B")?,
+        }),
+        Some("2") => Ok(Test::NPlusOne {
+            s: field("S")?,
+            p: if section.contains_key("P") {
+                field("P")?
+            } else {
+                Int::one()
+            },
+            q: field("Q")?,
+        }),
+        Some("3") => Ok(Test::Curve {
+            s: field("S")?,
+            w: field("W")?,
+            a: field("A")?,
+            b: field("B")?,
+            t: field("T")?,
+        }),
+        Some("4") => Ok(Test::JInvariant {
+            s: field("S")?,
+            w: field("W")?,
+            j: field("J")?,
+            t: field("T")?,
+        }),
+        _ => Err(parse_error()),
+    }
+}
+
+/// Parses the number under `key`, in decimal or, after a `$`, hexadecimal.
+/// Either may be negative, with the `-` on either side of the `$`.
+fn parse_field(section: &HashMap<String, String>, key: &str) -> Result<Int> {
+    let value = section.get(key).ok_or_else(parse_error)?;
+    let (negative, value) = match value.trim_start_matches('$').chars().next() {
+        Some('-') => (true, value.replacen('-', "", 1)),
+        _ => (false, value.clone()),
+    };
+    let (radix, digits) = match value.strip_prefix('$') {
+        Some(digits) => (16, digits),
+        None => (10, &value[..]),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Err(parse_error());
+    }
+
+    let magnitude = Int::from_str_radix(digits, radix as u8).map_err(|err| Error::Parse {
+        what: "Primo certificate",
+        source: Some(Box::new(err)),
+    })?;
+    Ok(if negative { -magnitude } else { magnitude })
+}
+
+fn parse_error() -> Error {
+    Error::Parse {
+        what: "Primo certificate",
+        source: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lucas_u, verify, Certificate, Test};
+    use error::Error;
+    use ramp::Int;
+
+    // Written in Primo's format by a script rather than by Primo itself:
+    // 256 -> 129 -> 66 -> 36 bits, with one test of each type, down to
+    // 1000003.
+    const CERTIFICATE: &str = "\
+[PRIMO - Primality Certificate]
+Version=4.3.3 - LX64
+Format=4
+TestCount=4
+Status=Candidate certified prime
+
+[Comments]
+Put here any comment
+
+[Candidate]
+Binary Size=256
+N=$9DB66C740E10514A49AD66CD382F88E62AF81AACD1EF94D25B9A520056464EFD
+
+[1]
+Type=4
+S=$90CFE36C0D0DB4120CA90C48FE9F0704
+W=$151C5C576FD0A50AE5FB4108DB518D0D2
+J=$-8000
+T=$1
+
+[2]
+Type=3
+S=$7D188343925E6ED8
+W=$1B3FF927867F08AF4
+A=$797CFA6DEE2E769C018EE19B42812CC6
+B=$109BCEA2166DBA6207CE1404DEC9806AE
+T=$1
+
+[3]
+Type=1
+S=$287A4650
+B=$2
+
+[4]
+Type=2
+S=$EC7C
+Q=$4
+
+[Signature]
+1=$0123456789ABCDEF
+";
+
+    #[test]
+    fn test_primo_verify() {
+        let certificate: Certificate = CERTIFICATE.parse().unwrap();
+        assert_eq!(certificate.n.bit_length(), 256);
+        assert_eq!(certificate.tests.len(), 4);
+        match certificate.tests[0] {
+            Test::JInvariant { ref j, .. } => assert_eq!(*j, Int::from(-32768)),
+            ref other => panic!("unexpected test: {:?}", other),
+        }
+        match certificate.tests[3] {
+            Test::NPlusOne { ref p, ref q, .. } => {
+                assert_eq!(*p, Int::one());
+                assert_eq!(*q, Int::from(4));
+            }
+            ref other => panic!("unexpected test: {:?}", other),
+        }
+        assert!(verify(&certificate).is_ok());
+
+        // Breaking any test breaks the proof.
+        for i in 0..4 {
+            let mut broken = certificate.clone();
+            match broken.tests[i] {
+                Test::NMinusOne { ref mut s, .. }
+                | Test::NPlusOne { ref mut s, .. }
+                | Test::Curve { ref mut s, .. }
+                | Test::JInvariant { ref mut s, .. } => *s *= 2_usize,
+            }
+            match verify(&broken) {
+                Err(Error::Verification { .. }) => {}
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+
+        // Without its last test, the chain ends on a 36-bit prime, which is
+        // fine, and without the last two on a 66-bit one, which is not.
+        let mut shorter = certificate.clone();
+        shorter.tests.pop();
+        assert!(verify(&shorter).is_ok());
+        shorter.tests.pop();
+        assert!(verify(&shorter).is_err());
+
+        let mut composite = certificate;
+        composite.n += 2_usize;
+        assert!(verify(&composite).is_err());
+    }
+
+    // The order of the NIST P-256 group, from FIPS 186-4, certified in
+    // Primo's format by the same script rather than by Primo: 256 -> 215
+    // -> 117 -> 73 -> 40 bits. Only the number is published; a certificate
+    // from Primo itself would also cover the fields that the script does
+    // not write, and belongs here once one is at hand.
+    const P256_ORDER: &str = "\
+[PRIMO - Primality Certificate]
+Format=4
+TestCount=4
+
+[Candidate]
+N=$FFFFFFFF00000000FFFFFFFFFFFFFFFFBCE6FAADA7179E84F3B9CAC2FC632551
+
+[1]
+Type=2
+S=$303750F1FE2
+Q=$2
+
+[2]
+Type=2
+S=$54091E7B6D0E73A0CB327050E
+Q=$3
+
+[3]
+Type=1
+S=$DB4B2AD8E82
+B=$2
+
+[4]
+Type=1
+S=$23ED9A744
+B=$2
+";
+
+    #[test]
+    fn test_primo_p256_order() {
+        let certificate: Certificate = P256_ORDER.parse().unwrap();
+        let order = Int::from_str_radix(
+            "115792089210356248762697446949407573529996955224135760342422259061068512044369",
+            10,
+        )
+        .unwrap();
+
+        assert_eq!(certificate.n, order);
+        assert!(verify(&certificate).is_ok());
+    }
+
+    #[test]
+    fn test_primo_parse() {
+        let decimal = CERTIFICATE.replace(
+            "N=$9DB66C740E10514A49AD66CD382F88E62AF81AACD1EF94D25B9A520056464EFD",
+            "N=71335431910802840422842074627193959649123430311805782567444839901757708848893",
+        );
+        assert_eq!(
+            decimal.parse::<Certificate>().unwrap(),
+            CERTIFICATE.parse::<Certificate>().unwrap()
+        );
+
+        let broken = [
+            CERTIFICATE.replace("Format=4", "Format=3"),
+            CERTIFICATE.replace("TestCount=4", "TestCount=5"),
+            CERTIFICATE.replace("Type=2", "Type=5"),
+            CERTIFICATE.replace("Q=$4", "Q=$4G"),
+            CERTIFICATE.replace("[Candidate]\n", ""),
+            CERTIFICATE.replace("B=$2\n", ""),
+            CERTIFICATE.replace("[3]", "[2]"),
+        ];
+        for text in &broken {
+            match text.parse::<Certificate>() {
+                Err(Error::Parse { what, .. }) => assert_eq!(what, "Primo certificate"),
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_primo_lucas() {
+        // With P = 1 and Q = -1, U is the Fibonacci sequence.
+        let n = Int::from(1_000_003);
+        let fibonacci = [0, 1, 1, 2, 3, 5, 8, 13, 21, 34, 55, 89];
+        for (k, &f) in fibonacci.iter().enumerate() {
+            let u = lucas_u(&Int::one(), &Int::from(-1), &Int::from(k), &n);
+            assert_eq!(u, Int::from(f));
+        }
+    }
+}
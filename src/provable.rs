@@ -0,0 +1,617 @@
+//! Generates provable primes, whose primality follows from the way they were
+//! constructed rather than from a probabilistic test.
+//!
+//! `shawe_taylor()` is the Shawe-Taylor random prime routine of FIPS 186-5:
+//! a prime of up to 32 bits is found by hashing the seed and trial division,
+//! and each longer prime `c = 2 t c0 + 1` is built on a prime `c0` of just
+//! over half its length, and proven prime by Pocklington's criterion with a
+//! base also derived from the seed. `construct_prime()` builds on it the
+//! provable primes `p` of appendix A.1.2, optionally with the prime factors
+//! `p1` of `p - 1` and `p2` of `p + 1`.
+//!
+//! Both are deterministic, given the seed and the hash `D`. Their results
+//! record the seeds, counters and intermediate primes, so that a third party
+//! can derive them again with `verify()`.
+//!
+//! `maurer()` is Maurer's construction, which draws randomly from every
+//! prime of the requested size, nearly uniformly. Each prime `n = 2 R q + 1`
+//! is built on a prime `q` of random relative size, greater than `sqrt(n)`,
+//! and is returned with a `certificate::Certificate` that proves it prime
+//! with the chain of Pocklington witnesses, for anyone to check with
+//! `certificate::verify_certificate()`.
+
+use ramp::Int;
+
+use hmac::digest::Digest;
+
+use rand::Rng;
+
+use std::sync::Arc;
+
+use backend::BigInt;
+use certificate::{Certificate, CertifiedPrime, Pocklington, PocklingtonFactor};
+use common::{check_bit_length, mod_inverse, random_bits, random_range, SMALL_PRIMES};
+use error::{Error, Result};
+use prime::small::is_prime_u32;
+
+/// The longest prime found by trial division rather than built on another.
+const MAX_TRIAL_DIVISION_BITS: usize = 32;
+
+/// The fewest bits that `maurer()` leaves to the random `R` in `2 R q + 1`.
+const MIN_MAURER_MARGIN: usize = 20;
+
+/// A prime from `shawe_taylor()`, with everything needed to derive it again.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShaweTaylorPrime {
+    /// The prime itself.
+    pub prime: Int,
+    /// The seed it was derived from.
+    pub input_seed: Vec<u8>,
+    /// The seed as it was left by the construction, from which further
+    /// values may be derived.
+    pub prime_seed: Vec<u8>,
+    /// The number of candidates tried, over every level of the
+    /// construction.
+    pub prime_gen_counter: u32,
+    /// The primes that `prime` was built on, smallest first. The first was
+    /// found by trial division, and each proves the next one prime.
+    pub intermediate_primes: Vec<Int>,
+}
+
+impl ShaweTaylorPrime {
+    /// Derives the prime again from `input_seed`, with the hash `D`, and
+    /// checks that every recorded value matches.
+    ///
+    /// Fails with `Error::Verification` if any of them differs.
+    pub fn verify<D: Digest>(&self) -> Result<()> {
+        let length = self.prime.bit_length() as usize;
+
+        match shawe_taylor::<D>(length, &self.input_seed) {
+            Ok(ref derived) if derived == self => Ok(()),
+            _ => Err(Error::Verification {
+                what: "the Shawe-Taylor prime",
+            }),
+        }
+    }
+}
+
+/// A prime from `construct_prime()`, with everything needed to derive it
+/// again.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConstructedPrime {
+    /// The prime itself.
+    pub prime: Int,
+    /// The prime factor of `prime - 1` that was asked for, or 1.
+    pub p1: Int,
+    /// The prime factor of `prime + 1` that was asked for, or 1.
+    pub p2: Int,
+    /// The Shawe-Taylor prime `p0`, which also divides `prime - 1` and
+    /// proves `prime` prime.
+    pub p0: ShaweTaylorPrime,
+    /// The seed it was derived from.
+    pub first_seed: Vec<u8>,
+    /// The seed as it was left by the construction.
+    pub prime_seed: Vec<u8>,
+    /// The number of candidates tried for `prime`.
+    pub pgen_counter: u32,
+}
+
+impl ConstructedPrime {
+    /// Derives the prime again from `first_seed`, with the hash `D` and the
+    /// public exponent `e`, and checks that every recorded value matches.
+    ///
+    /// Fails with `Error::Verification` if any of them differs.
+    pub fn verify<D: Digest>(&self, e: &Int) -> Result<()> {
+        let one = Int::one();
+        let length_of = |prime: &Int| {
+            if *prime == one {
+                1
+            } else {
+                prime.bit_length() as usize
+            }
+        };
+        let length = self.prime.bit_length() as usize;
+
+        match construct_prime::<D>(
+            length,
+            length_of(&self.p1),
+            length_of(&self.p2),
+            &self.first_seed,
+            e,
+        ) {
+            Ok(ref derived) if derived == self => Ok(()),
+            _ => Err(Error::Verification {
+                what: "the constructed prime",
+            }),
+        }
+    }
+}
+
+/// A prime with its certificate, shared so that every prime built on it can
+/// rely on it without a copy.
+pub(crate) struct ProvenFactor {
+    pub prime: Int,
+    pub certificate: Arc<Certificate>,
+}
+
+/// Runs the Shawe-Taylor random prime routine, deriving a prime of exactly
+/// `length` bits from `input_seed` with the hash `D`.
+///
+/// Fails with `Error::BitLength` if `length` is below 2, and with
+/// `Error::BudgetExhausted` if a level of the construction tries more
+/// candidates than the routine allows.
+pub fn shawe_taylor<D: Digest>(length: usize, input_seed: &[u8]) -> Result<ShaweTaylorPrime> {
+    check_bit_length(length, 2)?;
+
+    if length <= MAX_TRIAL_DIVISION_BITS {
+        return small_prime::<D>(length, input_seed);
+    }
+
+    let mut result = shawe_taylor::<D>(length.div_ceil(2) + 1, input_seed)?;
+    let c0 = result.prime.clone();
+    let old_counter = result.prime_gen_counter;
+    let mut prime_seed = result.prime_seed.clone();
+
+    let one = Int::one();
+    let top = one.clone() << (length - 1);
+    let twice_c0: Int = &c0 << 1;
+
+    let x = top.clone() + hash_sum::<D>(&mut prime_seed, length) % &top;
+    let mut t = ceil_div(&x, &twice_c0);
+
+    loop {
+        if &twice_c0 * &t + 1_usize > one.clone() << length {
+            t = ceil_div(&top, &twice_c0);
+        }
+        let c = &twice_c0 * &t + 1_usize;
+        result.prime_gen_counter += 1;
+
+        let three: Int = Int::from(3);
+        let a = hash_sum::<D>(&mut prime_seed, length) % (&c - &three) + 2_usize;
+        let z = a.pow_mod(&(&t << 1), &c);
+
+        if (&z - &one).gcd(&c) == one && z.pow_mod(&c0, &c) == one {
+            result.intermediate_primes.push(c0);
+            result.prime = c;
+            result.prime_seed = prime_seed;
+
+            return Ok(result);
+        }
+
+        if result.prime_gen_counter as usize >= 4 * length + old_counter as usize {
+            return Err(Error::BudgetExhausted {
+                attempts: u64::from(result.prime_gen_counter),
+            });
+        }
+        t += 1_usize;
+    }
+}
+
+/// Runs the provable prime construction, deriving a prime `p` of exactly
+/// `length` bits, at least `sqrt(2) 2^(length - 1)`, from `first_seed`
+/// with the hash `D`. `p - 1` is coprime to `e`, and has a prime factor
+/// `p1` of `n1` bits, and `p + 1` a prime factor `p2` of `n2` bits. Either
+/// factor is left out when its length is 1.
+///
+/// Fails with `Error::InvalidParameter` if `n1` or `n2` is 0, or if they
+/// leave too little room for `p0`, which has just over half of `length`
+/// bits, and with `Error::BudgetExhausted` if the construction tries more
+/// candidates than it allows.
+pub fn construct_prime<D: Digest>(
+    length: usize,
+    n1: usize,
+    n2: usize,
+    first_seed: &[u8],
+    e: &Int,
+) -> Result<ConstructedPrime> {
+    check_bit_length(length, MAX_TRIAL_DIVISION_BITS + 1)?;
+    if n1 == 0 || n2 == 0 || n1 + n2 + 4 + length.div_ceil(2) > length {
+        return Err(Error::InvalidParameter {
+            name: "n1, n2",
+            reason: format!("must be positive, and add up to at most {}", length / 2 - 4),
+        });
+    }
+
+    let one = Int::one();
+    let (p1, p1_seed) = factor_prime::<D>(n1, first_seed)?;
+    let (p2, p2_seed) = factor_prime::<D>(n2, &p1_seed)?;
+    let p0 = shawe_taylor::<D>(length.div_ceil(2) + 1, &p2_seed)?;
+    let mut prime_seed = p0.prime_seed.clone();
+
+    // floor(sqrt(2) 2^(length - 1)) = floor(sqrt(2^(2 length - 1))).
+    let (bound, _) = (one.clone() << (2 * length - 1))
+        .sqrt_rem()
+        .expect("the square is positive");
+    let x = hash_sum::<D>(&mut prime_seed, length) % ((one.clone() << length) - &bound) + &bound;
+
+    let p0_p1 = &p0.prime * &p1;
+    if p0_p1.gcd(&p2) != one {
+        return Err(Error::InvalidParameter {
+            name: "first_seed",
+            reason: "derives auxiliary primes that share a factor".to_string(),
+        });
+    }
+
+    // y is in [1, p2], with p0 p1 y = 1 (mod p2).
+    let y = if p2 == one {
+        one.clone()
+    } else {
+        mod_inverse(&p0_p1, &p2).expect("p0 p1 is coprime to p2")
+    };
+    let twice_p0_p1: Int = &p0_p1 << 1;
+    let step = &twice_p0_p1 * &p2;
+    let mut t = ceil_div(&(&twice_p0_p1 * &y + &x), &step);
+    let mut pgen_counter = 0;
+
+    loop {
+        if (&t * &p2 - &y) * &twice_p0_p1 + 1_usize > one.clone() << length {
+            t = ceil_div(&(&twice_p0_p1 * &y + &bound), &step);
+        }
+        let multiplier = &t * &p2 - &y;
+        let p = &multiplier * &twice_p0_p1 + 1_usize;
+
+        if (&p - &one).gcd(e) == one {
+            let three: Int = Int::from(3);
+            let a = hash_sum::<D>(&mut prime_seed, length) % (&p - &three) + 2_usize;
+            let z = a.pow_mod(&((multiplier << 1) * &p1), &p);
+
+            if (&z - &one).gcd(&p) == one && z.pow_mod(&p0.prime, &p) == one {
+                return Ok(ConstructedPrime {
+                    prime: p,
+                    p1,
+                    p2,
+                    p0,
+                    first_seed: first_seed.to_vec(),
+                    prime_seed,
+                    pgen_counter: pgen_counter + 1,
+                });
+            }
+        }
+
+        pgen_counter += 1;
+        if pgen_counter as usize >= 5 * length {
+            return Err(Error::BudgetExhausted {
+                attempts: u64::from(pgen_counter),
+            });
+        }
+        t += 1_usize;
+    }
+}
+
+/// Constructs a random provable prime of exactly `bit_length` bits with
+/// Maurer's algorithm, along with its certificate.
+///
+/// Fails with `Error::BitLength` if `bit_length` is below 2.
+pub fn maurer<R: Rng + ?Sized>(bit_length: usize, rngesus: &mut R) -> Result<CertifiedPrime> {
+    check_bit_length(bit_length, 2)?;
+
+    if bit_length <= MAX_TRIAL_DIVISION_BITS {
+        loop {
+            let mut candidate: Int = random_bits(rngesus, bit_length)?;
+            candidate.set_bit(bit_length as u32 - 1, true);
+            candidate.set_bit(0, true);
+
+            let n = u64::from(&candidate);
+            if is_prime_u32(n as u32) {
+                return Ok(CertifiedPrime {
+                    prime: candidate,
+                    certificate: Certificate::Small(n),
+                });
+            }
+        }
+    }
+
+    let q = maurer_factor(bit_length, rngesus)?;
+    maurer_last_step(bit_length, &q, rngesus, |_| false)
+}
+
+/// Draws the prime `q` that Maurer's algorithm builds a prime of
+/// `bit_length` bits on, for `bit_length` above `MAX_TRIAL_DIVISION_BITS`.
+pub(crate) fn maurer_factor<R: Rng + ?Sized>(
+    bit_length: usize,
+    rngesus: &mut R,
+) -> Result<ProvenFactor> {
+    // The relative size of q is 2^(s - 1), for s uniform in [0, 1], so long
+    // as that leaves enough bits for R. q must exceed sqrt(n) regardless.
+    let mut relative_size = 0.5;
+    if bit_length > 2 * MIN_MAURER_MARGIN {
+        loop {
+            let s: Int = random_bits(rngesus, 53)?;
+            relative_size = (u64::from(&s) as f64 / (1_u64 << 53) as f64 - 1.0).exp2();
+
+            if bit_length as f64 * (1.0 - relative_size) > MIN_MAURER_MARGIN as f64 {
+                break;
+            }
+        }
+    }
+    let q_bits = (bit_length as f64 * relative_size) as usize;
+    let q = maurer(q_bits.max(bit_length.div_ceil(2)) + 1, rngesus)?;
+
+    Ok(ProvenFactor {
+        prime: q.prime,
+        certificate: Arc::new(q.certificate),
+    })
+}
+
+/// Runs the last step of Maurer's algorithm on the prime `q` from
+/// `maurer_factor()`: draws `R` until `n = 2 R q + 1` of `bit_length` bits
+/// is proven prime, skipping without any exponentiation the candidates
+/// that `rejects`.
+pub(crate) fn maurer_last_step<R, F>(
+    bit_length: usize,
+    q: &ProvenFactor,
+    rngesus: &mut R,
+    mut rejects: F,
+) -> Result<CertifiedPrime>
+where
+    R: Rng + ?Sized,
+    F: FnMut(&Int) -> bool,
+{
+    let one = Int::one();
+    let twice_q: Int = &q.prime << 1;
+    let interval = (one.clone() << (bit_length - 1)) / &twice_q;
+    let low = &interval + 1_usize;
+    let high = &interval * 2_usize + 1_usize;
+
+    // Trial division by the primes below 0.1 bit_length^2 weeds out most
+    // candidates before the exponentiations.
+    let divisors = trial_divisors(bit_length);
+
+    loop {
+        let r = random_range(&low, &high, rngesus)?;
+        let n = &twice_q * &r + 1_usize;
+        if divisors
+            .iter()
+            .any(|&prime| &n % prime as usize == Int::zero())
+            || rejects(&n)
+        {
+            continue;
+        }
+
+        let minus_one = &n - &one;
+        let witness = random_range(&Int::from(2), &minus_one, rngesus)?;
+        if witness.pow_mod(&minus_one, &n) != one
+            || (witness.pow_mod(&(r << 1), &n) - &one).gcd(&n) != one
+        {
+            continue;
+        }
+
+        return Ok(CertifiedPrime {
+            certificate: Certificate::Pocklington(Pocklington {
+                n: n.clone(),
+                factors: vec![PocklingtonFactor {
+                    certificate: q.certificate.clone(),
+                    exponent: 1,
+                    witness,
+                }],
+            }),
+            prime: n,
+        });
+    }
+}
+
+/// Returns the small primes below `0.1 bit_length^2`, which are worth
+/// trying as divisors of a candidate of `bit_length` bits.
+pub(crate) fn trial_divisors(bit_length: usize) -> Vec<u32> {
+    let bound = bit_length * bit_length / 10;
+
+    SMALL_PRIMES
+        .iter()
+        .cloned()
+        .take_while(|&prime| (prime as usize) < bound)
+        .collect()
+}
+
+/// Finds a prime of at most 32 bits by hashing the seed, and trial division.
+fn small_prime<D: Digest>(length: usize, input_seed: &[u8]) -> Result<ShaweTaylorPrime> {
+    let mut prime_seed = input_seed.to_vec();
+    let mask = (1_u64 << (length - 1)) - 1;
+
+    for prime_gen_counter in 1.. {
+        let first = D::digest(&prime_seed);
+        let second = D::digest(seed_plus(&prime_seed, 1));
+
+        // Only the last 4 bytes of the exclusive or are kept.
+        let tail = first
+            .iter()
+            .zip(second.iter())
+            .map(|(a, b)| a ^ b)
+            .skip(first.len() - 4)
+            .fold(0_u64, |word, byte| word << 8 | u64::from(byte));
+        let c = ((1 << (length - 1)) | (tail & mask)) | 1;
+        prime_seed = seed_plus(&prime_seed, 2);
+
+        if is_prime_u32(c as u32) {
+            return Ok(ShaweTaylorPrime {
+                prime: Int::from(c),
+                input_seed: input_seed.to_vec(),
+                prime_seed,
+                prime_gen_counter,
+                intermediate_primes: Vec::new(),
+            });
+        }
+        if prime_gen_counter as usize > 4 * length {
+            return Err(Error::BudgetExhausted {
+                attempts: u64::from(prime_gen_counter),
+            });
+        }
+    }
+
+    unreachable!()
+}
+
+/// Returns a Shawe-Taylor prime of `length` bits and the seed it left, or 1
+/// and `seed` itself when `length` is 1.
+fn factor_prime<D: Digest>(length: usize, seed: &[u8]) -> Result<(Int, Vec<u8>)> {
+    if length == 1 {
+        return Ok((Int::one(), seed.to_vec()));
+    }
+
+    shawe_taylor::<D>(length, seed).map(|result| (result.prime, result.prime_seed))
+}
+
+/// Returns the sum of `Hash(seed + i) 2^(i outlen)`, over as many `i` as
+/// cover `length` bits, and advances the seed past them.
+fn hash_sum<D: Digest>(seed: &mut Vec<u8>, length: usize) -> Int {
+    let outlen = <D as Digest>::output_size() * 8;
+    let iterations = length.div_ceil(outlen);
+    let mut sum = Int::zero();
+
+    for i in (0..iterations).rev() {
+        sum <<= outlen;
+        sum += Int::from_be_bytes(&D::digest(seed_plus(seed, i as u64)));
+    }
+    *seed = seed_plus(seed, iterations as u64);
+
+    sum
+}
+
+/// Adds `i` to the big-endian `seed`, modulo 2 to the power of its length.
+pub(crate) fn seed_plus(seed: &[u8], mut i: u64) -> Vec<u8> {
+    let mut sum = seed.to_vec();
+
+    for byte in sum.iter_mut().rev() {
+        let total = u64::from(*byte) + (i & 0xff);
+        *byte = total as u8;
+        i = (i >> 8) + (total >> 8);
+    }
+
+    sum
+}
+
+/// Returns `ceil(a / b)` for positive `a` and `b`.
+fn ceil_div(a: &Int, b: &Int) -> Int {
+    (a + b - 1_usize) / b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{construct_prime, maurer, seed_plus, shawe_taylor};
+    use certificate::{verify_certificate, Certificate};
+    use common::is_prime;
+    use error::Error;
+    use ramp::Int;
+    use rand::thread_rng;
+    use sha2::{Sha256, Sha512};
+
+    // The expected values below are for a seed of our own, since no CAVP
+    // response file for provable primes is at hand. They were checked
+    // against a separate implementation of appendices C.6 and C.10 of
+    // FIPS 186-4, written from the text of the standard alone.
+    const SEED: &str = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+
+    fn hex(digits: &str) -> Vec<u8> {
+        (0..digits.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_shawe_taylor_vectors() {
+        let seed = hex(SEED);
+        let vectors = [
+            (32, "e503149d", "ef0b", 6, 0),
+            (
+                160,
+                "a2451f685ad8bfb1e4a844cc921bff6aab1751d1",
+                "ef43",
+                60,
+                3,
+            ),
+            (
+                256,
+                "99065a3b22b9a2dc9d6d96f5e6f123faa5ba076a68d6f26273ea91aece162683",
+                "efb2",
+                158,
+                4,
+            ),
+        ];
+
+        for &(length, prime, seed_tail, counter, intermediates) in &vectors {
+            let result = shawe_taylor::<Sha256>(length, &seed).unwrap();
+
+            assert_eq!(result.prime, Int::from_str_radix(prime, 16).unwrap());
+            assert_eq!(result.input_seed, seed);
+            assert_eq!(result.prime_seed[..30], seed[..30]);
+            assert_eq!(result.prime_seed[30..], hex(seed_tail)[..]);
+            assert_eq!(result.prime_gen_counter, counter);
+            assert_eq!(result.intermediate_primes.len(), intermediates);
+            assert!(result.verify::<Sha256>().is_ok());
+        }
+
+        let result = shawe_taylor::<Sha512>(521, &seed).unwrap();
+        assert_eq!(
+            result.prime.to_str_radix(16, false),
+            "141ddae0bb955e1742e5951fb61d7a433c44d88ebb3ec0af89d5032dfa10277a28f3f59a72fc40eb2809df\
+             7be85f7d07fd6ca006456c3df3fcd7ee97dcfd9616fa1"
+        );
+        assert_eq!(result.prime_gen_counter, 470);
+        assert!(result.intermediate_primes.iter().all(is_prime));
+        assert!(result.verify::<Sha256>().is_err());
+    }
+
+    #[test]
+    fn test_construct_prime() {
+        let seed = hex(&SEED[..56]);
+        let e = Int::from(65537);
+        let result = construct_prime::<Sha256>(1024, 1, 1, &seed, &e).unwrap();
+
+        assert_eq!(
+            result.prime.to_str_radix(16, false),
+            "d9caba3885b7c2a4939d719886810fe63f42b6c0c3b0fc063a371ac5465f33ebeb80ee28f061cfec3e87\
+             22a0add5052b70656eb5964d9fdfc50ec02ab2c20c6cbb1447579b5d2af1cbd140855e08596a9cf9f764\
+             e2841f040fb6446b6467fb3c822866830f37f8047784dd8be133a8d2ed7ca4d1e90a4111318b7adbb9d7\
+             fe29"
+        );
+        assert_eq!(
+            result.prime_seed,
+            hex("00112233445566778899aabbccddeeff00112233445566778899af36")
+        );
+        assert_eq!(result.pgen_counter, 126);
+        assert!(result.verify::<Sha256>(&e).is_ok());
+
+        let result = construct_prime::<Sha256>(1024, 144, 160, &seed, &e).unwrap();
+        assert!(is_prime(&result.prime));
+        assert_eq!(result.p1.bit_length(), 144);
+        assert_eq!(result.p2.bit_length(), 160);
+        assert_eq!((&result.prime - 1_usize) % &result.p1, Int::zero());
+        assert_eq!((&result.prime + 1_usize) % &result.p2, Int::zero());
+        assert_eq!((&result.prime - 1_usize) % &result.p0.prime, Int::zero());
+        assert!(result.verify::<Sha256>(&e).is_ok());
+
+        // n1 + n2 may be at most length - ceil(length / 2) - 4: 508 for 1025.
+        for &(length, n1, n2) in &[(1024, 300, 300), (1025, 254, 255)] {
+            match construct_prime::<Sha256>(length, n1, n2, &seed, &e) {
+                Err(Error::InvalidParameter { .. }) => (),
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_maurer() {
+        let mut rngesus = thread_rng();
+
+        for &bit_length in &[2, 20, 33, 64, 256, 1024] {
+            let result = maurer(bit_length, &mut rngesus).unwrap();
+
+            assert_eq!(result.prime.bit_length() as usize, bit_length);
+            assert_eq!(result.certificate.n(), result.prime);
+            assert!(is_prime(&result.prime) || bit_length == 2);
+            assert!(verify_certificate(&result.certificate).is_ok());
+        }
+
+        let mut result = maurer(512, &mut rngesus).unwrap();
+        if let Certificate::Pocklington(ref mut certificate) = result.certificate {
+            certificate.n += 2_usize;
+        }
+        assert!(verify_certificate(&result.certificate).is_err());
+    }
+
+    #[test]
+    fn test_provable_helpers() {
+        assert_eq!(seed_plus(&[0x01, 0xff, 0xfe], 3), vec![0x02, 0x00, 0x01]);
+        assert_eq!(seed_plus(&[0xff, 0xff], 1), vec![0x00, 0x00]);
+    }
+}
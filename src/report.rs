@@ -0,0 +1,98 @@
+//! Statistics describing the work performed while generating a prime.
+
+use core::time::Duration;
+
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+/// A summary of a single prime number generation.
+///
+/// Reports are returned by the `from_rng_with_report()` family of functions,
+/// making it possible to compare how generation behaves across hosts, or to
+/// confirm that a change to the primality tests actually helps.
+///
+/// Without the `std` feature there is no clock to read, and every duration
+/// is left at zero.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GenerationReport {
+    /// The number of random candidates drawn.
+    pub candidates: u64,
+
+    /// The number of candidates rejected by dividing by the small primes.
+    pub trial_division_rejections: u64,
+
+    /// The number of candidates rejected by the Fermat test.
+    pub fermat_rejections: u64,
+
+    /// The number of candidates rejected by the Miller-Rabin test.
+    pub miller_rabin_rejections: u64,
+
+    /// The total number of Miller-Rabin rounds executed, across all
+    /// candidates.
+    pub miller_rabin_rounds: u64,
+
+    /// The number of bytes drawn from the caller's random number generator.
+    pub rng_bytes: u64,
+
+    /// Time spent drawing and shaping random candidates.
+    pub sampling_time: Duration,
+
+    /// Time spent dividing candidates by the small primes.
+    pub trial_division_time: Duration,
+
+    /// Time spent running the Fermat test.
+    pub fermat_time: Duration,
+
+    /// Time spent running the Miller-Rabin test.
+    pub miller_rabin_time: Duration,
+
+    /// Wall-clock time for the whole generation.
+    pub total_time: Duration,
+}
+
+impl GenerationReport {
+    /// Emits the report as a single `tracing` event.
+    #[cfg(all(feature = "tracing", feature = "std"))]
+    pub(crate) fn trace(&self, kind: &'static str) {
+        debug!(
+            kind = kind,
+            candidates = self.candidates,
+            trial_division_rejections = self.trial_division_rejections,
+            fermat_rejections = self.fermat_rejections,
+            miller_rabin_rejections = self.miller_rabin_rejections,
+            miller_rabin_rounds = self.miller_rabin_rounds,
+            rng_bytes = self.rng_bytes,
+            sampling_time = ?self.sampling_time,
+            trial_division_time = ?self.trial_division_time,
+            fermat_time = ?self.fermat_time,
+            miller_rabin_time = ?self.miller_rabin_time,
+            total_time = ?self.total_time,
+            "generation finished"
+        );
+    }
+}
+
+/// Measures the time spent in a stage of generation, if a clock is
+/// available.
+pub(crate) struct Stopwatch {
+    #[cfg(feature = "std")]
+    started: Instant,
+}
+
+impl Stopwatch {
+    pub(crate) fn start() -> Stopwatch {
+        Stopwatch {
+            #[cfg(feature = "std")]
+            started: Instant::now(),
+        }
+    }
+
+    /// Returns the time since `start()`, or zero without `std`.
+    pub(crate) fn elapsed(&self) -> Duration {
+        #[cfg(feature = "std")]
+        return self.started.elapsed();
+
+        #[cfg(not(feature = "std"))]
+        Duration::default()
+    }
+}
@@ -0,0 +1,584 @@
+//! Generates RSA key pairs from probable primes, as in FIPS 186-5 appendix
+//! B.3.3.
+//!
+//! Each prime is drawn at random with exactly half the bits of the modulus,
+//! and must be at least `sqrt(2) 2^(nlen / 2 - 1)`, so that the modulus has
+//! exactly `nlen` bits. `p - 1` and `q - 1` must be coprime to the public
+//! exponent, and `p` and `q` must differ by more than `2^(nlen / 2 - 100)`.
+//! The private exponent is the inverse of `e` modulo `λ(n) = lcm(p - 1, q -
+//! 1)`, and must exceed `2^(nlen / 2)`.
+//!
+//! Alternatively, `Method::AuxiliaryPrimes` builds each prime on auxiliary
+//! primes as in appendix A.1.6, so that `p - 1` and `p + 1` both have a
+//! large prime factor. The auxiliary primes are kept in the key for audit.
+//! `Method::ProvablePrimes` instead derives provable primes from a random
+//! seed as in appendix A.1.2, and keeps the seed and the constructions.
+//!
+//! Every generated key passes `PrivateKey::check()` before it is returned.
+
+mod auxiliary;
+mod provable;
+
+use ramp::Int;
+
+use rand::Rng;
+
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use std::fmt;
+
+use backend::BigInt;
+use common::{check_bit_length, is_prime_bpsw, is_prime_with_rng, mod_inverse, random_bits};
+use entropy;
+use error::{Error, Result};
+
+pub use self::auxiliary::AuxiliaryPrimes;
+pub use self::provable::ProvablePrimes;
+
+/// The smallest modulus that FIPS 186-5 allows.
+pub const MIN_MODULUS_BITS: usize = 2048;
+
+/// The public exponent used unless another is asked for.
+pub const DEFAULT_PUBLIC_EXPONENT: u64 = 65537;
+
+/// How the prime factors of the modulus are generated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Method {
+    /// Random probable primes, as in appendix B.3.3.
+    #[default]
+    ProbablePrimes,
+    /// Probable primes with conditions based on auxiliary probable primes,
+    /// as in appendix A.1.6.
+    AuxiliaryPrimes,
+    /// Provable primes derived from a random seed with the Shawe-Taylor
+    /// construction, as in appendix A.1.2.
+    ProvablePrimes,
+}
+
+/// The public half of an RSA key pair.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublicKey {
+    /// The modulus `n = p q`.
+    pub n: Int,
+    /// The public exponent.
+    pub e: Int,
+}
+
+/// An RSA private key, holding everything that can be computed from its
+/// primes. The secret values are wiped when it is dropped, and redacted from
+/// its `Debug` output.
+pub struct PrivateKey {
+    public: PublicKey,
+    d: Int,
+    p: Int,
+    q: Int,
+    lambda: Int,
+    d_p: Int,
+    d_q: Int,
+    q_inv: Int,
+    auxiliary: Option<AuxiliaryPrimes>,
+    provable: Option<ProvablePrimes>,
+}
+
+impl PrivateKey {
+    /// Returns the public key.
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public
+    }
+
+    /// Returns the modulus `n`.
+    pub fn n(&self) -> &Int {
+        &self.public.n
+    }
+
+    /// Returns the public exponent `e`.
+    pub fn e(&self) -> &Int {
+        &self.public.e
+    }
+
+    /// Returns the private exponent `d`, the inverse of `e` modulo `λ(n)`.
+    pub fn d(&self) -> &Int {
+        &self.d
+    }
+
+    /// Returns the prime factors `(p, q)` of the modulus, with `p > q`.
+    pub fn primes(&self) -> (&Int, &Int) {
+        (&self.p, &self.q)
+    }
+
+    /// Returns Carmichael's function of the modulus, `λ(n) = lcm(p - 1, q -
+    /// 1)`.
+    pub fn lambda(&self) -> &Int {
+        &self.lambda
+    }
+
+    /// Returns the CRT exponents `(dP, dQ)`, which are `d` modulo `p - 1`
+    /// and `q - 1`.
+    pub fn crt_exponents(&self) -> (&Int, &Int) {
+        (&self.d_p, &self.d_q)
+    }
+
+    /// Returns the CRT coefficient `qInv`, the inverse of `q` modulo `p`.
+    pub fn crt_coefficient(&self) -> &Int {
+        &self.q_inv
+    }
+
+    /// Returns the auxiliary primes that `p` and `q` were built on, if they
+    /// were generated with `Method::AuxiliaryPrimes`.
+    pub fn auxiliary_primes(&self) -> Option<&AuxiliaryPrimes> {
+        self.auxiliary.as_ref()
+    }
+
+    /// Returns the seed and constructions of `p` and `q`, if they were
+    /// generated with `Method::ProvablePrimes`.
+    pub fn provable_primes(&self) -> Option<&ProvablePrimes> {
+        self.provable.as_ref()
+    }
+
+    /// Checks that the key is consistent: that both primes pass the
+    /// Baillie-PSW test and meet the size and distance requirements of
+    /// B.3.3, that every derived value matches them, and that a value raised
+    /// to `e` comes back from both the plain and the CRT private key
+    /// operations. Any auxiliary primes must be prime, long enough, and
+    /// divide `p - 1`, `p + 1`, `q - 1` and `q + 1`, and any provable
+    /// primes must derive again from their seed.
+    ///
+    /// Fails with `Error::Verification` naming the first check to fail.
+    pub fn check(&self) -> Result<()> {
+        let one = Int::one();
+        let nlen = self.n().bit_length() as usize;
+        let p_minus_one = &self.p - &one;
+        let q_minus_one = &self.q - &one;
+
+        let checks = [
+            (
+                nlen.is_multiple_of(2) && nlen >= MIN_MODULUS_BITS,
+                "the modulus size",
+            ),
+            (
+                check_public_exponent(self.e()).is_ok(),
+                "the public exponent",
+            ),
+            (&self.p * &self.q == *self.n(), "the modulus"),
+            (
+                self.p > self.q && has_length(&self.p, nlen / 2) && has_length(&self.q, nlen / 2),
+                "the prime sizes",
+            ),
+            (
+                &self.p - &self.q > one.clone() << (nlen / 2 - 100),
+                "the distance between the primes",
+            ),
+            (
+                p_minus_one.gcd(self.e()) == one && q_minus_one.gcd(self.e()) == one,
+                "the primes' coprimality with e",
+            ),
+            (
+                self.lambda == p_minus_one.lcm(&q_minus_one),
+                "the Carmichael function",
+            ),
+            (
+                self.d > one.clone() << (nlen / 2)
+                    && self.d < self.lambda
+                    && (&self.d * self.e()) % &self.lambda == one,
+                "the private exponent",
+            ),
+            (
+                self.d_p == &self.d % &p_minus_one && self.d_q == &self.d % &q_minus_one,
+                "the CRT exponents",
+            ),
+            (
+                self.q_inv < self.p && (&self.q_inv * &self.q) % &self.p == one,
+                "the CRT coefficient",
+            ),
+            (
+                is_prime_bpsw(&self.p) && is_prime_bpsw(&self.q),
+                "the primality of the factors",
+            ),
+            (
+                self.auxiliary
+                    .as_ref()
+                    .is_none_or(|auxiliary| auxiliary.check(&self.p, &self.q, nlen)),
+                "the auxiliary primes",
+            ),
+            (
+                self.provable
+                    .as_ref()
+                    .is_none_or(|provable| provable.check(&self.p, &self.q, self.e())),
+                "the provable primes",
+            ),
+        ];
+
+        if let Some(&(_, what)) = checks.iter().find(|&&(passed, _)| !passed) {
+            return Err(Error::Verification { what });
+        }
+
+        // A pairwise consistency test, with a message that exercises every
+        // limb of the modulus.
+        let message = (Int::one() << (nlen - 2)) + 0x5a5a_usize;
+        let ciphertext = message.pow_mod(self.e(), self.n());
+
+        if ciphertext.pow_mod(&self.d, self.n()) != message
+            || self.decrypt_crt(&ciphertext) != message
+        {
+            return Err(Error::Verification {
+                what: "the pairwise consistency test",
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Runs the private key operation with the CRT values, as in RSADP.
+    fn decrypt_crt(&self, ciphertext: &Int) -> Int {
+        let m_1 = ciphertext.pow_mod(&self.d_p, &self.p);
+        let m_2 = ciphertext.pow_mod(&self.d_q, &self.q);
+
+        let mut h = (m_1 - &m_2) * &self.q_inv % &self.p;
+        if h < Int::zero() {
+            h += &self.p;
+        }
+
+        m_2 + h * &self.q
+    }
+}
+
+impl fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PrivateKey")
+            .field("n", self.n())
+            .field("e", self.e())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Zeroize for PrivateKey {
+    fn zeroize(&mut self) {
+        for secret in &mut [
+            &mut self.d,
+            &mut self.p,
+            &mut self.q,
+            &mut self.lambda,
+            &mut self.d_p,
+            &mut self.d_q,
+            &mut self.q_inv,
+        ] {
+            secret.wipe();
+        }
+    }
+}
+
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for PrivateKey {}
+
+/// Generates a key pair with a modulus of `modulus_bits` bits and the
+/// public exponent 65537.
+///
+/// This will initialize an `OsRng` instance, checked by the health tests
+/// of the `entropy` module, and call the `from_rng()` function.
+///
+/// Note: `modulus_bits` MUST be even and at least 2048.
+pub fn new(modulus_bits: usize) -> Result<PrivateKey> {
+    check_bit_length(modulus_bits, MIN_MODULUS_BITS)?;
+
+    let mut rngesus = entropy::os()?;
+    from_rng(modulus_bits, &mut rngesus)
+}
+
+/// Generates a key pair with a modulus of `modulus_bits` bits and the
+/// public exponent 65537, sourced from an already-initialized random number
+/// generator.
+pub fn from_rng<R: Rng + ?Sized>(modulus_bits: usize, rngesus: &mut R) -> Result<PrivateKey> {
+    from_rng_with_exponent(modulus_bits, &Int::from(DEFAULT_PUBLIC_EXPONENT), rngesus)
+}
+
+/// Generates a key pair like `from_rng()`, with the public exponent `e`.
+///
+/// `e` must be odd, and `2^16 < e < 2^256`, as FIPS 186-5 requires. Fails
+/// with `Error::InvalidParameter` otherwise, and with
+/// `Error::BudgetExhausted` if either prime takes more attempts than B.3.3
+/// allows.
+pub fn from_rng_with_exponent<R: Rng + ?Sized>(
+    modulus_bits: usize,
+    e: &Int,
+    rngesus: &mut R,
+) -> Result<PrivateKey> {
+    from_rng_with_method(modulus_bits, e, Method::default(), rngesus)
+}
+
+/// Generates a key pair like `from_rng_with_exponent()`, with primes
+/// generated by `method`.
+pub fn from_rng_with_method<R: Rng + ?Sized>(
+    modulus_bits: usize,
+    e: &Int,
+    method: Method,
+    rngesus: &mut R,
+) -> Result<PrivateKey> {
+    check_bit_length(modulus_bits, MIN_MODULUS_BITS)?;
+    check_public_exponent(e)?;
+    if !modulus_bits.is_multiple_of(2) {
+        return Err(Error::InvalidParameter {
+            name: "modulus_bits",
+            reason: "must be even".to_string(),
+        });
+    }
+
+    #[cfg(feature = "tracing")]
+    let _span = debug_span!(
+        "rsa",
+        modulus_bits = modulus_bits as u64,
+        method = ?method
+    )
+    .entered();
+
+    let half = modulus_bits / 2;
+    let one = Int::one();
+    let minimum_distance = one.clone() << (half - 100);
+
+    loop {
+        let (mut p, mut q, auxiliary, provable) = match method {
+            Method::ProbablePrimes => {
+                let p = gen_factor(half, e, 5 * half, rngesus, |_| true)?;
+                let q = gen_factor(half, e, 10 * half, rngesus, |q| {
+                    (&p - q).abs() > minimum_distance
+                })?;
+                (p, q, None, None)
+            }
+            Method::AuxiliaryPrimes => {
+                let (p, q, auxiliary) =
+                    auxiliary::gen_factors(modulus_bits, e, &minimum_distance, rngesus)?;
+                (p, q, Some(auxiliary), None)
+            }
+            Method::ProvablePrimes => {
+                let (p, q, provable) =
+                    provable::gen_factors(modulus_bits, e, &minimum_distance, rngesus)?;
+                (p, q, None, Some(provable))
+            }
+        };
+        if q > p {
+            std::mem::swap(&mut p, &mut q);
+        }
+
+        let p_minus_one = &p - &one;
+        let q_minus_one = &q - &one;
+        let lambda = p_minus_one.lcm(&q_minus_one);
+        let d = mod_inverse(e, &lambda).expect("e is coprime to p - 1 and q - 1");
+
+        // Happens with negligible probability, and calls for new primes.
+        if d <= one.clone() << half {
+            for secret in &mut [&mut p, &mut q] {
+                secret.wipe();
+            }
+            continue;
+        }
+
+        let key = PrivateKey {
+            public: PublicKey {
+                n: &p * &q,
+                e: e.clone(),
+            },
+            d_p: &d % &p_minus_one,
+            d_q: &d % &q_minus_one,
+            q_inv: mod_inverse(&q, &p).expect("p and q are distinct primes"),
+            d,
+            p,
+            q,
+            lambda,
+            auxiliary,
+            provable,
+        };
+        key.check()?;
+
+        return Ok(key);
+    }
+}
+
+/// Draws random odd numbers of `bits` bits until one is a prime that is at
+/// least `sqrt(2) 2^(bits - 1)`, is one more than a number coprime to `e`,
+/// and is `accepted`. Gives up after `budget` candidates that pass the size
+/// check, as B.3.3 steps 4 and 5 do.
+fn gen_factor<R, F>(bits: usize, e: &Int, budget: usize, rngesus: &mut R, accepted: F) -> Result
+where
+    R: Rng + ?Sized,
+    F: Fn(&Int) -> bool,
+{
+    let one = Int::one();
+    let mut attempts = 0;
+
+    loop {
+        let mut candidate: Int = random_bits(rngesus, bits)?;
+        candidate.set_bit(0, true);
+
+        if has_length(&candidate, bits) && accepted(&candidate) {
+            if (&candidate - &one).gcd(e) == one && is_prime_with_rng(&candidate, rngesus) {
+                return Ok(candidate);
+            }
+
+            attempts += 1;
+            if attempts >= budget {
+                candidate.wipe();
+                return Err(Error::BudgetExhausted {
+                    attempts: attempts as u64,
+                });
+            }
+        }
+
+        candidate.wipe();
+    }
+}
+
+/// Checks that `sqrt(2) 2^(bits - 1) <= number < 2^bits`, by comparing
+/// squares, which needs no irrational bound.
+fn has_length(number: &Int, bits: usize) -> bool {
+    number.bit_length() as usize == bits && number * number >= Int::one() << (2 * bits - 1)
+}
+
+fn check_public_exponent(e: &Int) -> Result<()> {
+    if e.is_even() || *e <= Int::one() << 16 || *e >= Int::one() << 256 {
+        return Err(Error::InvalidParameter {
+            name: "e",
+            reason: "must be odd, and between 2^16 and 2^256".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        from_rng, from_rng_with_exponent, from_rng_with_method, Method, PrivateKey,
+        MIN_MODULUS_BITS,
+    };
+    use error::Error;
+    use ramp::Int;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_rsa_key() {
+        let key = from_rng(MIN_MODULUS_BITS, &mut thread_rng()).unwrap();
+        let one = Int::one();
+        let (p, q) = key.primes();
+        let (d_p, d_q) = key.crt_exponents();
+
+        assert_eq!(key.n().bit_length(), 2048);
+        assert_eq!(*key.e(), Int::from(65537));
+        assert!(p - q > one.clone() << 924);
+        assert_eq!(key.d() * key.e() % key.lambda(), one);
+        assert_eq!(key.d() * key.e() % (p - 1_usize), one);
+        assert_eq!(d_p * key.e() % (p - 1_usize), one);
+        assert_eq!(d_q * key.e() % (q - 1_usize), one);
+        assert_eq!(key.crt_coefficient() * q % p, one);
+        assert!(key.auxiliary_primes().is_none());
+        assert!(key.provable_primes().is_none());
+
+        let debug = format!("{:?}", key);
+        assert!(debug.contains(&key.n().to_string()));
+        assert!(!debug.contains(&key.d().to_string()));
+    }
+
+    #[test]
+    fn test_rsa_key_with_exponent() {
+        let mut rngesus = thread_rng();
+        let e = (Int::one() << 100) + 277_usize;
+        let key = from_rng_with_exponent(2176, &e, &mut rngesus).unwrap();
+
+        assert_eq!(key.n().bit_length(), 2176);
+        assert_eq!(*key.e(), e);
+        assert!(key.check().is_ok());
+
+        for e in &[Int::from(3), Int::from(65536), Int::one() << 256] {
+            match from_rng_with_exponent(2048, e, &mut rngesus) {
+                Err(Error::InvalidParameter { name, .. }) => assert_eq!(name, "e"),
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+        match from_rng_with_exponent(2049, &e, &mut rngesus) {
+            Err(Error::InvalidParameter { name, .. }) => assert_eq!(name, "modulus_bits"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(from_rng(1024, &mut rngesus).is_err());
+    }
+
+    #[test]
+    fn test_rsa_key_with_auxiliary_primes() {
+        let e = Int::from(65537);
+        let key =
+            from_rng_with_method(3072, &e, Method::AuxiliaryPrimes, &mut thread_rng()).unwrap();
+        let (p, q) = key.primes();
+        let auxiliary = key.auxiliary_primes().unwrap();
+        let (p1, p2) = auxiliary.p();
+        let (q1, q2) = auxiliary.q();
+
+        assert_eq!(key.n().bit_length(), 3072);
+        assert_eq!(p1.bit_length(), 176);
+        assert_eq!((p - 1_usize) % p1, Int::zero());
+        assert_eq!((p + 1_usize) % p2, Int::zero());
+        assert_eq!((q - 1_usize) % q1, Int::zero());
+        assert_eq!((q + 1_usize) % q2, Int::zero());
+        assert_eq!(format!("{:?}", auxiliary), "AuxiliaryPrimes([REDACTED])");
+    }
+
+    #[test]
+    fn test_rsa_key_with_provable_primes() {
+        let e = Int::from(65537);
+        let key =
+            from_rng_with_method(2048, &e, Method::ProvablePrimes, &mut thread_rng()).unwrap();
+        let (p, q) = key.primes();
+        let provable = key.provable_primes().unwrap();
+
+        assert_eq!(key.n().bit_length(), 2048);
+        assert_eq!(provable.seed().len(), 28);
+        assert_eq!(provable.p().first_seed, provable.seed());
+        assert!(provable.p().prime == *p || provable.p().prime == *q);
+        assert!(provable.q().prime == *p || provable.q().prime == *q);
+        for construction in &[provable.p(), provable.q()] {
+            assert_eq!(
+                (&construction.prime - 1_usize) % &construction.p0.prime,
+                Int::zero()
+            );
+        }
+        assert!(provable.verify(&e).is_ok());
+        assert_eq!(format!("{:?}", provable), "ProvablePrimes([REDACTED])");
+    }
+
+    #[test]
+    fn test_rsa_check() {
+        let key = from_rng(MIN_MODULUS_BITS, &mut thread_rng()).unwrap();
+
+        let tampered = |change: &dyn Fn(&mut PrivateKey)| {
+            let mut copy = PrivateKey {
+                public: key.public.clone(),
+                d: key.d.clone(),
+                p: key.p.clone(),
+                q: key.q.clone(),
+                lambda: key.lambda.clone(),
+                d_p: key.d_p.clone(),
+                d_q: key.d_q.clone(),
+                q_inv: key.q_inv.clone(),
+                auxiliary: None,
+                provable: None,
+            };
+            change(&mut copy);
+
+            match copy.check() {
+                Err(Error::Verification { what }) => what,
+                other => panic!("unexpected result: {:?}", other),
+            }
+        };
+
+        assert_eq!(tampered(&|key| key.d_p += 2_usize), "the CRT exponents");
+        assert_eq!(tampered(&|key| key.q_inv += 1_usize), "the CRT coefficient");
+        assert_eq!(tampered(&|key| key.d += 2_usize), "the private exponent");
+        assert_eq!(tampered(&|key| key.public.n += 2_usize), "the modulus");
+        assert_eq!(
+            tampered(&|key| key.public.e = Int::from(3)),
+            "the public exponent"
+        );
+    }
+}
@@ -0,0 +1,256 @@
+//! Generates the factors of an RSA modulus from auxiliary primes, as in FIPS
+//! 186-5 appendix A.1.6.
+//!
+//! Each factor `p` is built so that `p - 1` has a large prime factor `p1`
+//! and `p + 1` has a large prime factor `p2`. The auxiliary primes are found
+//! by searching upward from random odd numbers. The Chinese remainder
+//! theorem then gives the residue `R` that is 1 modulo `2 p1` and -1 modulo
+//! `p2`, and `p` is the first probable prime at least a random starting
+//! point that is congruent to `R` modulo `2 p1 p2`.
+
+use ramp::Int;
+
+use rand::Rng;
+
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use std::fmt;
+
+use super::has_length;
+use backend::BigInt;
+use common::{is_prime_bpsw, is_prime_with_rng, mod_inverse, random_bits};
+use error::{Error, Result};
+
+/// The auxiliary primes behind the two factors of a modulus, kept for audit.
+/// They are wiped when dropped, and redacted from `Debug` output.
+pub struct AuxiliaryPrimes {
+    p1: Int,
+    p2: Int,
+    q1: Int,
+    q2: Int,
+}
+
+impl AuxiliaryPrimes {
+    /// Returns `(p1, p2)`, the large prime factors of `p - 1` and `p + 1`.
+    pub fn p(&self) -> (&Int, &Int) {
+        (&self.p1, &self.p2)
+    }
+
+    /// Returns `(q1, q2)`, the large prime factors of `q - 1` and `q + 1`.
+    pub fn q(&self) -> (&Int, &Int) {
+        (&self.q1, &self.q2)
+    }
+
+    /// Checks that the auxiliary primes are prime, of at least the length
+    /// Table A.1 sets for the modulus, and divide `p - 1`, `p + 1`, `q - 1`
+    /// and `q + 1` in turn.
+    pub(super) fn check(&self, p: &Int, q: &Int, modulus_bits: usize) -> bool {
+        let one = Int::one();
+        let (minimum, combined) = lengths(modulus_bits);
+
+        let pairs = [
+            (&self.p1, &self.p2, p - &one, p + &one),
+            (&self.q1, &self.q2, q - &one, q + &one),
+        ];
+
+        pairs.iter().all(|&(first, second, ref below, ref above)| {
+            let bits = first.bit_length() as usize + second.bit_length() as usize;
+
+            [first, second]
+                .iter()
+                .all(|&prime| prime.bit_length() as usize > minimum && is_prime_bpsw(prime))
+                && bits < combined
+                && below % first == Int::zero()
+                && above % second == Int::zero()
+        })
+    }
+}
+
+impl fmt::Debug for AuxiliaryPrimes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("AuxiliaryPrimes([REDACTED])")
+    }
+}
+
+impl Zeroize for AuxiliaryPrimes {
+    fn zeroize(&mut self) {
+        for secret in &mut [&mut self.p1, &mut self.p2, &mut self.q1, &mut self.q2] {
+            secret.wipe();
+        }
+    }
+}
+
+impl Drop for AuxiliaryPrimes {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for AuxiliaryPrimes {}
+
+/// Generates the factors `p > q` of a modulus of `modulus_bits` bits,
+/// differing by more than `minimum_distance`, along with their auxiliary
+/// primes.
+pub(super) fn gen_factors<R: Rng + ?Sized>(
+    modulus_bits: usize,
+    e: &Int,
+    minimum_distance: &Int,
+    rngesus: &mut R,
+) -> Result<(Int, Int, AuxiliaryPrimes)> {
+    let half = modulus_bits / 2;
+    let bits = auxiliary_bits(modulus_bits);
+
+    let p1 = gen_auxiliary(bits, rngesus)?;
+    let p2 = gen_auxiliary(bits, rngesus)?;
+    let mut p = gen_factor(&p1, &p2, half, e, rngesus, |_| true)?;
+
+    let q1 = gen_auxiliary(bits, rngesus)?;
+    let q2 = gen_auxiliary(bits, rngesus)?;
+    let mut q = gen_factor(&q1, &q2, half, e, rngesus, |q| {
+        (&p - q).abs() > *minimum_distance
+    })?;
+
+    let mut auxiliary = AuxiliaryPrimes { p1, p2, q1, q2 };
+    if q > p {
+        std::mem::swap(&mut p, &mut q);
+        std::mem::swap(&mut auxiliary.p1, &mut auxiliary.q1);
+        std::mem::swap(&mut auxiliary.p2, &mut auxiliary.q2);
+    }
+
+    Ok((p, q, auxiliary))
+}
+
+/// Returns the length that an auxiliary prime must exceed, and the length
+/// that each pair of them must stay below, for a modulus of `modulus_bits`
+/// bits, from Table A.1.
+fn lengths(modulus_bits: usize) -> (usize, usize) {
+    match modulus_bits {
+        0..=3071 => (140, 1007),
+        3072..=4095 => (170, 1518),
+        _ => (200, 2030),
+    }
+}
+
+/// Returns the length of the auxiliary primes generated for a modulus of
+/// `modulus_bits` bits: the first multiple of 16 above the minimum.
+fn auxiliary_bits(modulus_bits: usize) -> usize {
+    (lengths(modulus_bits).0 / 16 + 1) * 16
+}
+
+/// Returns the first probable prime at least a random odd number of `bits`
+/// bits, drawing a new starting point should the search overflow.
+fn gen_auxiliary<R: Rng + ?Sized>(bits: usize, rngesus: &mut R) -> Result {
+    loop {
+        let mut candidate: Int = random_bits(rngesus, bits)?;
+        candidate.set_bit(bits as u32 - 1, true);
+        candidate.set_bit(0, true);
+
+        while candidate.bit_length() as usize == bits {
+            if is_prime_with_rng(&candidate, rngesus) {
+                return Ok(candidate);
+            }
+            candidate += 2_usize;
+        }
+
+        candidate.wipe();
+    }
+}
+
+/// Computes a probable prime factor of `bits` bits from the auxiliary primes
+/// `r1` and `r2`, such that `r1` divides `p - 1`, `r2` divides `p + 1`, and
+/// `p - 1` is coprime to `e`. Gives up after `5 bits` candidates from one
+/// starting point.
+fn gen_factor<R, F>(
+    r1: &Int,
+    r2: &Int,
+    bits: usize,
+    e: &Int,
+    rngesus: &mut R,
+    accepted: F,
+) -> Result
+where
+    R: Rng + ?Sized,
+    F: Fn(&Int) -> bool,
+{
+    let one = Int::one();
+    let twice_r1: Int = r1 << 1;
+    let step = &twice_r1 * r2;
+
+    // R = 1 (mod 2 r1) and R = -1 (mod r2).
+    let residue = match (mod_inverse(r2, &twice_r1), mod_inverse(&twice_r1, r2)) {
+        (Some(r2_inverse), Some(r1_inverse)) => r2_inverse * r2 - r1_inverse * &twice_r1,
+        _ => {
+            return Err(Error::InvalidParameter {
+                name: "auxiliary primes",
+                reason: "2 r1 and r2 must be coprime".to_string(),
+            })
+        }
+    };
+
+    loop {
+        let mut start: Int = random_bits(rngesus, bits)?;
+        if !has_length(&start, bits) {
+            start.wipe();
+            continue;
+        }
+
+        let mut offset = (&residue - &start) % &step;
+        if offset < Int::zero() {
+            offset += &step;
+        }
+        let mut candidate = start + offset;
+
+        for attempt in 1.. {
+            if candidate.bit_length() as usize > bits || !accepted(&candidate) {
+                break;
+            }
+            if (&candidate - &one).gcd(e) == one && is_prime_with_rng(&candidate, rngesus) {
+                return Ok(candidate);
+            }
+            if attempt >= 5 * bits {
+                candidate.wipe();
+                return Err(Error::BudgetExhausted {
+                    attempts: attempt as u64,
+                });
+            }
+
+            candidate += &step;
+        }
+
+        candidate.wipe();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{auxiliary_bits, gen_auxiliary, gen_factor, lengths};
+    use common::is_prime;
+    use ramp::Int;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_auxiliary_factor() {
+        let mut rngesus = thread_rng();
+        let e = Int::from(65537);
+        let r1 = gen_auxiliary(144, &mut rngesus).unwrap();
+        let r2 = gen_auxiliary(144, &mut rngesus).unwrap();
+        let p = gen_factor(&r1, &r2, 1024, &e, &mut rngesus, |_| true).unwrap();
+
+        assert_eq!(r1.bit_length(), 144);
+        assert!(is_prime(&r1) && is_prime(&r2) && is_prime(&p));
+        assert_eq!(p.bit_length(), 1024);
+        assert_eq!((&p - 1_usize) % &r1, Int::zero());
+        assert_eq!((&p + 1_usize) % &r2, Int::zero());
+
+        assert!(gen_factor(&r1, &r1, 1024, &e, &mut rngesus, |_| true).is_err());
+    }
+
+    #[test]
+    fn test_auxiliary_lengths() {
+        assert_eq!(auxiliary_bits(2048), 144);
+        assert_eq!(auxiliary_bits(3072), 176);
+        assert_eq!(auxiliary_bits(4096), 208);
+        assert_eq!(auxiliary_bits(8192), 208);
+        assert_eq!(lengths(2560), (140, 1007));
+    }
+}
@@ -0,0 +1,226 @@
+//! Generates the factors of an RSA modulus as provable primes, as in FIPS
+//! 186-5 appendix A.1.2.
+//!
+//! A random seed of twice the security strength of the modulus is drawn,
+//! and `p` and then `q` are derived from it in turn with
+//! `provable::construct_prime()`, each starting from the seed that the last
+//! left behind. The seed and both constructions are kept, so that the
+//! primes can be derived again by anyone who is given them.
+
+use ramp::Int;
+
+use rand::Rng;
+
+use sha2::{Sha256, Sha512};
+
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use std::fmt;
+
+use backend::BigInt;
+use common::random_bytes;
+use error::{Error, Result};
+use provable::{construct_prime, ConstructedPrime};
+
+/// The provable primes behind the two factors of a modulus, with the seed
+/// they were derived from. They are wiped when dropped, and redacted from
+/// `Debug` output.
+pub struct ProvablePrimes {
+    seed: Vec<u8>,
+    p: ConstructedPrime,
+    q: ConstructedPrime,
+}
+
+impl ProvablePrimes {
+    /// Returns the random seed that both primes were derived from.
+    pub fn seed(&self) -> &[u8] {
+        &self.seed
+    }
+
+    /// Returns the construction of `p`.
+    pub fn p(&self) -> &ConstructedPrime {
+        &self.p
+    }
+
+    /// Returns the construction of `q`.
+    pub fn q(&self) -> &ConstructedPrime {
+        &self.q
+    }
+
+    /// Derives both constructions again from the seed, with the public
+    /// exponent `e`, retrying `q` as generation does until it is far enough
+    /// from `p`, and checks that both match.
+    ///
+    /// Fails with `Error::Verification` otherwise.
+    pub fn verify(&self, e: &Int) -> Result<()> {
+        let modulus_bits = 2 * self.p.prime.bit_length() as usize;
+        if self.seed.len() != seed_bytes(modulus_bits) {
+            return Err(Error::Verification {
+                what: "the provable primes' seed",
+            });
+        }
+
+        let minimum_distance = Int::one() << (modulus_bits / 2 - 100);
+        match derive(modulus_bits, self.seed.clone(), e, &minimum_distance) {
+            Ok(ref derived) if derived.p == self.p && derived.q == self.q => Ok(()),
+            _ => Err(Error::Verification {
+                what: "the provable primes",
+            }),
+        }
+    }
+
+    /// Checks that the constructions are of `p` and `q`, and derive again
+    /// from the seed.
+    pub(super) fn check(&self, p: &Int, q: &Int, e: &Int) -> bool {
+        let matches = (self.p.prime == *p && self.q.prime == *q)
+            || (self.p.prime == *q && self.q.prime == *p);
+
+        matches && self.verify(e).is_ok()
+    }
+}
+
+impl fmt::Debug for ProvablePrimes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ProvablePrimes([REDACTED])")
+    }
+}
+
+impl Zeroize for ProvablePrimes {
+    fn zeroize(&mut self) {
+        self.seed.zeroize();
+
+        for construction in &mut [&mut self.p, &mut self.q] {
+            construction.first_seed.zeroize();
+            construction.prime_seed.zeroize();
+            construction.p0.input_seed.zeroize();
+            construction.p0.prime_seed.zeroize();
+
+            for secret in &mut [
+                &mut construction.prime,
+                &mut construction.p1,
+                &mut construction.p2,
+                &mut construction.p0.prime,
+            ] {
+                secret.wipe();
+            }
+            for secret in &mut construction.p0.intermediate_primes {
+                secret.wipe();
+            }
+        }
+    }
+}
+
+impl Drop for ProvablePrimes {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for ProvablePrimes {}
+
+/// Generates the factors of a modulus of `modulus_bits` bits, differing by
+/// more than `minimum_distance`, along with their constructions. The
+/// factors are returned in the order they were derived, not by size.
+pub(super) fn gen_factors<R: Rng + ?Sized>(
+    modulus_bits: usize,
+    e: &Int,
+    minimum_distance: &Int,
+    rngesus: &mut R,
+) -> Result<(Int, Int, ProvablePrimes)> {
+    let seed = random_bytes(rngesus, 8 * seed_bytes(modulus_bits))?.to_vec();
+    let primes = derive(modulus_bits, seed, e, minimum_distance)?;
+
+    Ok((primes.p.prime.clone(), primes.q.prime.clone(), primes))
+}
+
+/// Derives `p` from `seed`, then `q` from the seed that `p` left, and again
+/// from the seed that each `q` left until `q` differs from `p` by more than
+/// `minimum_distance`.
+fn derive(
+    modulus_bits: usize,
+    seed: Vec<u8>,
+    e: &Int,
+    minimum_distance: &Int,
+) -> Result<ProvablePrimes> {
+    let p = construct(modulus_bits, &seed, e)?;
+    let mut working_seed = p.prime_seed.clone();
+
+    loop {
+        let mut q = construct(modulus_bits, &working_seed, e)?;
+        working_seed.zeroize();
+
+        if (&p.prime - &q.prime).abs() > *minimum_distance {
+            return Ok(ProvablePrimes { seed, p, q });
+        }
+
+        working_seed = q.prime_seed.clone();
+        q.prime.wipe();
+    }
+}
+
+/// Runs the construction of a factor of a modulus of `modulus_bits` bits,
+/// with a hash of at least its security strength.
+fn construct(modulus_bits: usize, seed: &[u8], e: &Int) -> Result<ConstructedPrime> {
+    let half = modulus_bits / 2;
+
+    if security_strength(modulus_bits) > 128 {
+        construct_prime::<Sha512>(half, 1, 1, seed, e)
+    } else {
+        construct_prime::<Sha256>(half, 1, 1, seed, e)
+    }
+}
+
+/// Returns the length in bytes of the seed: twice the security strength.
+fn seed_bytes(modulus_bits: usize) -> usize {
+    2 * security_strength(modulus_bits) / 8
+}
+
+/// Returns the security strength of a modulus of `modulus_bits` bits, as
+/// estimated by SP 800-56B.
+fn security_strength(modulus_bits: usize) -> usize {
+    match modulus_bits {
+        0..=3071 => 112,
+        3072..=4095 => 128,
+        4096..=6143 => 152,
+        6144..=8191 => 176,
+        _ => 200,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{construct, gen_factors, ProvablePrimes};
+    use error::Error;
+    use ramp::Int;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_rsa_provable_primes_verify() {
+        let e = Int::from(65537);
+        let minimum_distance = Int::one() << 412;
+        let (p, q, primes) = gen_factors(1024, &e, &minimum_distance, &mut thread_rng()).unwrap();
+
+        assert_eq!((&primes.p.prime, &primes.q.prime), (&p, &q));
+        assert!(primes.verify(&e).is_ok());
+
+        // Each construction derives again on its own, but not in this order.
+        let swapped = ProvablePrimes {
+            seed: primes.seed.clone(),
+            p: primes.q.clone(),
+            q: primes.p.clone(),
+        };
+        // A q from one retry too many, which is far enough from p as well.
+        let retried = ProvablePrimes {
+            seed: primes.seed.clone(),
+            p: primes.p.clone(),
+            q: construct(1024, &primes.q.prime_seed, &e).unwrap(),
+        };
+
+        for tampered in &[swapped, retried] {
+            match tampered.verify(&e) {
+                Err(Error::Verification { .. }) => {}
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+    }
+}
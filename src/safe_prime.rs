@@ -2,73 +2,206 @@
 
 use ramp::Int;
 
-use rand::rngs::OsRng;
+use rand::{thread_rng, Rng, RngCore};
 
+use std::time::Instant;
+
+use backend::BigInt;
+use certificate::{certify, verify_certificate, Certificate, CertifiedPrime};
+use common::{
+    check_bit_length, gen_prime_recorded, is_prime_recorded, is_prime_with_rng, three, two,
+    MIN_BIT_LENGTH,
+};
 pub use common::{gen_prime, is_prime};
-use common::{three, two};
+use entropy;
 use error::{Error, Result};
+use provable::{maurer_factor, maurer_last_step, trial_divisors};
+use report::GenerationReport;
 
 /// Constructs a new `SafePrime` with a size of `bit_length` bits.
 ///
-/// This will initialize an `OsRng` instance and call the
-/// `SafePrime::from_rng()` method.
+/// This will initialize an `OsRng` instance, checked by the health tests
+/// of the `entropy` module, and call the `SafePrime::from_rng()` method.
 ///
 /// Note: the `bit_length` MUST be at least 512-bits.
 pub fn new(bit_length: usize) -> Result {
-    if bit_length < 512 {
-        Err(Error::BitLength(bit_length))
-    } else {
-        let mut rngesus = OsRng::new()?;
-        Ok(from_rng(bit_length, &mut rngesus)?)
-    }
+    check_bit_length(bit_length, MIN_BIT_LENGTH)?;
+
+    let mut rngesus = entropy::os()?;
+    from_rng(bit_length, &mut rngesus)
 }
 
 /// Checks if number is a safe prime
 pub fn is_safe_prime(candidate: &Int) -> bool {
+    is_safe_prime_recorded(
+        candidate,
+        &mut thread_rng(),
+        &mut GenerationReport::default(),
+    )
+}
+
+fn is_safe_prime_recorded<R: RngCore + ?Sized>(
+    candidate: &Int,
+    rngesus: &mut R,
+    report: &mut GenerationReport,
+) -> bool {
     // according to https://eprint.iacr.org/2003/186.pdf
     // a safe prime is congruent to 2 mod 3
     if (candidate % three()) == two() {
-        if is_prime(&candidate) {
+        if is_prime_recorded(candidate, rngesus, report) {
             // a safe prime satisfies (p-1)/2 is prime. Since a
             // prime is odd, We just need to divide by 2
             let candidate_p = candidate >> 1;
-            return is_prime(&candidate_p);
+            return is_prime_recorded(&candidate_p, rngesus, report);
         }
     }
     false
 }
 
 /// Constructs a new `SafePrime` with the size of `bit_length` bits, sourced
-/// from an already-initialized `OsRng`.
-pub fn from_rng(bit_length: usize, mut rngesus: &mut OsRng) -> Result {
-    if bit_length < 512 {
-        Err(Error::BitLength(bit_length))
-    } else {
-        let mut candidate: Int;
-
-        loop {
-            candidate = gen_prime(bit_length, &mut rngesus)?;
-
-            if is_safe_prime(&candidate) {
-                break;
-            }
+/// from an already-initialized random number generator.
+pub fn from_rng<R: Rng + ?Sized>(bit_length: usize, rngesus: &mut R) -> Result {
+    from_rng_recorded(bit_length, rngesus, &mut GenerationReport::default())
+}
 
-            candidate <<= 1;
-            candidate += 1;
+/// Constructs a new `SafePrime` exactly like `from_rng()`, additionally
+/// returning a `GenerationReport` covering every prime tested along the way.
+pub fn from_rng_with_report<R: Rng + ?Sized>(
+    bit_length: usize,
+    rngesus: &mut R,
+) -> Result<(Int, GenerationReport)> {
+    let started = Instant::now();
+    let mut report = GenerationReport::default();
+    let prime = from_rng_recorded(bit_length, rngesus, &mut report)?;
+    report.total_time = started.elapsed();
 
-            if is_prime(&candidate) {
-                break;
-            }
+    #[cfg(feature = "tracing")]
+    report.trace("safe_prime");
+
+    Ok((prime, report))
+}
+
+fn from_rng_recorded<R: Rng + ?Sized>(
+    bit_length: usize,
+    rngesus: &mut R,
+    report: &mut GenerationReport,
+) -> Result {
+    check_bit_length(bit_length, MIN_BIT_LENGTH)?;
+
+    #[cfg(feature = "tracing")]
+    let _span = debug_span!("safe_prime", bit_length = bit_length as u64).entered();
+
+    let mut candidate: Int;
+
+    loop {
+        candidate = gen_prime_recorded(bit_length, rngesus, report)?;
+
+        if is_safe_prime_recorded(&candidate, rngesus, report) {
+            break;
         }
 
-        Ok(candidate)
+        candidate <<= 1;
+        candidate += 1;
+
+        if is_prime_recorded(&candidate, rngesus, report) {
+            break;
+        }
+
+        candidate.wipe();
     }
+
+    Ok(candidate)
+}
+
+/// Constructs a new `SafePrime` with a size of `bit_length` bits, along
+/// with a certificate of its primality.
+///
+/// This will initialize an `OsRng` instance, checked by the health tests
+/// of the `entropy` module, and call the `from_rng_certified()` method.
+///
+/// Note: the `bit_length` MUST be at least 512-bits.
+pub fn new_certified(bit_length: usize) -> Result<CertifiedPrime> {
+    check_bit_length(bit_length, MIN_BIT_LENGTH)?;
+
+    let mut rngesus = entropy::os()?;
+    from_rng_certified(bit_length, &mut rngesus)
+}
+
+/// Constructs a new `SafePrime` with a size of `bit_length` bits, along
+/// with a certificate of its primality, sourced from an
+/// already-initialized random number generator.
+///
+/// `(p - 1) / 2` is drawn as a provable prime with Maurer's algorithm, as
+/// in `provable::maurer()`, so that `p - 1` is fully factored, and `p` gets
+/// a `Pratt` certificate built on the one of `(p - 1) / 2`. The prime that
+/// Maurer's algorithm builds `(p - 1) / 2` on is only drawn once: when `p`
+/// turns out composite, only its last step is run again. Candidates whose
+/// `p` has a small factor are skipped before they are proven prime.
+///
+/// It is slower than `from_rng()`, but the result can be checked with
+/// `verify_certified()` alone, which proves both `p` and `(p - 1) / 2`
+/// prime.
+pub fn from_rng_certified<R: Rng + ?Sized>(
+    bit_length: usize,
+    rngesus: &mut R,
+) -> Result<CertifiedPrime> {
+    check_bit_length(bit_length, MIN_BIT_LENGTH)?;
+
+    let factor = maurer_factor(bit_length - 1, rngesus)?;
+    let divisors = trial_divisors(bit_length);
+
+    loop {
+        // 2 q + 1 is divisible by an odd prime d when q = (d - 1) / 2 mod d.
+        let q = maurer_last_step(bit_length - 1, &factor, rngesus, |q| {
+            divisors
+                .iter()
+                .skip(1)
+                .any(|&prime| q % prime as usize == (prime / 2) as usize)
+        })?;
+        let prime = (&q.prime << 1) + 1_usize;
+
+        if is_prime_with_rng(&prime, rngesus) {
+            let certificate = certify(&prime, &[(Certificate::Small(2), 1), (q.certificate, 1)])?;
+            return Ok(CertifiedPrime { prime, certificate });
+        }
+    }
+}
+
+/// Checks that `certified` proves both its prime `p` and `(p - 1) / 2`
+/// prime, as `from_rng_certified()` returns it, without any randomness.
+///
+/// Fails with `Error::Verification` if the certificate is not of `p`, or
+/// does not rely on a certificate of `(p - 1) / 2`, or does not hold.
+pub fn verify_certified(certified: &CertifiedPrime) -> Result<()> {
+    let p = &certified.prime;
+    let q = (p - 1_usize) >> 1;
+    let proves_q = match certified.certificate {
+        Certificate::Small(_) | Certificate::Ecpp(_) => false,
+        Certificate::Pratt(ref pratt) => pratt
+            .factors
+            .iter()
+            .any(|factor| factor.certificate.n() == q),
+        Certificate::Pocklington(ref pocklington) => pocklington
+            .factors
+            .iter()
+            .any(|factor| factor.certificate.n() == q),
+    };
+
+    if certified.certificate.n() != *p || p.is_even() || !proves_q {
+        return Err(Error::Verification {
+            what: "the safe prime certificate",
+        });
+    }
+
+    verify_certificate(&certified.certificate)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{is_safe_prime, new};
+    use super::{from_rng_certified, is_safe_prime, new, verify_certified};
+    use certificate::{certify, verify_certificate, Certificate, CertifiedPrime};
     use ramp::Int;
+    use rand::thread_rng;
 
     #[test]
     fn test_safe_prime_bit_length_too_small() {
@@ -109,4 +242,45 @@ mod tests {
         let p4 = Int::from_str_radix("4806876214089177439121678559764069543282270755154137981051366776821330958611719328037311759924923156830623290278296826263863902327008664143707117531049168010908663795201825132050017581985031718536424081509084930569115857201636971728388275433540277562846153879803474020036767852693656753257597801227199822164846876100177774044259379232968071371318658371230787073384750022830829873718254139779006439569882904712552834431199870749249168775012460891012776977366721903", 10).unwrap();
         assert!(is_safe_prime(&p4));
     }
+
+    #[test]
+    fn test_safe_prime_certified() {
+        let certified = from_rng_certified(512, &mut thread_rng()).unwrap();
+
+        assert_eq!(certified.prime.bit_length(), 512);
+        assert_eq!(certified.certificate.n(), certified.prime);
+        assert!(is_safe_prime(&certified.prime));
+        assert!(verify_certificate(&certified.certificate).is_ok());
+        assert!(verify_certified(&certified).is_ok());
+        match certified.certificate {
+            Certificate::Pratt(ref pratt) => assert_eq!(pratt.factors.len(), 2),
+            ref other => panic!("unexpected certificate: {:?}", other),
+        }
+
+        let text = certified.certificate.to_string();
+        assert_eq!(text.parse::<Certificate>().unwrap(), certified.certificate);
+    }
+
+    #[test]
+    fn test_safe_prime_verify_certified() {
+        let small = |n| (Certificate::Small(n), 1);
+        let certified = |n: u32, factors: &[(Certificate, u32)]| CertifiedPrime {
+            prime: Int::from(n),
+            certificate: certify(&Int::from(n), factors).unwrap(),
+        };
+
+        // 1031 - 1 = 2 * 5 * 103, and 2063 - 1 = 2 * 1031.
+        let q = certified(1031, &[small(2), small(5), small(103)]);
+        let p = certified(2063, &[small(2), (q.certificate.clone(), 1)]);
+        assert!(verify_certified(&p).is_ok());
+        // 515 is not prime.
+        assert!(verify_certified(&q).is_err());
+
+        // The certificate must be of the prime itself.
+        let mismatched = CertifiedPrime {
+            prime: Int::from(2063),
+            certificate: q.certificate,
+        };
+        assert!(verify_certified(&mismatched).is_err());
+    }
 }
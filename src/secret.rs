@@ -0,0 +1,112 @@
+//! Holds generated primes that must stay secret.
+//!
+//! A `SecretPrime` wipes its value when dropped and never prints it: its
+//! `Debug` output is redacted, and reading the prime takes an explicit call
+//! to `expose_secret()`. The generators in this module use the
+//! `constant_time` tests, and wipe every candidate they reject.
+//!
+//! Wiping clears the buffer the prime currently occupies. Copies made by
+//! operations on the exposed value, or by `ramp` while resizing, are out of
+//! reach.
+
+use ramp::Int;
+
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use std::fmt;
+
+use backend::BigInt;
+use constant_time;
+use error::Result;
+
+use rand::Rng;
+
+/// A prime number that is wiped from memory when dropped.
+pub struct SecretPrime {
+    prime: Int,
+}
+
+impl SecretPrime {
+    /// Returns the prime itself.
+    pub fn expose_secret(&self) -> &Int {
+        &self.prime
+    }
+}
+
+impl From<Int> for SecretPrime {
+    fn from(prime: Int) -> SecretPrime {
+        SecretPrime { prime }
+    }
+}
+
+impl fmt::Debug for SecretPrime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SecretPrime([REDACTED])")
+    }
+}
+
+impl Zeroize for SecretPrime {
+    fn zeroize(&mut self) {
+        self.prime.wipe();
+    }
+}
+
+impl Drop for SecretPrime {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SecretPrime {}
+
+/// Constructs a new secret prime number with a size of `bit_length` bits.
+///
+/// This will initialize an `OsRng` instance, checked by the health tests
+/// of the `entropy` module, and call the `from_rng()` function.
+///
+/// Note: the `bit_length` MUST be at least 512-bits, and at most
+/// `constant_time::MAX_BIT_LENGTH`.
+pub fn new(bit_length: usize) -> Result<SecretPrime> {
+    constant_time::new(bit_length).map(SecretPrime::from)
+}
+
+/// Constructs a new secret prime number with the size of `bit_length` bits,
+/// sourced from an already-initialized random number generator.
+pub fn from_rng<R: Rng + ?Sized>(bit_length: usize, rngesus: &mut R) -> Result<SecretPrime> {
+    constant_time::from_rng(bit_length, rngesus).map(SecretPrime::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_rng, SecretPrime};
+    use common;
+    use ramp::Int;
+    use rand::rngs::OsRng;
+    use zeroize::Zeroize;
+
+    #[test]
+    fn test_secret_prime() {
+        let mut rngesus = OsRng::new().unwrap();
+        let p = from_rng(512, &mut rngesus).unwrap();
+
+        assert_eq!(p.expose_secret().bit_length(), 512);
+        assert!(common::is_prime(p.expose_secret()));
+    }
+
+    #[test]
+    fn test_secret_prime_debug_is_redacted() {
+        let p = SecretPrime::from(Int::from(65537));
+
+        assert_eq!(format!("{:?}", p), "SecretPrime([REDACTED])");
+        assert!(!format!("{:?}", p).contains("65537"));
+    }
+
+    #[test]
+    fn test_secret_prime_zeroize() {
+        let mut p =
+            SecretPrime::from(Int::from_str_radix("fedcba9876543210fedcba987", 16).unwrap());
+        p.zeroize();
+
+        assert_eq!(*p.expose_secret(), Int::zero());
+    }
+}
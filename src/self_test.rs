@@ -0,0 +1,565 @@
+//! Known-answer self-tests of the primality engine.
+//!
+//! `run()` checks a backend against vectors embedded in the crate:
+//!
+//! 1. `mod_exp`: modular exponentiations with known results.
+//!
+//! 2. `is_prime`: known primes, including Mersenne primes and the largest of
+//!    the small primes used for trial division, must be accepted. Carmichael
+//!    numbers, strong pseudoprimes to fixed bases and products of large
+//!    primes must be rejected.
+//!
+//! 3. `miller_rabin`: each strong pseudoprime must pass Miller-Rabin to the
+//!    bases it is known to fool, and fail to the first base that catches it.
+//!
+//! 4. `generation`: a prime generated from an HMAC_DRBG with fixed seed
+//!    material must come out exactly as expected.
+//!
+//! The bases of the probable prime tests also come from a seeded HMAC_DRBG,
+//! so a run is entirely deterministic. Vectors wider than a fixed-width
+//! backend can hold are skipped.
+//!
+//! The first failing vector is returned as `Error::SelfTest`, naming the
+//! `Check` and the index of the vector within it.
+//!
+//! With the `self-test` feature enabled, the first call that generates a
+//! prime with a given backend runs the self-tests against that backend
+//! before anything else, and every generator using it fails with the same
+//! error from then on if they did not pass. Each backend is tested on its
+//! own, so a backend that fails does not take the others down with it.
+//! Other backends can be checked with `run()` directly.
+
+#[cfg(feature = "std")]
+use ramp::Int;
+
+use rand::{ErrorKind, RngCore};
+
+use alloc::vec::Vec;
+use core::fmt;
+
+#[cfg(feature = "self-test")]
+use core::any::TypeId;
+#[cfg(feature = "self-test")]
+use core::hash::{Hash, Hasher};
+#[cfg(feature = "self-test")]
+use core::hint;
+#[cfg(feature = "self-test")]
+use core::sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering};
+
+use backend::BigInt;
+use common::{bytes_bit_length, gen_prime_untested, is_prime_with_rng, miller_rabin_with_bases};
+use drbg::HmacDrbg;
+use error::{Error, Result};
+use report::GenerationReport;
+
+/// One of the groups of known-answer vectors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Check {
+    /// Modular exponentiation by the backend.
+    ModExp,
+    /// Accepting primes, and rejecting composites, with `is_prime()`.
+    IsPrime,
+    /// Miller-Rabin to fixed bases.
+    MillerRabin,
+    /// Seeded prime generation.
+    Generation,
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Check::ModExp => write!(f, "mod_exp"),
+            Check::IsPrime => write!(f, "is_prime"),
+            Check::MillerRabin => write!(f, "miller_rabin"),
+            Check::Generation => write!(f, "generation"),
+        }
+    }
+}
+
+/// `(base, exponent, modulus, result)`, in hexadecimal.
+const MOD_EXP: &[(&str, &str, &str, &str)] = &[
+    ("04", "0d", "01f1", "01bd"),
+    (
+        "02",
+        "7ffffffffffffffffffffffffffffffe",
+        "7fffffffffffffffffffffffffffffff",
+        "01",
+    ),
+    (
+        "24f7408a71a1f3d76c58bca5bc731dd6f91ae57c60ea264fecde8b73482c3495\
+         cba859a099c566c674f47996aed5ce80fb85a89c1b9c7ec8ca032fa5872c3a65",
+        "6eae951bb477caf2a5f274ab5c718332ee00fefa49e0c5518b2de38c133d33ea\
+         69c16089174d94060cacc82cd69eee90e724fe81f8a43b14ccd8904ef5a965f9",
+        "f00fdb1c5e85d8b3fbd5a293a18bb42f0912b8e383d833a9a269d132d5a5167b\
+         bf2eb110d7881003aa59ce1e9e293641ef70b4c0177344a39b95f239ae97d9db",
+        "786a812689ec30dbb61ef222dc81886e44f420f7c47cfe923f878627b8515f8e\
+         fc8902e4842c4e429e102a069f412ea2f9cd38ab46cc6113df912a77caca2007",
+    ),
+    (
+        "2eb62ce74d035f4ded2fd4695eec1f7d00de9e7bdf3d3d2bf762e4455597b2ac\
+         b07272715cafab7b1a367a2a15039697fc35550bc51c92fca2ed11a5156277cd\
+         f52869d00900ac8416996d5a9b9ec6e3e7716097fb3775e09dd574d258df6f5a\
+         ebf7d37fd7db4b8b866255d2bc1a8608723b24877954d7616fab642227b2554d",
+        "d40a12ace62a8097c08ae60f3f3b5a6fe0cd47c8e90775f6057dc7a1d3c38e09\
+         f95fef100583ea1fce5035b76913ae13f5152890b29e37580611eecd8fcbadf7\
+         e7b3a0f1ae8a338775a18ce31ef94d6495256c52d2010ae33aa023f41742d4bf\
+         f95e7a2edd832fb9293c016e492caf2e639f969eea35d95b28dd86112284e8bc",
+        "e8dd7cad1a2fb79863b7e6c6cc32d94a23e12368dfaab154beaf95ccb620d9a0\
+         1894934cfb6e77edcb46d03f636e22c55e6b64bfb749bdeece9af45cb8c92a67\
+         ced7384bf4eac10f5dab7e5e834c0f2571f36ec219b61d4485106fdcded4b961\
+         cae9a673d45434de52be468a63b18a93d991a72b7bc694eb04e5f7cfcc0b9271",
+        "791c2ae74b400c85c1d6f50c310b7f5f2c5f71c0cf2f60f8934916f36b59fb39\
+         c6323b5ff9f018aa6870a4cdad610794debec57796f1bbbc9a80cdee0f90fe00\
+         b355ffe72670c6143018d8f48515c801fae150fef4603cfed0585fe5f5cc9ae4\
+         884c31493fad862287a4611e64702ac2b345252b5ec844795e618e8e5530784e",
+    ),
+];
+
+/// Primes, in hexadecimal: 2, 3, the largest small prime, the first prime
+/// after it, and 2^255 - 19.
+const PRIMES: &[&str] = &[
+    "02",
+    "03",
+    "45c7",
+    "45d9",
+    "7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffed",
+];
+
+/// Exponents of Mersenne primes, `2^p - 1`.
+const MERSENNE_EXPONENTS: &[usize] = &[61, 89, 107, 127, 521, 607, 1279];
+
+/// Composites, in hexadecimal: the Carmichael numbers 561, 41041, 825265,
+/// 321197185 and 5394826801, a Carmichael number whose three prime factors
+/// are all above 2^62, and `(2^127 - 1) * (2^521 - 1)`.
+const COMPOSITES: &[&str] = &[
+    "0231",
+    "a051",
+    "0c97b1",
+    "13251481",
+    "01418e8631",
+    "51000000000013956c000000019409bd4000000ada9e6b99",
+    "fffffffffffffffffffffffffffffffdffffffffffffffffffffffffffffffff\
+     ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff\
+     80000000000000000000000000000001",
+];
+
+/// `(number, bases it fools, base that catches it)`, for strong
+/// pseudoprimes.
+const STRONG_PSEUDOPRIMES: &[(&str, &[u32], u32)] = &[
+    ("bfa17dc7", &[2, 3, 5, 7], 11),
+    (
+        "351591274f9af9fb",
+        &[2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31],
+        37,
+    ),
+    (
+        "437ae92817f9fc85b7e5",
+        &[2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37],
+        41,
+    ),
+    ("51000000000013956c000000019409bd4000000ada9e6b99", &[19], 2),
+];
+
+/// Seed material of the HMAC_DRBGs used by the self-tests.
+const ENTROPY_INPUT: &[u8] = b"pumpkin known-answer entropy in.";
+const NONCE: &[u8] = b"self-test nonce.";
+const PERSONALIZATION: &[u8] = b"pumpkin::self_test";
+
+/// The 512-bit prime generated from `ENTROPY_INPUT`, `NONCE` and
+/// `PERSONALIZATION`, in hexadecimal.
+const GENERATED_512: &str = "cd21644b7436d3be74fba8c726c84a4bf8483464a5e198c42a5ac649f92188fe\
+     93d2461b9b9e89aa96b3798c1574a448fa6533e54b3a22a12991ede19ee1eaa7";
+
+#[cfg(feature = "self-test")]
+const UNTESTED: u8 = 0;
+#[cfg(feature = "self-test")]
+const RUNNING: u8 = 1;
+#[cfg(feature = "self-test")]
+const PASSED: u8 = 2;
+#[cfg(feature = "self-test")]
+const FAILED: u8 = 3;
+
+/// The number of backends whose outcomes are remembered. Any further
+/// backend is tested on every use instead.
+#[cfg(feature = "self-test")]
+const SLOT_COUNT: usize = 16;
+
+/// The outcome of the self-tests against one backend.
+#[cfg(feature = "self-test")]
+struct Slot {
+    /// A hash of the `TypeId` of the backend, or zero while the slot is free.
+    backend: AtomicU64,
+    state: AtomicU8,
+    /// Where the first run failed, kept for the callers that come after it.
+    failed_check: AtomicUsize,
+    failed_vector: AtomicUsize,
+}
+
+#[cfg(feature = "self-test")]
+static SLOTS: [Slot; SLOT_COUNT] = [const {
+    Slot {
+        backend: AtomicU64::new(0),
+        state: AtomicU8::new(UNTESTED),
+        failed_check: AtomicUsize::new(0),
+        failed_vector: AtomicUsize::new(0),
+    }
+}; SLOT_COUNT];
+
+#[cfg(feature = "self-test")]
+const CHECKS: [Check; 4] = [
+    Check::ModExp,
+    Check::IsPrime,
+    Check::MillerRabin,
+    Check::Generation,
+];
+
+/// Runs every known-answer test against `ramp::Int`.
+#[cfg(feature = "std")]
+pub fn self_test() -> Result<()> {
+    run::<Int>()
+}
+
+/// Runs every known-answer test against the backend `B`, failing with
+/// `Error::SelfTest` on the first vector that does not give the expected
+/// answer.
+pub fn run<B: BigInt>() -> Result<()> {
+    check_mod_exp::<B>(MOD_EXP)?;
+
+    let mersennes: Vec<Vec<u8>> = MERSENNE_EXPONENTS.iter().map(|&p| mersenne(p)).collect();
+    let primes: Vec<Vec<u8>> = PRIMES.iter().map(|hex| unhex(hex)).collect();
+    let composites: Vec<Vec<u8>> = COMPOSITES.iter().map(|hex| unhex(hex)).collect();
+    check_is_prime::<B>(&primes, &mersennes, &composites)?;
+
+    check_miller_rabin::<B>(&mersennes, STRONG_PSEUDOPRIMES)?;
+    check_generation::<B>(512, GENERATED_512)
+}
+
+/// Runs the self-tests against `B` the first time it is called with `B` in
+/// this process, and returns their outcome from then on.
+///
+/// Callers on other threads wait for a run in progress to finish.
+#[cfg(feature = "self-test")]
+pub(crate) fn on_first_use<B: BigInt>() -> Result<()> {
+    let slot = match slot_of::<B>() {
+        Some(slot) => slot,
+        None => return run::<B>(),
+    };
+
+    loop {
+        match slot
+            .state
+            .compare_exchange(UNTESTED, RUNNING, Ordering::Acquire, Ordering::Acquire)
+        {
+            Ok(_) => {
+                let outcome = run::<B>();
+                let state = match outcome {
+                    Err(Error::SelfTest { check, vector }) => {
+                        let index = CHECKS.iter().position(|&known| known == check);
+                        slot.failed_check
+                            .store(index.unwrap_or(0), Ordering::Relaxed);
+                        slot.failed_vector.store(vector, Ordering::Relaxed);
+                        FAILED
+                    }
+                    _ => PASSED,
+                };
+                slot.state.store(state, Ordering::Release);
+
+                return outcome;
+            }
+            Err(PASSED) => return Ok(()),
+            Err(FAILED) => {
+                let check = CHECKS[slot.failed_check.load(Ordering::Relaxed)];
+                return Err(failure(check, slot.failed_vector.load(Ordering::Relaxed)));
+            }
+            Err(_) => hint::spin_loop(),
+        }
+    }
+}
+
+/// Finds the slot of the backend `B`, claiming a free one the first time,
+/// or returns `None` if every slot belongs to another backend.
+#[cfg(feature = "self-test")]
+fn slot_of<B: BigInt>() -> Option<&'static Slot> {
+    let mut hasher = Fnv1a(0xcbf2_9ce4_8422_2325);
+    TypeId::of::<B>().hash(&mut hasher);
+    // Zero marks a free slot, so keep it out of the keys.
+    let backend = hasher.finish() | 1;
+
+    SLOTS.iter().find(|slot| {
+        match slot
+            .backend
+            .compare_exchange(0, backend, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => true,
+            Err(owner) => owner == backend,
+        }
+    })
+}
+
+/// The 64-bit FNV-1a hash, which needs neither `std` nor a key.
+#[cfg(feature = "self-test")]
+struct Fnv1a(u64);
+
+#[cfg(feature = "self-test")]
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+fn check_mod_exp<B: BigInt>(vectors: &[(&str, &str, &str, &str)]) -> Result<()> {
+    for (vector, &(base, exponent, modulus, result)) in vectors.iter().enumerate() {
+        let numbers = (
+            decode::<B>(base),
+            decode::<B>(exponent),
+            decode::<B>(modulus),
+            decode::<B>(result),
+        );
+
+        if let (Some(base), Some(exponent), Some(modulus), Some(result)) = numbers {
+            if base.pow_mod(&exponent, &modulus) != result {
+                return Err(failure(Check::ModExp, vector));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that `primes` and `mersennes` are accepted, and `composites`
+/// rejected. Vectors are numbered in that order.
+fn check_is_prime<B: BigInt>(
+    primes: &[Vec<u8>],
+    mersennes: &[Vec<u8>],
+    composites: &[Vec<u8>],
+) -> Result<()> {
+    let mut rngesus = seeded();
+    let expected = primes
+        .iter()
+        .chain(mersennes)
+        .map(|bytes| (bytes, true))
+        .chain(composites.iter().map(|bytes| (bytes, false)));
+
+    for (vector, (bytes, prime)) in expected.enumerate() {
+        if let Some(number) = from_bytes::<B>(bytes) {
+            if is_prime_with_rng(&number, &mut rngesus) != prime {
+                return Err(failure(Check::IsPrime, vector));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that every one of `mersennes` passes Miller-Rabin to the first
+/// few prime bases, and that each of the `pseudoprimes` passes to the bases
+/// it fools and fails to the one that catches it. Vectors are numbered in
+/// that order.
+fn check_miller_rabin<B: BigInt>(
+    mersennes: &[Vec<u8>],
+    pseudoprimes: &[(&str, &[u32], u32)],
+) -> Result<()> {
+    for (vector, bytes) in mersennes.iter().enumerate() {
+        if let Some(number) = from_bytes::<B>(bytes) {
+            if !miller_rabin_with_bases(&number, &[2, 3, 5, 7, 11, 13]) {
+                return Err(failure(Check::MillerRabin, vector));
+            }
+        }
+    }
+
+    for (vector, &(hex, fooled, caught)) in pseudoprimes.iter().enumerate() {
+        if let Some(number) = decode::<B>(hex) {
+            if !miller_rabin_with_bases(&number, fooled)
+                || miller_rabin_with_bases(&number, &[caught])
+            {
+                return Err(failure(Check::MillerRabin, mersennes.len() + vector));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn check_generation<B: BigInt>(bit_length: usize, expected: &str) -> Result<()> {
+    match B::MAX_BITS {
+        Some(max) if bit_length > max => return Ok(()),
+        _ => {}
+    }
+
+    let mut rngesus = seeded();
+    let generated: B =
+        gen_prime_untested(bit_length, &mut rngesus, &mut GenerationReport::default())
+            .map_err(|_| failure(Check::Generation, 0))?;
+
+    if generated.to_be_bytes() != unhex(expected) {
+        return Err(failure(Check::Generation, 0));
+    }
+
+    Ok(())
+}
+
+fn failure(check: Check, vector: usize) -> Error {
+    Error::SelfTest { check, vector }
+}
+
+/// An HMAC_DRBG instantiated from the fixed seed material.
+fn seeded() -> HmacDrbg<Unseeded> {
+    HmacDrbg::from_entropy_input(ENTROPY_INPUT, NONCE, PERSONALIZATION, Unseeded)
+}
+
+/// Decodes `hex`, or returns `None` if it does not fit in `B`.
+fn decode<B: BigInt>(hex: &str) -> Option<B> {
+    from_bytes(&unhex(hex))
+}
+
+/// Decodes big-endian `bytes`, or returns `None` if they do not fit in `B`.
+fn from_bytes<B: BigInt>(bytes: &[u8]) -> Option<B> {
+    match B::MAX_BITS {
+        Some(max) if bytes_bit_length(bytes) > max => None,
+        _ => Some(B::from_be_bytes(bytes)),
+    }
+}
+
+/// Returns the big-endian bytes of `2^exponent - 1`.
+fn mersenne(exponent: usize) -> Vec<u8> {
+    let mut bytes = vec![0xff; exponent.div_ceil(8)];
+    bytes[0] >>= bytes.len() * 8 - exponent;
+
+    bytes
+}
+
+fn unhex(hex: &str) -> Vec<u8> {
+    let digits: Vec<u8> = hex
+        .bytes()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .map(|byte| (byte as char).to_digit(16).expect("hex digit") as u8)
+        .collect();
+
+    digits
+        .chunks(2)
+        .map(|pair| (pair[0] << 4) | pair[1])
+        .collect()
+}
+
+/// The entropy source of the seeded HMAC_DRBGs, which never reseed within a
+/// self-test.
+struct Unseeded;
+
+impl RngCore for Unseeded {
+    fn next_u32(&mut self) -> u32 {
+        unreachable!()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        unreachable!()
+    }
+
+    fn fill_bytes(&mut self, _dest: &mut [u8]) {
+        unreachable!()
+    }
+
+    fn try_fill_bytes(&mut self, _dest: &mut [u8]) -> ::core::result::Result<(), ::rand::Error> {
+        Err(::rand::Error::new(
+            ErrorKind::Unavailable,
+            "the self-tests never reseed",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(any(feature = "num-bigint", feature = "crypto-bigint"))]
+    use super::run;
+    use super::{
+        check_generation, check_is_prime, check_miller_rabin, check_mod_exp, mersenne, self_test,
+        unhex, Check, GENERATED_512,
+    };
+    use error::Error;
+    use ramp::Int;
+
+    #[test]
+    fn test_self_test() {
+        self_test().unwrap();
+        assert_eq!(mersenne(61), unhex("1fffffffffffffff"));
+    }
+
+    #[test]
+    #[cfg(feature = "num-bigint")]
+    fn test_self_test_num_bigint() {
+        run::<::num_bigint::BigUint>().unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "crypto-bigint")]
+    fn test_self_test_crypto_bigint() {
+        // Vectors wider than the type are skipped, rather than failing.
+        run::<::crypto_bigint::U256>().unwrap();
+        run::<::crypto_bigint::U1024>().unwrap();
+    }
+
+    #[test]
+    #[cfg(all(feature = "self-test", feature = "num-bigint"))]
+    fn test_self_test_per_backend() {
+        use super::{on_first_use, slot_of};
+        use core::ptr;
+
+        let ramp = slot_of::<Int>().unwrap();
+        let num_bigint = slot_of::<::num_bigint::BigUint>().unwrap();
+        assert!(!ptr::eq(ramp, num_bigint));
+        assert!(ptr::eq(ramp, slot_of::<Int>().unwrap()));
+
+        on_first_use::<Int>().unwrap();
+        on_first_use::<::num_bigint::BigUint>().unwrap();
+    }
+
+    #[test]
+    fn test_self_test_failures() {
+        let failed = |result| match result {
+            Err(Error::SelfTest { check, vector }) => (check, vector),
+            other => panic!("unexpected result: {:?}", other),
+        };
+
+        let wrong = &[("04", "0d", "01f1", "01bd"), ("04", "0d", "01f1", "01be")];
+        assert_eq!(failed(check_mod_exp::<Int>(wrong)), (Check::ModExp, 1));
+
+        let carmichael = vec![unhex("0231")];
+        assert_eq!(
+            failed(check_is_prime::<Int>(&carmichael, &[], &[])),
+            (Check::IsPrime, 0)
+        );
+        assert_eq!(
+            failed(check_is_prime::<Int>(
+                &[],
+                &[mersenne(127)],
+                &[mersenne(61)]
+            )),
+            (Check::IsPrime, 1)
+        );
+
+        let fooled: &[(&str, &[u32], u32)] = &[("bfa17dc7", &[2, 3, 5, 7, 11], 13)];
+        assert_eq!(
+            failed(check_miller_rabin::<Int>(&[mersenne(89)], fooled)),
+            (Check::MillerRabin, 1)
+        );
+
+        assert_eq!(
+            failed(check_generation::<Int>(1024, GENERATED_512)),
+            (Check::Generation, 0)
+        );
+
+        let err = Error::SelfTest {
+            check: Check::MillerRabin,
+            vector: 3,
+        };
+        assert_eq!(
+            err.to_string(),
+            "The miller_rabin self-test failed on vector 3"
+        );
+    }
+}
@@ -0,0 +1,559 @@
+//! Statistical tests of the distribution of generated primes.
+//!
+//! Every generator draws a fresh random candidate for each attempt, and
+//! forces its top two bits and its lowest bit to one. Its output should
+//! therefore be distributed like a prime chosen uniformly at random from
+//! `[3 * 2^(n-2), 2^n)`, where the density of primes around `x` is close to
+//! `1 / ln(x)`. A change to the sieve or the search strategy that skews this,
+//! as stepping from a random start to the next prime does towards primes
+//! that follow large gaps, would show up here.
+//!
+//! `analyze()` runs the following tests over a batch of primes:
+//!
+//! 1. A chi-square test of the residues modulo each small odd prime `q`,
+//!    which should be spread evenly over `1..q`.
+//!
+//! 2. The frequency of ones in each of the top eight and bottom eight bits,
+//!    against the expected frequency, with a two-sided normal
+//!    approximation of the binomial.
+//!
+//! 3. A Kolmogorov-Smirnov test of the position of each prime within its
+//!    interval against the ideal distribution described above.
+//!
+//! Every test yields a p-value: the probability of a result at least as
+//! extreme if the primes were ideal. `UniformityReport::passes()` combines
+//! them with a Bonferroni correction. A few hundred primes are enough to
+//! catch gross bias, but subtle bias needs many thousands.
+//!
+//! `sample()` generates the batch itself, which makes it easy to run as a
+//! test with a seeded `drbg::HmacDrbg`.
+
+use ramp::Int;
+
+use rand::Rng;
+
+use backend::BigInt;
+use common::{bytes_bit_length, bytes_rem, check_bit_length, gen_prime_unchecked};
+use error::{Error, Result};
+use report::GenerationReport;
+
+/// The smallest bit length that can be analyzed.
+pub const MIN_BIT_LENGTH: usize = 64;
+
+/// The number of bits examined at each end of every prime.
+const EDGE_BITS: usize = 8;
+
+/// The moduli of the residue tests.
+const RESIDUE_MODULI: [u32; 17] = [
+    3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61,
+];
+
+/// The smallest expected count per residue for the chi-square
+/// approximation to hold. Moduli with fewer are skipped.
+const MIN_EXPECTED_COUNT: usize = 5;
+
+/// The number of leading bits forced to one by the generators.
+const FORCED_TOP_BITS: usize = 2;
+
+/// The results of the statistical tests over a batch of primes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UniformityReport {
+    /// The number of primes analyzed.
+    pub samples: usize,
+
+    /// The bit length of every prime.
+    pub bit_length: usize,
+
+    /// The chi-square tests of the residues modulo small primes.
+    pub residues: Vec<ResidueTest>,
+
+    /// The frequencies of the top bits, most significant first.
+    pub top_bits: Vec<BitFrequency>,
+
+    /// The frequencies of the bottom bits, least significant first.
+    pub low_bits: Vec<BitFrequency>,
+
+    /// The Kolmogorov-Smirnov test against the ideal distribution.
+    pub distribution: DistributionTest,
+}
+
+/// A chi-square test of the residues of the primes modulo `modulus`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ResidueTest {
+    /// The small prime the residues were taken modulo.
+    pub modulus: u32,
+    /// The chi-square statistic.
+    pub chi_square: f64,
+    /// The degrees of freedom, `modulus - 2`.
+    pub degrees_of_freedom: usize,
+    /// The probability of a statistic at least this large.
+    pub p_value: f64,
+}
+
+/// How often a single bit is set.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BitFrequency {
+    /// The position of the bit, zero being the least significant.
+    pub bit: usize,
+    /// The number of primes with the bit set.
+    pub ones: usize,
+    /// The expected fraction of primes with the bit set.
+    pub expected: f64,
+    /// The probability of a count at least this far from the expected one.
+    pub p_value: f64,
+}
+
+/// A Kolmogorov-Smirnov test of where the primes fall within their interval.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DistributionTest {
+    /// The largest distance between the observed and ideal distribution
+    /// functions.
+    pub statistic: f64,
+    /// The probability of a distance at least this large.
+    pub p_value: f64,
+}
+
+impl UniformityReport {
+    /// Returns every p-value in the report.
+    pub fn p_values(&self) -> Vec<f64> {
+        self.residues
+            .iter()
+            .map(|test| test.p_value)
+            .chain(self.top_bits.iter().map(|bit| bit.p_value))
+            .chain(self.low_bits.iter().map(|bit| bit.p_value))
+            .chain(Some(self.distribution.p_value))
+            .collect()
+    }
+
+    /// Returns whether every test passes at the significance level `alpha`
+    /// for the report as a whole, which is divided evenly between the tests.
+    pub fn passes(&self, alpha: f64) -> bool {
+        let p_values = self.p_values();
+        let threshold = alpha / p_values.len() as f64;
+
+        p_values.iter().all(|&p_value| p_value >= threshold)
+    }
+}
+
+/// Generates `count` primes of `bit_length` bits from `rngesus`, with the
+/// same search as `prime::from_rng()`, and analyzes them.
+pub fn sample<R: Rng + ?Sized>(
+    count: usize,
+    bit_length: usize,
+    rngesus: &mut R,
+) -> Result<UniformityReport> {
+    check_bit_length(bit_length, MIN_BIT_LENGTH)?;
+
+    let mut report = GenerationReport::default();
+    let primes = (0..count)
+        .map(|_| gen_prime_unchecked::<Int, R>(bit_length, rngesus, &mut report))
+        .collect::<Result<Vec<Int>>>()?;
+
+    analyze(&primes, bit_length)
+}
+
+/// Runs the statistical tests over `primes`, each of which must have exactly
+/// `bit_length` bits.
+///
+/// Fails with `Error::BitLength` if `bit_length` is below `MIN_BIT_LENGTH`,
+/// and with `Error::InvalidParameter` if `primes` is empty or holds a number
+/// of the wrong size.
+pub fn analyze<B: BigInt>(primes: &[B], bit_length: usize) -> Result<UniformityReport> {
+    check_bit_length(bit_length, MIN_BIT_LENGTH)?;
+
+    if primes.is_empty() {
+        return Err(Error::InvalidParameter {
+            name: "primes",
+            reason: "must not be empty".to_string(),
+        });
+    }
+
+    let encoded: Vec<Vec<u8>> = primes.iter().map(BigInt::to_be_bytes).collect();
+    if let Some(bytes) = encoded
+        .iter()
+        .find(|bytes| bytes_bit_length(bytes) != bit_length)
+    {
+        return Err(Error::InvalidParameter {
+            name: "primes",
+            reason: format!(
+                "holds a number of {} bits rather than {}",
+                bytes_bit_length(bytes),
+                bit_length
+            ),
+        });
+    }
+
+    let ideal = Ideal::new(bit_length);
+
+    Ok(UniformityReport {
+        samples: primes.len(),
+        bit_length,
+        residues: RESIDUE_MODULI
+            .iter()
+            .filter(|&&modulus| primes.len() >= MIN_EXPECTED_COUNT * (modulus as usize - 1))
+            .map(|&modulus| residue_test(&encoded, modulus))
+            .collect(),
+        top_bits: (0..EDGE_BITS)
+            .map(|k| bit_frequency(&encoded, bit_length - 1 - k, ideal.top_bit(k)))
+            .collect(),
+        low_bits: (0..EDGE_BITS)
+            .map(|bit| bit_frequency(&encoded, bit, if bit == 0 { 1.0 } else { 0.5 }))
+            .collect(),
+        distribution: distribution_test(&encoded, bit_length, &ideal),
+    })
+}
+
+fn residue_test(primes: &[Vec<u8>], modulus: u32) -> ResidueTest {
+    let mut counts = vec![0_usize; modulus as usize];
+    for bytes in primes {
+        counts[bytes_rem(bytes, modulus) as usize] += 1;
+    }
+
+    // A prime above `modulus` is never divisible by it, so a count at zero
+    // counts against the result like any other.
+    let expected = primes.len() as f64 / f64::from(modulus - 1);
+    let chi_square = counts[1..]
+        .iter()
+        .map(|&count| (count as f64 - expected).powi(2) / expected)
+        .sum::<f64>()
+        + if counts[0] > 0 { f64::INFINITY } else { 0.0 };
+    let degrees_of_freedom = modulus as usize - 2;
+
+    ResidueTest {
+        modulus,
+        chi_square,
+        degrees_of_freedom,
+        p_value: chi_square_p_value(chi_square, degrees_of_freedom),
+    }
+}
+
+fn bit_frequency(primes: &[Vec<u8>], bit: usize, expected: f64) -> BitFrequency {
+    let ones = primes
+        .iter()
+        .filter(|bytes| (bytes[bytes.len() - 1 - bit / 8] >> (bit % 8)) & 1 == 1)
+        .count();
+    let samples = primes.len() as f64;
+
+    let p_value = if expected >= 1.0 {
+        if ones == primes.len() {
+            1.0
+        } else {
+            0.0
+        }
+    } else {
+        let deviation = (ones as f64 - samples * expected).abs();
+        let z = deviation / (samples * expected * (1.0 - expected)).sqrt();
+        gamma_q(0.5, z * z / 2.0)
+    };
+
+    BitFrequency {
+        bit,
+        ones,
+        expected,
+        p_value,
+    }
+}
+
+fn distribution_test(primes: &[Vec<u8>], bit_length: usize, ideal: &Ideal) -> DistributionTest {
+    let mut cdf: Vec<f64> = primes
+        .iter()
+        .map(|bytes| ideal.cdf(position(bytes, bit_length)))
+        .collect();
+    cdf.sort_by(|a, b| a.partial_cmp(b).expect("positions are finite"));
+
+    let samples = cdf.len() as f64;
+    let statistic = cdf
+        .iter()
+        .enumerate()
+        .map(|(i, &value)| {
+            let below = value - i as f64 / samples;
+            let above = (i + 1) as f64 / samples - value;
+            below.max(above)
+        })
+        .fold(0.0, f64::max);
+
+    let root = samples.sqrt();
+    let lambda = (root + 0.12 + 0.11 / root) * statistic;
+
+    DistributionTest {
+        statistic,
+        p_value: kolmogorov_p_value(lambda),
+    }
+}
+
+/// Returns `prime / 2^bit_length`, from its top 64 bits.
+fn position(bytes: &[u8], bit_length: usize) -> f64 {
+    bytes
+        .iter()
+        .take(8)
+        .enumerate()
+        .map(|(i, &byte)| {
+            let exponent = 8 * (bytes.len() - 1 - i) as i32 - bit_length as i32;
+            f64::from(byte) * 2_f64.powi(exponent)
+        })
+        .sum()
+}
+
+/// The distribution of a prime chosen uniformly from `[3 * 2^(n-2), 2^n)`,
+/// as a function of its position `t = prime / 2^n` in `[0.75, 1)`, with
+/// density proportional to `1 / ln(t * 2^n)`.
+struct Ideal {
+    ln_scale: f64,
+    total: f64,
+}
+
+impl Ideal {
+    const LOW: f64 = 0.75;
+
+    /// Simpson's rule intervals per unit of `t`. The density is nearly flat,
+    /// so few are needed.
+    const STEPS: f64 = 256.0;
+
+    fn new(bit_length: usize) -> Ideal {
+        let mut ideal = Ideal {
+            ln_scale: bit_length as f64 * 2_f64.ln(),
+            total: 1.0,
+        };
+        ideal.total = ideal.integral(1.0);
+
+        ideal
+    }
+
+    fn density(&self, t: f64) -> f64 {
+        1.0 / (self.ln_scale + t.ln())
+    }
+
+    /// Integrates the density from `LOW` to `t`.
+    fn integral(&self, t: f64) -> f64 {
+        let steps = (((t - Ideal::LOW) * Ideal::STEPS).ceil() as usize).max(1) * 2;
+        let width = (t - Ideal::LOW) / steps as f64;
+
+        let inner: f64 = (1..steps)
+            .map(|i| {
+                let weight = if i % 2 == 1 { 4.0 } else { 2.0 };
+                weight * self.density(Ideal::LOW + i as f64 * width)
+            })
+            .sum();
+
+        (self.density(Ideal::LOW) + inner + self.density(t)) * width / 3.0
+    }
+
+    fn cdf(&self, t: f64) -> f64 {
+        if t <= Ideal::LOW {
+            0.0
+        } else if t >= 1.0 {
+            1.0
+        } else {
+            self.integral(t) / self.total
+        }
+    }
+
+    /// The probability that the `k`-th bit from the top is set.
+    fn top_bit(&self, k: usize) -> f64 {
+        if k < FORCED_TOP_BITS {
+            return 1.0;
+        }
+
+        // The bit is set on every other interval of width 2^-(k + 1).
+        let intervals = 1_usize << (k + 1);
+        let width = 1.0 / intervals as f64;
+
+        (intervals * 3 / 4..intervals)
+            .filter(|interval| interval % 2 == 1)
+            .map(|interval| {
+                let start = interval as f64 * width;
+                self.cdf(start + width) - self.cdf(start)
+            })
+            .sum()
+    }
+}
+
+/// Returns the probability that a chi-square variable with
+/// `degrees_of_freedom` is at least `chi_square`.
+fn chi_square_p_value(chi_square: f64, degrees_of_freedom: usize) -> f64 {
+    if chi_square.is_infinite() {
+        return 0.0;
+    }
+
+    gamma_q(degrees_of_freedom as f64 / 2.0, chi_square / 2.0)
+}
+
+/// Returns the probability that the Kolmogorov distribution exceeds
+/// `lambda`, from its alternating series.
+fn kolmogorov_p_value(lambda: f64) -> f64 {
+    let exponent = -2.0 * lambda * lambda;
+    let mut sign = 2.0;
+    let mut sum = 0.0;
+    let mut previous = 0.0;
+
+    for j in 1..=100 {
+        let term = sign * (exponent * f64::from(j * j)).exp();
+        sum += term;
+
+        if term.abs() <= 0.001 * previous || term.abs() <= 1e-8 * sum {
+            return sum.clamp(0.0, 1.0);
+        }
+
+        sign = -sign;
+        previous = term.abs();
+    }
+
+    // The series has not converged, which only happens as lambda nears zero.
+    1.0
+}
+
+/// Returns the regularized upper incomplete gamma function `Q(a, x)`, by its
+/// series below `a + 1` and its continued fraction above, as in Numerical
+/// Recipes.
+fn gamma_q(a: f64, x: f64) -> f64 {
+    const EPSILON: f64 = 1e-14;
+    const TINY: f64 = 1e-300;
+
+    if x <= 0.0 {
+        return 1.0;
+    }
+
+    let scale = (-x + a * x.ln() - ln_gamma(a)).exp();
+
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        let mut n = a;
+        for _ in 0..1000 {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * EPSILON {
+                break;
+            }
+        }
+
+        return (1.0 - sum * scale).max(0.0);
+    }
+
+    // Lentz's method.
+    let mut b = x + 1.0 - a;
+    let mut c = 1.0 / TINY;
+    let mut d = 1.0 / b;
+    let mut fraction = d;
+    for i in 1..1000 {
+        let an = -f64::from(i) * (f64::from(i) - a);
+        b += 2.0;
+
+        d = an * d + b;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = b + an / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+
+        d = 1.0 / d;
+        let delta = d * c;
+        fraction *= delta;
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+
+    (fraction * scale).min(1.0)
+}
+
+/// Returns `ln(Gamma(x))` for `x > 0`, by the Lanczos approximation.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.120_865_097_386_617_9e-2,
+        -0.539_523_938_495_3e-5,
+    ];
+
+    let mut tmp = x + 5.5;
+    tmp -= (x + 0.5) * tmp.ln();
+
+    let mut series = 1.000_000_000_190_015;
+    let mut y = x;
+    for coefficient in COEFFICIENTS.iter() {
+        y += 1.0;
+        series += coefficient / y;
+    }
+
+    -tmp + (2.506_628_274_631_000_5 * series / x).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{analyze, chi_square_p_value, gamma_q, kolmogorov_p_value, sample, Ideal};
+    use drbg::HmacDrbg;
+    use error::Error;
+    use ramp::Int;
+    use rand::rngs::OsRng;
+
+    fn seeded() -> HmacDrbg<OsRng> {
+        HmacDrbg::from_entropy_input(
+            &[0x42; 32],
+            &[0x24; 16],
+            b"statistics",
+            OsRng::new().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_statistics_functions() {
+        assert!((chi_square_p_value(3.841_459, 1) - 0.05).abs() < 1e-6);
+        assert!((chi_square_p_value(18.307_038, 10) - 0.05).abs() < 1e-6);
+        assert!((gamma_q(0.5, 1.96 * 1.96 / 2.0) - 0.049_995_8).abs() < 1e-6);
+        assert!((kolmogorov_p_value(1.358_099) - 0.05).abs() < 1e-4);
+
+        let ideal = Ideal::new(512);
+        assert!((ideal.cdf(0.875) - 0.5).abs() < 0.01);
+        assert!((ideal.top_bit(2) - 0.5).abs() < 0.01);
+        assert!(ideal.top_bit(2) < 0.5);
+    }
+
+    #[test]
+    fn test_statistics_uniform() {
+        let report = sample(300, 128, &mut seeded()).unwrap();
+
+        assert_eq!(report.samples, 300);
+        assert_eq!(report.residues.len(), 17);
+        assert_eq!(report.top_bits[0].ones, 300);
+        assert_eq!(report.low_bits[0].ones, 300);
+        assert!(report.passes(0.001), "{:?}", report);
+    }
+
+    #[test]
+    fn test_statistics_detects_bias() {
+        let mut rngesus = seeded();
+        let mut primes = Vec::new();
+        while primes.len() < 200 {
+            let p: Int = ::common::gen_prime_unchecked(
+                96,
+                &mut rngesus,
+                &mut ::report::GenerationReport::default(),
+            )
+            .unwrap();
+
+            // Keep only primes that are 1 mod 3.
+            if u64::from(&(&p % 3_usize)) == 1 {
+                primes.push(p);
+            }
+        }
+
+        let report = analyze(&primes, 96).unwrap();
+        assert!(!report.passes(0.001));
+        assert_eq!(report.residues[0].modulus, 3);
+        assert!(report.residues[0].p_value < 1e-20);
+
+        // Primes of one bit too few fall outside the ideal interval.
+        primes.truncate(20);
+        match analyze(&primes, 97) {
+            Err(Error::InvalidParameter { name, .. }) => assert_eq!(name, "primes"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(analyze::<Int>(&[], 96).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+//! Generates strong primes with Gordon's algorithm, as ANSI X9.31 and some
+//! older standards require for RSA.
+//!
+//! A strong prime `p` has a large prime factor `r` of `p - 1`, a large
+//! prime factor `s` of `p + 1`, and `r - 1` has a large prime factor `t`.
+//! Gordon's algorithm draws `s` and `t`, searches `r = 2 i t + 1` for a
+//! prime, and sets `p0 = 2 (s^(r - 2) mod r) s - 1`, which is `1` modulo
+//! `r` and `-1` modulo `s`. `p` is then the first probable prime of the
+//! form `p0 + 2 j r s` from a random starting point.
+
+use ramp::Int;
+
+use rand::Rng;
+
+use backend::BigInt;
+use common::{
+    check_bit_length, gen_prime_unchecked, is_prime, is_prime_with_rng, random_bits, MIN_BIT_LENGTH,
+};
+use entropy;
+use error::Result;
+use report::GenerationReport;
+
+/// The length that every auxiliary prime must reach, which is what X9.31
+/// asks for: more than 100 bits.
+pub const MIN_FACTOR_BITS: usize = 101;
+
+/// The bits that `s` and `t` fall short of half the length of `p`, which
+/// leaves room for the searches for `r` and `p`.
+const SHORTFALL_BITS: usize = 32;
+
+/// The length of the starting multiplier `i` of `t`.
+const MULTIPLIER_BITS: usize = 16;
+
+/// The auxiliary primes of a strong prime `p`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Factors {
+    /// A prime factor of `p - 1`.
+    pub r: Int,
+    /// A prime factor of `p + 1`.
+    pub s: Int,
+    /// A prime factor of `r - 1`.
+    pub t: Int,
+}
+
+/// A strong prime along with the auxiliary primes that make it one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StrongPrime {
+    /// The prime itself.
+    pub prime: Int,
+    /// Its auxiliary primes.
+    pub factors: Factors,
+}
+
+/// Constructs a new strong prime with a size of `bit_length` bits.
+///
+/// This will initialize an `OsRng` instance, checked by the health tests
+/// of the `entropy` module, and call the `from_rng()` function.
+///
+/// Note: the `bit_length` MUST be at least 512-bits.
+pub fn new(bit_length: usize) -> Result<StrongPrime> {
+    check_bit_length(bit_length, MIN_BIT_LENGTH)?;
+
+    let mut rngesus = entropy::os()?;
+    from_rng(bit_length, &mut rngesus)
+}
+
+/// Constructs a new strong prime with a size of `bit_length` bits, sourced
+/// from an already-initialized random number generator.
+///
+/// `s` and `t` are 32 bits short of half of `bit_length`, and `r` is about
+/// 16 bits longer than `t`.
+pub fn from_rng<R: Rng + ?Sized>(bit_length: usize, rngesus: &mut R) -> Result<StrongPrime> {
+    check_bit_length(bit_length, MIN_BIT_LENGTH)?;
+
+    #[cfg(feature = "tracing")]
+    let _span = debug_span!("strong_prime", bit_length = bit_length as u64).entered();
+
+    let mut report = GenerationReport::default();
+    let factor_bits = bit_length / 2 - SHORTFALL_BITS;
+    let s: Int = gen_prime_unchecked(factor_bits, rngesus, &mut report)?;
+    let t: Int = gen_prime_unchecked(factor_bits, rngesus, &mut report)?;
+
+    // r = 2 i t + 1, from a random i of MULTIPLIER_BITS bits.
+    let mut i: Int = random_bits(rngesus, MULTIPLIER_BITS)?;
+    i.set_bit(MULTIPLIER_BITS as u32 - 1, true);
+    let twice_t: Int = &t << 1;
+    let mut r = &twice_t * &i + 1_usize;
+    while !is_prime_with_rng(&r, rngesus) {
+        r += &twice_t;
+    }
+
+    // p0 = 1 (mod r) and p0 = -1 (mod s), as s^(r - 2) inverts s modulo r.
+    let step = (&r * &s) << 1;
+    let p0 = ((s.pow_mod(&(&r - 2_usize), &r) * &s) << 1) - 1_usize;
+
+    loop {
+        let mut start: Int = random_bits(rngesus, bit_length)?;
+        start.set_bit(bit_length as u32 - 1, true);
+
+        let mut offset = (&p0 - &start) % &step;
+        if offset < Int::zero() {
+            offset += &step;
+        }
+        let mut candidate = start + offset;
+
+        while candidate.bit_length() as usize == bit_length {
+            if is_prime_with_rng(&candidate, rngesus) {
+                return Ok(StrongPrime {
+                    prime: candidate,
+                    factors: Factors { r, s, t },
+                });
+            }
+            candidate += &step;
+        }
+
+        candidate.wipe();
+    }
+}
+
+/// Checks that `candidate` is a strong prime, with the auxiliary primes
+/// given by `hint`: `r` divides `candidate - 1`, `s` divides
+/// `candidate + 1`, `t` divides `r - 1`, and all of them are probable
+/// primes of at least `MIN_FACTOR_BITS` bits.
+pub fn is_strong_prime(candidate: &Int, hint: &Factors) -> bool {
+    let Factors {
+        ref r,
+        ref s,
+        ref t,
+    } = *hint;
+    let divides = |factor: &Int, multiple: Int| multiple % factor == 0_i32;
+
+    [r, s, t]
+        .iter()
+        .all(|factor| factor.bit_length() as usize >= MIN_FACTOR_BITS)
+        && *candidate > 2_i32
+        && divides(r, candidate - 1_usize)
+        && divides(s, candidate + 1_usize)
+        && divides(t, r - 1_usize)
+        && [r, s, t, candidate].iter().all(|&prime| is_prime(prime))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_rng, is_strong_prime, new, Factors};
+    use common::is_prime;
+    use error::Error;
+    use ramp::Int;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_strong_prime() {
+        let strong = from_rng(512, &mut thread_rng()).unwrap();
+        let Factors {
+            ref r,
+            ref s,
+            ref t,
+        } = strong.factors;
+        let p = &strong.prime;
+
+        assert_eq!(p.bit_length(), 512);
+        assert_eq!((s.bit_length(), t.bit_length()), (224, 224));
+        assert!(is_prime(p) && is_prime(r) && is_prime(s) && is_prime(t));
+        assert_eq!((p - 1_usize) % r, Int::zero());
+        assert_eq!((p + 1_usize) % s, Int::zero());
+        assert_eq!((r - 1_usize) % t, Int::zero());
+        assert!(is_strong_prime(p, &strong.factors));
+
+        // Swapping r and s breaks both divisibilities.
+        let swapped = Factors {
+            r: s.clone(),
+            s: r.clone(),
+            t: t.clone(),
+        };
+        assert!(!is_strong_prime(p, &swapped));
+
+        // t divides r - 1, but is not a factor of p + 1.
+        let wrong = Factors {
+            s: t.clone(),
+            ..strong.factors.clone()
+        };
+        assert!(!is_strong_prime(p, &wrong));
+
+        // Another r does not divide p - 1, and p + 2 is not prime.
+        let other_r = Factors {
+            r: (r << 1) + 1_usize,
+            ..strong.factors.clone()
+        };
+        assert!(!is_strong_prime(p, &other_r));
+        assert!(!is_strong_prime(&(p + 2_usize), &strong.factors));
+
+        // Auxiliary primes must be large.
+        let small = Factors {
+            t: Int::from(2),
+            ..strong.factors.clone()
+        };
+        assert!(!is_strong_prime(p, &small));
+    }
+
+    #[test]
+    fn test_strong_prime_bit_length_too_small() {
+        match new(511) {
+            Err(Error::BitLength { length, minimum }) => assert_eq!((length, minimum), (511, 512)),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}